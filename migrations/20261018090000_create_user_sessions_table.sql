@@ -0,0 +1,13 @@
+CREATE TABLE user_sessions (
+    user_session_id uuid NOT NULL,
+    session_id TEXT NOT NULL UNIQUE,
+    user_id uuid NOT NULL
+        REFERENCES users (user_id),
+    ip_address TEXT NULL,
+    user_agent TEXT NULL,
+    created_at timestamptz NOT NULL,
+    last_seen_at timestamptz NOT NULL,
+    PRIMARY KEY (user_session_id)
+);
+
+CREATE INDEX user_sessions_user_id_idx ON user_sessions (user_id);
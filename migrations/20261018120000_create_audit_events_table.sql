@@ -0,0 +1,24 @@
+-- Actors are not a foreign key, so that the log outlives removed users.
+CREATE TABLE audit_events (
+    audit_event_id uuid NOT NULL,
+    occurred_at timestamptz NOT NULL,
+    event_type TEXT NOT NULL,
+    actor_user_id uuid NULL,
+    request_id TEXT NULL,
+    ip_address TEXT NULL,
+    payload JSONB NOT NULL,
+    PRIMARY KEY (audit_event_id)
+);
+
+CREATE INDEX audit_events_occurred_at_idx ON audit_events (occurred_at);
+CREATE INDEX audit_events_actor_user_id_idx ON audit_events (actor_user_id);
+
+CREATE FUNCTION reject_audit_event_changes() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_events is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_events_append_only
+    BEFORE UPDATE OR DELETE ON audit_events
+    FOR EACH ROW EXECUTE FUNCTION reject_audit_event_changes();
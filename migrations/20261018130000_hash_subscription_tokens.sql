@@ -0,0 +1,9 @@
+-- Subscription tokens are stored as HMAC-SHA256 hashes keyed with the
+-- application secret. The secret is not available to migrations, so tokens
+-- issued before this migration keep their plaintext value until the
+-- application hashes them on startup and clears the plaintext column.
+ALTER TABLE subscription_tokens DROP CONSTRAINT subscription_tokens_pkey;
+ALTER TABLE subscription_tokens ALTER COLUMN subscription_token DROP NOT NULL;
+ALTER TABLE subscription_tokens ADD COLUMN subscription_token_hash TEXT UNIQUE;
+ALTER TABLE subscription_tokens ADD CONSTRAINT subscription_tokens_token_present
+    CHECK (subscription_token IS NOT NULL OR subscription_token_hash IS NOT NULL);
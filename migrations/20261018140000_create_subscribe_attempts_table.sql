@@ -0,0 +1,11 @@
+CREATE TABLE subscribe_attempts(
+    attempted_at timestamptz NOT NULL,
+    ip_address TEXT,
+    email TEXT NOT NULL
+);
+CREATE INDEX subscribe_attempts_ip_address_idx ON subscribe_attempts (ip_address, attempted_at);
+CREATE INDEX subscribe_attempts_email_idx ON subscribe_attempts (email, attempted_at);
+
+-- Every confirmation email carries a fresh token, so counting recent tokens
+-- caps the confirmation emails sent to a single address.
+ALTER TABLE subscription_tokens ADD COLUMN created_at timestamptz NOT NULL DEFAULT now();
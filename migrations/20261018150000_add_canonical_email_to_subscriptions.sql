@@ -0,0 +1,35 @@
+-- Subscribers are identified by the canonical form of their email. Existing
+-- rows get the canonical form of the default rules, a lowercased address.
+ALTER TABLE subscriptions ADD COLUMN canonical_email TEXT;
+UPDATE subscriptions SET canonical_email = lower(email);
+
+-- Merge subscribers whose addresses differ only in case, keeping a confirmed
+-- subscription over a pending one, and the oldest one among those.
+CREATE TABLE merged_subscriptions AS
+SELECT id, email
+FROM (
+    SELECT
+        id,
+        email,
+        row_number() OVER (
+            PARTITION BY canonical_email
+            ORDER BY status = 'confirmed' DESC, subscribed_at, id
+        ) AS rank
+    FROM subscriptions
+) ranked
+WHERE rank > 1;
+
+DELETE FROM subscription_tokens
+WHERE subscriber_id IN (SELECT id FROM merged_subscriptions);
+DELETE FROM issue_delivery_queue
+WHERE subscriber_email IN (SELECT email FROM merged_subscriptions);
+DELETE FROM subscriptions
+WHERE id IN (SELECT id FROM merged_subscriptions);
+DROP TABLE merged_subscriptions;
+
+ALTER TABLE subscriptions ALTER COLUMN canonical_email SET NOT NULL;
+CREATE UNIQUE INDEX subscriptions_canonical_email_idx ON subscriptions (canonical_email);
+
+-- Deliveries are queued per subscriber, the address is looked up when sending.
+ALTER TABLE issue_delivery_queue RENAME COLUMN subscriber_email TO canonical_email;
+UPDATE issue_delivery_queue SET canonical_email = lower(canonical_email);
@@ -0,0 +1,7 @@
+ALTER TABLE subscriptions
+    ADD COLUMN content_format TEXT NOT NULL DEFAULT 'html'
+        CHECK (content_format IN ('html', 'plain_text')),
+    ADD COLUMN digest_frequency TEXT NOT NULL DEFAULT 'immediate'
+        CHECK (digest_frequency IN ('immediate', 'daily', 'weekly')),
+    ADD COLUMN paused_until TIMESTAMPTZ NULL,
+    ADD COLUMN last_digest_sent_at TIMESTAMPTZ NULL;
@@ -0,0 +1,12 @@
+-- Address changes waiting for the subscriber to confirm the new address.
+CREATE TABLE email_changes (
+    subscription_token_hash TEXT NOT NULL,
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    new_email TEXT NOT NULL,
+    new_canonical_email TEXT NOT NULL,
+    requested_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (subscription_token_hash)
+);
+
+CREATE INDEX email_changes_subscriber_id_idx ON email_changes (subscriber_id);
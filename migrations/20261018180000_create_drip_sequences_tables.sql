@@ -0,0 +1,40 @@
+-- Onboarding emails sent to subscribers after they confirm, each step a
+-- number of days after the subscriber entered the sequence.
+CREATE TABLE drip_sequences (
+    drip_sequence_id uuid NOT NULL,
+    name TEXT NOT NULL,
+    active BOOLEAN NOT NULL DEFAULT false,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (drip_sequence_id)
+);
+
+CREATE TABLE drip_steps (
+    drip_sequence_id uuid NOT NULL
+        REFERENCES drip_sequences (drip_sequence_id) ON DELETE CASCADE,
+    position INT NOT NULL CHECK (position >= 0),
+    delay_days INT NOT NULL CHECK (delay_days >= 0),
+    title TEXT NOT NULL,
+    text_content TEXT NOT NULL,
+    html_content TEXT NOT NULL,
+    PRIMARY KEY (drip_sequence_id, position)
+);
+
+-- Progress of a subscriber through a sequence. `next_send_at` is cleared
+-- once the sequence is completed or stopped.
+CREATE TABLE drip_enrollments (
+    drip_sequence_id uuid NOT NULL
+        REFERENCES drip_sequences (drip_sequence_id) ON DELETE CASCADE,
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    enrolled_at timestamptz NOT NULL,
+    next_position INT NOT NULL DEFAULT 0,
+    next_send_at timestamptz NULL,
+    last_sent_at timestamptz NULL,
+    completed_at timestamptz NULL,
+    stopped_at timestamptz NULL,
+    PRIMARY KEY (drip_sequence_id, subscriber_id)
+);
+
+CREATE INDEX drip_enrollments_next_send_at_idx
+    ON drip_enrollments (next_send_at)
+    WHERE next_send_at IS NOT NULL;
@@ -0,0 +1,15 @@
+CREATE TABLE email_events (
+    email_event_id uuid NOT NULL PRIMARY KEY,
+    provider_event_id TEXT NULL UNIQUE,
+    provider_message_id TEXT NULL,
+    subscriber_id uuid NULL REFERENCES subscriptions (id) ON DELETE SET NULL,
+    email TEXT NOT NULL,
+    event_type TEXT NOT NULL CHECK (
+        event_type IN ('hard_bounce', 'soft_bounce', 'spam_complaint', 'subscription_change')
+    ),
+    details TEXT NULL,
+    occurred_at timestamptz NOT NULL,
+    received_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX email_events_subscriber_id_idx ON email_events (subscriber_id, occurred_at);
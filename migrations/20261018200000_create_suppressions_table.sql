@@ -0,0 +1,11 @@
+CREATE TABLE suppressions (
+    suppression_id uuid NOT NULL PRIMARY KEY,
+    kind TEXT NOT NULL CHECK (kind IN ('email', 'domain')),
+    value TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    source TEXT NOT NULL CHECK (source IN ('admin', 'csv_import')),
+    created_at timestamptz NOT NULL,
+    skipped_sends INT NOT NULL DEFAULT 0,
+    last_skipped_at timestamptz NULL,
+    UNIQUE (kind, value)
+);
@@ -0,0 +1,15 @@
+ALTER TABLE newsletter_issues
+    ADD COLUMN track_opens BOOLEAN NOT NULL DEFAULT false,
+    ADD COLUMN track_clicks BOOLEAN NOT NULL DEFAULT false;
+
+CREATE TABLE issue_tracking_events (
+    newsletter_issue_id uuid NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id) ON DELETE CASCADE,
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    event_type TEXT NOT NULL CHECK (event_type IN ('open', 'click')),
+    link_url TEXT NULL,
+    occurred_at timestamptz NOT NULL
+);
+
+CREATE INDEX issue_tracking_events_issue_idx
+    ON issue_tracking_events (newsletter_issue_id, event_type);
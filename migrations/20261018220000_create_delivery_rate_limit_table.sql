@@ -0,0 +1,12 @@
+-- A single row holding the token buckets shared by all workers.
+CREATE TABLE delivery_rate_limit (
+    id BOOLEAN PRIMARY KEY DEFAULT true CHECK (id),
+    second_tokens DOUBLE PRECISION NOT NULL,
+    hour_tokens DOUBLE PRECISION NOT NULL,
+    refilled_at timestamptz NOT NULL,
+    paused_until timestamptz NULL
+);
+
+-- Refilled in full on first use.
+INSERT INTO delivery_rate_limit (second_tokens, hour_tokens, refilled_at)
+VALUES (0, 0, 'epoch');
@@ -0,0 +1,7 @@
+-- Form tokens and proof of work challenges already submitted, so that a form
+-- filled in once cannot be replayed. Kept until the token would expire anyway.
+CREATE TABLE used_form_tokens(
+    token TEXT PRIMARY KEY,
+    expires_at timestamptz NOT NULL
+);
+CREATE INDEX used_form_tokens_expires_at_idx ON used_form_tokens (expires_at);
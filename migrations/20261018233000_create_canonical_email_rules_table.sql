@@ -0,0 +1,7 @@
+-- Local part rules the canonical emails of subscribers were last computed
+-- with, so that they are recomputed on startup once the rules change. Starts
+-- empty, as the backfill of the canonical emails could only lowercase them.
+CREATE TABLE canonical_email_rules(
+    rules TEXT NOT NULL
+);
+INSERT INTO canonical_email_rules (rules) VALUES ('');
@@ -0,0 +1,3 @@
+-- Failed attempts at sending the next step, which is retried a few times
+-- before being skipped.
+ALTER TABLE drip_enrollments ADD COLUMN failed_attempts INT NOT NULL DEFAULT 0;
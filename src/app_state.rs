@@ -1,14 +1,44 @@
-use crate::email_client::EmailClient;
+use crate::{
+    authentication::{password::PasswordHashing, password_policy::PasswordPolicy},
+    domain::LocalPartRules,
+    email_client::EmailClient,
+    email_events::{SuppressionThresholds, WebhookCredentials},
+    email_verification::EmailVerifier,
+    routes::health_check::CachedHealth,
+    subscribe_protection::SubscribeProtection,
+};
 use axum::{extract::FromRef, http::Uri};
+use secrecy::Secret;
 use sqlx::PgPool;
+use std::time::Duration;
 use tower_sessions::cookie::Key;
+use tower_sessions_redis_store::{fred::clients::RedisPool, RedisStore};
 
 #[derive(Clone)]
 pub struct AppState {
     pub db_pool: PgPool,
     pub email_client: EmailClient,
+    pub email_verifier: EmailVerifier,
+    pub local_part_rules: LocalPartRules,
     pub base_url: Uri,
     pub hmac_secret: Key,
+    /// Raw application secret, keying the hashes of stored tokens.
+    pub token_secret: Secret<String>,
+    pub session_store: RedisStore<RedisPool>,
+    /// The pool behind `session_store`, for checking that Redis is up.
+    pub redis_pool: RedisPool,
+    pub password_hashing: PasswordHashing,
+    pub password_policy: PasswordPolicy,
+    pub subscribe_protection: SubscribeProtection,
+    pub webhook_credentials: WebhookCredentials,
+    pub suppression_thresholds: SuppressionThresholds,
+    /// Issues can only be tracked, and tracking recorded, while it is enabled.
+    pub tracking_enabled: bool,
+    /// How long each dependency gets to respond to a readiness check.
+    pub health_check_timeout: Duration,
+    /// Readiness also requires the email provider to accept our token.
+    pub check_email_provider: bool,
+    pub email_provider_health: CachedHealth,
 }
 
 impl FromRef<AppState> for Key {
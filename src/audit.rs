@@ -0,0 +1,205 @@
+use crate::client_info::ClientInfo;
+use anyhow::Context;
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{request::Parts, HeaderName},
+};
+use serde_json::Value;
+use sqlx::{PgExecutor, PgPool};
+use std::{convert::Infallible, fmt, str::FromStr};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuditEventType {
+    LoginSucceeded,
+    LoginFailed,
+    LoggedOut,
+    PasswordChanged,
+    NewsletterPublished,
+    SubscriberAdded,
+    SubscriberRemoved,
+    SubscriberEmailChangeRequested,
+}
+
+impl AuditEventType {
+    pub const ALL: [AuditEventType; 8] = [
+        AuditEventType::LoginSucceeded,
+        AuditEventType::LoginFailed,
+        AuditEventType::LoggedOut,
+        AuditEventType::PasswordChanged,
+        AuditEventType::NewsletterPublished,
+        AuditEventType::SubscriberAdded,
+        AuditEventType::SubscriberRemoved,
+        AuditEventType::SubscriberEmailChangeRequested,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditEventType::LoginSucceeded => "login_succeeded",
+            AuditEventType::LoginFailed => "login_failed",
+            AuditEventType::LoggedOut => "logged_out",
+            AuditEventType::PasswordChanged => "password_changed",
+            AuditEventType::NewsletterPublished => "newsletter_published",
+            AuditEventType::SubscriberAdded => "subscriber_added",
+            AuditEventType::SubscriberRemoved => "subscriber_removed",
+            AuditEventType::SubscriberEmailChangeRequested => "subscriber_email_change_requested",
+        }
+    }
+}
+
+impl fmt::Display for AuditEventType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for AuditEventType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        AuditEventType::ALL
+            .into_iter()
+            .find(|event_type| event_type.as_str() == s)
+            .ok_or_else(|| anyhow::anyhow!("Unknown audit event type `{s}`"))
+    }
+}
+
+/// Request details recorded with every audit event.
+#[derive(Clone, Debug)]
+pub struct AuditContext {
+    pub request_id: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuditContext
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let ClientInfo { ip_address, .. } = ClientInfo::from_request_parts(parts, state).await?;
+
+        // Set by `RequestUuid` before the request reaches any handler.
+        let request_id = parts
+            .headers
+            .get(HeaderName::from_static("x-request-id"))
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+
+        Ok(Self {
+            request_id,
+            ip_address,
+        })
+    }
+}
+
+#[tracing::instrument(skip(executor, context, payload))]
+pub async fn record_audit_event<'e>(
+    executor: impl PgExecutor<'e>,
+    context: &AuditContext,
+    actor_user_id: Option<Uuid>,
+    event_type: AuditEventType,
+    payload: Value,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO audit_events (
+            audit_event_id,
+            occurred_at,
+            event_type,
+            actor_user_id,
+            request_id,
+            ip_address,
+            payload
+        )
+        VALUES ($1, now(), $2, $3, $4, $5, $6)
+        "#,
+        Uuid::new_v4(),
+        event_type.as_str(),
+        actor_user_id,
+        context.request_id,
+        context.ip_address,
+        payload,
+    )
+    .execute(executor)
+    .await
+    .context("Failed to record audit event")?;
+
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+pub struct AuditEventFilter {
+    pub event_type: Option<AuditEventType>,
+    pub actor_username: Option<String>,
+    pub since: Option<OffsetDateTime>,
+    pub until: Option<OffsetDateTime>,
+}
+
+/// Lists audit events matching a filter, newest first.
+#[tracing::instrument(skip(db_pool))]
+pub async fn list_audit_events(
+    db_pool: &PgPool,
+    filter: &AuditEventFilter,
+    limit: Option<i64>,
+) -> Result<Vec<AuditEvent>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT
+            e.audit_event_id,
+            e.occurred_at,
+            e.event_type,
+            e.actor_user_id,
+            u.username AS "actor_username?",
+            e.request_id,
+            e.ip_address,
+            e.payload
+        FROM audit_events e
+        LEFT JOIN users u ON u.user_id = e.actor_user_id
+        WHERE
+            ($1::TEXT IS NULL OR e.event_type = $1) AND
+            ($2::TEXT IS NULL OR u.username = $2) AND
+            ($3::timestamptz IS NULL OR e.occurred_at >= $3) AND
+            ($4::timestamptz IS NULL OR e.occurred_at < $4)
+        ORDER BY e.occurred_at DESC
+        LIMIT $5
+        "#,
+        filter.event_type.map(|event_type| event_type.as_str()),
+        filter.actor_username,
+        filter.since,
+        filter.until,
+        limit,
+    )
+    .fetch_all(db_pool)
+    .await
+    .context("Failed to retrieve audit events")?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| AuditEvent {
+            audit_event_id: row.audit_event_id,
+            occurred_at: row.occurred_at,
+            event_type: row.event_type,
+            actor_user_id: row.actor_user_id,
+            actor_username: row.actor_username,
+            request_id: row.request_id,
+            ip_address: row.ip_address,
+            payload: row.payload,
+        })
+        .collect())
+}
+
+pub struct AuditEvent {
+    pub audit_event_id: Uuid,
+    pub occurred_at: OffsetDateTime,
+    pub event_type: String,
+    pub actor_user_id: Option<Uuid>,
+    pub actor_username: Option<String>,
+    pub request_id: Option<String>,
+    pub ip_address: Option<String>,
+    pub payload: Value,
+}
@@ -0,0 +1,240 @@
+use anyhow::Context;
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::{fmt, str::FromStr};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+const TOKEN_PREFIX: &str = "z2p_";
+const TOKEN_RANDOM_LENGTH: usize = 40;
+const DISPLAYED_PREFIX_LENGTH: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ApiScope {
+    IssuesRead,
+    IssuesWrite,
+    SubscribersRead,
+    SubscribersWrite,
+}
+
+impl ApiScope {
+    pub const ALL: [ApiScope; 4] = [
+        ApiScope::IssuesRead,
+        ApiScope::IssuesWrite,
+        ApiScope::SubscribersRead,
+        ApiScope::SubscribersWrite,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiScope::IssuesRead => "issues:read",
+            ApiScope::IssuesWrite => "issues:write",
+            ApiScope::SubscribersRead => "subscribers:read",
+            ApiScope::SubscribersWrite => "subscribers:write",
+        }
+    }
+}
+
+impl fmt::Display for ApiScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ApiScope {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ApiScope::ALL
+            .into_iter()
+            .find(|scope| scope.as_str() == s)
+            .ok_or_else(|| anyhow::anyhow!("Unknown API scope `{s}`"))
+    }
+}
+
+/// Creates a new API token for a given user. The plaintext token is returned
+/// only once - the database keeps its SHA-256 hash, which is enough for
+/// randomly generated tokens that cannot be guessed from a dictionary.
+#[tracing::instrument(skip(db_pool, name))]
+pub async fn create_api_token(
+    db_pool: &PgPool,
+    user_id: Uuid,
+    name: &str,
+    scopes: &[ApiScope],
+) -> Result<Secret<String>, anyhow::Error> {
+    let token = generate_token();
+    let token_prefix = &token.expose_secret()[..DISPLAYED_PREFIX_LENGTH];
+    let scopes = scopes.iter().map(ApiScope::to_string).collect::<Vec<_>>();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO api_tokens (
+            api_token_id,
+            user_id,
+            name,
+            token_prefix,
+            token_hash,
+            scopes,
+            created_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, now())
+        "#,
+        Uuid::new_v4(),
+        user_id,
+        name,
+        token_prefix,
+        hash_token(&token),
+        &scopes,
+    )
+    .execute(db_pool)
+    .await
+    .context("Failed to store API token")?;
+
+    Ok(token)
+}
+
+#[tracing::instrument(skip(db_pool))]
+pub async fn list_api_tokens(
+    db_pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Vec<ApiToken>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT
+            api_token_id,
+            name,
+            token_prefix,
+            scopes,
+            created_at,
+            last_used_at
+        FROM api_tokens
+        WHERE user_id = $1
+        ORDER BY created_at DESC
+        "#,
+        user_id,
+    )
+    .fetch_all(db_pool)
+    .await
+    .context("Failed to retrieve API tokens")?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(ApiToken {
+                api_token_id: row.api_token_id,
+                name: row.name,
+                token_prefix: row.token_prefix,
+                scopes: parse_scopes(&row.scopes)?,
+                created_at: row.created_at,
+                last_used_at: row.last_used_at,
+            })
+        })
+        .collect()
+}
+
+/// Revokes an API token of a given user. Returns `false` if the user
+/// has no such token.
+#[tracing::instrument(skip(db_pool))]
+pub async fn revoke_api_token(
+    db_pool: &PgPool,
+    user_id: Uuid,
+    api_token_id: Uuid,
+) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM api_tokens
+        WHERE
+            api_token_id = $1 AND
+            user_id = $2
+        "#,
+        api_token_id,
+        user_id,
+    )
+    .execute(db_pool)
+    .await
+    .context("Failed to revoke API token")?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Looks up the owner and scopes of a presented token, recording its use.
+/// Returns `None` for unknown or revoked tokens.
+#[tracing::instrument(name = "Authenticate API token", skip(db_pool, token))]
+pub async fn authenticate_api_token(
+    db_pool: &PgPool,
+    token: &Secret<String>,
+) -> Result<Option<(Uuid, Vec<ApiScope>)>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        UPDATE api_tokens
+        SET last_used_at = now()
+        WHERE token_hash = $1
+        RETURNING user_id, scopes
+        "#,
+        hash_token(token),
+    )
+    .fetch_optional(db_pool)
+    .await
+    .context("Failed to perform a query to retrieve API token")?;
+
+    row.map(|row| Ok((row.user_id, parse_scopes(&row.scopes)?)))
+        .transpose()
+}
+
+fn generate_token() -> Secret<String> {
+    let random: String = thread_rng()
+        .sample_iter(Alphanumeric)
+        .map(char::from)
+        .take(TOKEN_RANDOM_LENGTH)
+        .collect();
+
+    Secret::new(format!("{TOKEN_PREFIX}{random}"))
+}
+
+fn hash_token(token: &Secret<String>) -> String {
+    hex::encode(Sha256::digest(token.expose_secret().as_bytes()))
+}
+
+fn parse_scopes(scopes: &[String]) -> Result<Vec<ApiScope>, anyhow::Error> {
+    scopes.iter().map(|scope| scope.parse()).collect()
+}
+
+pub struct ApiToken {
+    pub api_token_id: Uuid,
+    pub name: String,
+    pub token_prefix: String,
+    pub scopes: Vec<ApiScope>,
+    pub created_at: OffsetDateTime,
+    pub last_used_at: Option<OffsetDateTime>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_token, hash_token, ApiScope};
+    use claims::{assert_err, assert_ok_eq};
+    use secrecy::ExposeSecret;
+
+    #[test]
+    fn scopes_round_trip_through_strings() {
+        for scope in ApiScope::ALL {
+            assert_ok_eq!(scope.as_str().parse::<ApiScope>(), scope);
+        }
+    }
+
+    #[test]
+    fn unknown_scope_is_rejected() {
+        assert_err!("issues:delete".parse::<ApiScope>());
+    }
+
+    #[test]
+    fn generated_tokens_are_unique_and_hashed_deterministically() {
+        let token = generate_token();
+        let other_token = generate_token();
+
+        assert!(token.expose_secret().starts_with("z2p_"));
+        assert_ne!(token.expose_secret(), other_token.expose_secret());
+        assert_eq!(hash_token(&token), hash_token(&token));
+        assert_ne!(hash_token(&token), hash_token(&other_token));
+    }
+}
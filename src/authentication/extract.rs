@@ -1,3 +1,4 @@
+use super::api_tokens::ApiScope;
 use axum::{
     async_trait,
     extract::FromRequestParts,
@@ -5,6 +6,8 @@ use axum::{
 };
 use uuid::Uuid;
 
+/// Id of the authenticated user, inserted by either `AuthorizedSessionLayer`
+/// for browser sessions or `BearerTokenLayer` for API tokens.
 #[derive(Clone, Debug)]
 pub struct SessionUserId(pub Uuid);
 
@@ -17,8 +20,43 @@ where
 
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
         parts.extensions.get::<SessionUserId>().cloned().ok_or({
-            tracing::error!("User id not found in session");
+            tracing::error!("User id not found in request extensions");
             StatusCode::INTERNAL_SERVER_ERROR
         })
     }
 }
+
+/// Scopes granted to the authenticated user. Browser sessions are granted
+/// all of them, API tokens only the ones chosen when they were created.
+#[derive(Clone, Debug)]
+pub struct GrantedScopes(pub Vec<ApiScope>);
+
+impl GrantedScopes {
+    pub fn require(&self, scope: ApiScope) -> Result<(), StatusCode> {
+        if self.0.contains(&scope) {
+            Ok(())
+        } else {
+            tracing::info!("Missing `{scope}` scope");
+            Err(StatusCode::FORBIDDEN)
+        }
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for GrantedScopes
+where
+    S: Send + Sync,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<GrantedScopes>()
+            .cloned()
+            .ok_or_else(|| {
+                tracing::error!("Granted scopes not found in request extensions");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })
+    }
+}
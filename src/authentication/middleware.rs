@@ -1,7 +1,16 @@
-use super::extract::SessionUserId;
+use super::{
+    api_tokens::{authenticate_api_token, ApiScope},
+    extract::{GrantedScopes, SessionUserId},
+    sessions::touch_session,
+};
 use crate::session_state::TypedSession;
 use anyhow::anyhow;
-use axum::http::{header::LOCATION, HeaderValue, Request, Response, StatusCode};
+use axum::http::{
+    header::{AUTHORIZATION, LOCATION, WWW_AUTHENTICATE},
+    HeaderMap, HeaderValue, Request, Response, StatusCode,
+};
+use secrecy::Secret;
+use sqlx::PgPool;
 use std::{
     future::Future,
     pin::Pin,
@@ -12,19 +21,31 @@ use tower_sessions::Session;
 use tracing::Instrument;
 
 #[derive(Clone, Debug)]
-pub struct AuthorizedSessionLayer;
+pub struct AuthorizedSessionLayer {
+    db_pool: PgPool,
+}
+
+impl AuthorizedSessionLayer {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
+    }
+}
 
 impl<S> Layer<S> for AuthorizedSessionLayer {
     type Service = AuthorizedSession<S>;
 
     fn layer(&self, inner: S) -> Self::Service {
-        AuthorizedSession { inner }
+        AuthorizedSession {
+            inner,
+            db_pool: self.db_pool.clone(),
+        }
     }
 }
 
 #[derive(Clone, Debug)]
 pub struct AuthorizedSession<S> {
     inner: S,
+    db_pool: PgPool,
 }
 
 impl<S> AuthorizedSession<S> {
@@ -72,6 +93,7 @@ where
         let span = tracing::info_span!("call");
         let clone = self.inner.clone();
         let mut inner = std::mem::replace(&mut self.inner, clone);
+        let db_pool = self.db_pool.clone();
 
         Box::pin(
             async move {
@@ -88,11 +110,121 @@ where
                     Ok(Some(user_id)) => {
                         tracing::info!("User id `{user_id}` found in session");
                         req.extensions_mut().insert(SessionUserId(user_id));
+                        req.extensions_mut()
+                            .insert(GrantedScopes(ApiScope::ALL.to_vec()));
                     }
                     Ok(None) => return Ok(Self::see_other()),
                     Err(e) => return Ok(Self::internal_server_error(e)),
                 };
 
+                if let Some(session_id) = session.id() {
+                    if let Err(e) = touch_session(&db_pool, &session_id).await {
+                        tracing::warn!("{:#?}", e);
+                    }
+                }
+
+                inner.call(req).await
+            }
+            .instrument(span),
+        )
+    }
+}
+
+/// Authenticates requests with an `Authorization: Bearer <token>` header
+/// carrying an API token. Meant for JSON APIs, so unauthenticated requests
+/// get a `401` instead of a redirect to the login form.
+#[derive(Clone, Debug)]
+pub struct BearerTokenLayer {
+    db_pool: PgPool,
+}
+
+impl BearerTokenLayer {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
+    }
+}
+
+impl<S> Layer<S> for BearerTokenLayer {
+    type Service = BearerToken<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        BearerToken {
+            inner,
+            db_pool: self.db_pool.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BearerToken<S> {
+    inner: S,
+    db_pool: PgPool,
+}
+
+impl<S> BearerToken<S> {
+    fn unauthorized<ResBody>() -> Response<ResBody>
+    where
+        ResBody: Default,
+    {
+        tracing::info!("Missing or invalid API token");
+        let mut res = Response::default();
+        *res.status_mut() = StatusCode::UNAUTHORIZED;
+        res.headers_mut()
+            .insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        res
+    }
+
+    fn bearer_token(headers: &HeaderMap) -> Option<Secret<String>> {
+        let token = headers
+            .get(AUTHORIZATION)?
+            .to_str()
+            .ok()?
+            .strip_prefix("Bearer ")?
+            .trim();
+
+        (!token.is_empty()).then(|| Secret::new(token.to_string()))
+    }
+}
+
+impl<ReqBody, ResBody, S> Service<Request<ReqBody>> for BearerToken<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    S::Error: Send,
+    ReqBody: Send + 'static,
+    ResBody: Default + Send,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<ReqBody>) -> Self::Future {
+        let span = tracing::info_span!("call");
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let db_pool = self.db_pool.clone();
+
+        Box::pin(
+            async move {
+                let Some(token) = Self::bearer_token(req.headers()) else {
+                    return Ok(Self::unauthorized());
+                };
+
+                match authenticate_api_token(&db_pool, &token).await {
+                    Ok(Some((user_id, scopes))) => {
+                        tracing::info!("User id `{user_id}` found for API token");
+                        req.extensions_mut().insert(SessionUserId(user_id));
+                        req.extensions_mut().insert(GrantedScopes(scopes));
+                    }
+                    Ok(None) => return Ok(Self::unauthorized()),
+                    Err(e) => return Ok(AuthorizedSession::<S>::internal_server_error(e)),
+                };
+
                 inner.call(req).await
             }
             .instrument(span),
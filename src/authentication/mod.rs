@@ -1,3 +1,6 @@
+pub mod api_tokens;
 pub mod extract;
 pub mod middleware;
 pub mod password;
+pub mod password_policy;
+pub mod sessions;
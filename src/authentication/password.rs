@@ -4,22 +4,74 @@ use argon2::{
     password_hash::SaltString, Algorithm, Argon2, Params, PasswordHash, PasswordHasher,
     PasswordVerifier, Version,
 };
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
 use secrecy::{ExposeSecret, Secret};
 use sqlx::PgPool;
+use tracing::Instrument;
 use uuid::Uuid;
 
-#[tracing::instrument(name = "Validate credentials", skip(db_pool, credentials))]
+#[derive(Clone)]
+pub struct PasswordHashing {
+    params: Params,
+    dummy_password_hash: Secret<String>,
+}
+
+impl PasswordHashing {
+    /// Creates hashing settings with the given parameters. The dummy hash,
+    /// which is verified for unknown usernames to equalise response times,
+    /// is computed with the same parameters, so it follows configuration changes.
+    pub fn new(params: Params) -> Result<Self, anyhow::Error> {
+        let dummy_password = thread_rng()
+            .sample_iter(Alphanumeric)
+            .map(char::from)
+            .take(32)
+            .collect();
+        let dummy_password_hash = compute_password_hash(Secret::new(dummy_password), &params)
+            .context("Failed to compute dummy password hash")?;
+
+        Ok(Self {
+            params,
+            dummy_password_hash,
+        })
+    }
+
+    /// Tells whether a stored hash uses a different algorithm or version,
+    /// or weaker cost parameters than the configured ones.
+    fn needs_rehash(&self, password_hash: &Secret<String>) -> bool {
+        let Ok(password_hash) = PasswordHash::new(password_hash.expose_secret()) else {
+            return false;
+        };
+
+        if !matches!(
+            Algorithm::try_from(password_hash.algorithm),
+            Ok(Algorithm::Argon2id)
+        ) || password_hash.version != Some(Version::V0x13.into())
+        {
+            return true;
+        }
+
+        match Params::try_from(&password_hash) {
+            Ok(params) => {
+                params.m_cost() < self.params.m_cost()
+                    || params.t_cost() < self.params.t_cost()
+                    || params.p_cost() < self.params.p_cost()
+            }
+            Err(_) => true,
+        }
+    }
+}
+
+#[tracing::instrument(
+    name = "Validate credentials",
+    skip(db_pool, password_hashing, credentials)
+)]
 pub async fn validate_credentials(
     db_pool: &PgPool,
+    password_hashing: &PasswordHashing,
     credentials: Credentials,
 ) -> Result<Uuid, AuthError> {
     let mut user_id = None;
-    let mut expected_password_hash = Secret::new(
-        "$argon2id$v=19$m=15000,t=2,p=1$\
-        5sbY1nJpEqWJ9gQP0SvDbw$\
-        ZgUSqWDG8XJozXYqOTrah9Ori8FmepJwhTHZMLradFU"
-            .to_string(),
-    );
+    let mut expected_password_hash = password_hashing.dummy_password_hash.clone();
 
     if let Some((stored_user_id, stored_password_hash)) =
         get_stored_credentials(db_pool, credentials.username).await?
@@ -28,15 +80,30 @@ pub async fn validate_credentials(
         expected_password_hash = stored_password_hash;
     }
 
-    spawn_blocking_with_tracing(move || {
-        verify_password_hash(expected_password_hash, credentials.password)
-    })
-    .await
-    .context("Failed to spawn blocking task")??;
+    let password = credentials.password.clone();
+    let password_hash = expected_password_hash.clone();
+    spawn_blocking_with_tracing(move || verify_password_hash(password_hash, password))
+        .await
+        .context("Failed to spawn blocking task")??;
 
-    user_id
+    let user_id = user_id
         .ok_or_else(|| anyhow::anyhow!("Unknown username"))
-        .map_err(AuthError::InvalidCredentials)
+        .map_err(AuthError::InvalidCredentials)?;
+
+    if password_hashing.needs_rehash(&expected_password_hash) {
+        tokio::spawn(
+            upgrade_password_hash(
+                db_pool.clone(),
+                password_hashing.params.clone(),
+                user_id,
+                expected_password_hash,
+                credentials.password,
+            )
+            .in_current_span(),
+        );
+    }
+
+    Ok(user_id)
 }
 
 #[tracing::instrument(name = "Get stored credentials", skip(db_pool, username))]
@@ -80,15 +147,73 @@ fn verify_password_hash(
         .map_err(AuthError::InvalidCredentials)
 }
 
-#[tracing::instrument(skip(db_pool, user_id, password))]
+async fn upgrade_password_hash(
+    db_pool: PgPool,
+    params: Params,
+    user_id: Uuid,
+    old_password_hash: Secret<String>,
+    password: Secret<String>,
+) {
+    if let Err(e) =
+        try_upgrade_password_hash(&db_pool, params, user_id, old_password_hash, password).await
+    {
+        tracing::error!(
+            error_cause_chain = ?e,
+            error.message = %e,
+            "Failed to upgrade password hash"
+        );
+    }
+}
+
+#[tracing::instrument(
+    name = "Upgrade password hash",
+    skip(db_pool, params, old_password_hash, password)
+)]
+async fn try_upgrade_password_hash(
+    db_pool: &PgPool,
+    params: Params,
+    user_id: Uuid,
+    old_password_hash: Secret<String>,
+    password: Secret<String>,
+) -> Result<(), anyhow::Error> {
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password, &params))
+            .await?
+            .context("Failed to hash password")?;
+
+    // The old hash is a part of the condition, so that a password changed
+    // in the meantime is never overwritten with a stale one.
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET password_hash = $1
+        WHERE
+            user_id = $2 AND
+            password_hash = $3
+        "#,
+        password_hash.expose_secret(),
+        user_id,
+        old_password_hash.expose_secret(),
+    )
+    .execute(db_pool)
+    .await
+    .context("Failed to store upgraded password hash in the database")?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip(db_pool, password_hashing, user_id, password))]
 pub async fn change_password(
     db_pool: &PgPool,
+    password_hashing: &PasswordHashing,
     user_id: Uuid,
     password: Secret<String>,
 ) -> Result<(), anyhow::Error> {
-    let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(password))
-        .await?
-        .context("Failed to hash password")?;
+    let params = password_hashing.params.clone();
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password, &params))
+            .await?
+            .context("Failed to hash password")?;
 
     sqlx::query!(
         r#"
@@ -106,15 +231,62 @@ pub async fn change_password(
     Ok(())
 }
 
-fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>, anyhow::Error> {
-    let salt = SaltString::generate(&mut rand::thread_rng());
-    let password_hash = Argon2::new(
-        Algorithm::Argon2id,
-        Version::V0x13,
-        Params::new(15000, 2, 1, None).unwrap(),
+/// Creates a user with the given password. Fails if the username is taken.
+#[tracing::instrument(skip(db_pool, password_hashing, password))]
+pub async fn create_user(
+    db_pool: &PgPool,
+    password_hashing: &PasswordHashing,
+    username: &str,
+    password: Secret<String>,
+) -> Result<Uuid, anyhow::Error> {
+    let params = password_hashing.params.clone();
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password, &params))
+            .await?
+            .context("Failed to hash password")?;
+
+    let user_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO users (user_id, username, password_hash)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (username) DO NOTHING
+        RETURNING user_id
+        "#,
+        Uuid::new_v4(),
+        username,
+        password_hash.expose_secret(),
     )
-    .hash_password(password.expose_secret().as_bytes(), &salt)?
-    .to_string();
+    .fetch_optional(db_pool)
+    .await
+    .context("Failed to store new user in the database")?
+    .ok_or_else(|| anyhow::anyhow!("User `{username}` already exists"))?;
+
+    Ok(user_id)
+}
+
+#[tracing::instrument(skip(db_pool))]
+pub async fn get_user_id(db_pool: &PgPool, username: &str) -> Result<Option<Uuid>, anyhow::Error> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT user_id
+        FROM users
+        WHERE username = $1
+        "#,
+        username,
+    )
+    .fetch_optional(db_pool)
+    .await
+    .context("Failed to retrieve user id")
+}
+
+fn compute_password_hash(
+    password: Secret<String>,
+    params: &Params,
+) -> Result<Secret<String>, anyhow::Error> {
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    let password_hash = Argon2::new(Algorithm::Argon2id, Version::V0x13, params.clone())
+        .hash_password(password.expose_secret().as_bytes(), &salt)?
+        .to_string();
 
     Ok(Secret::new(password_hash))
 }
@@ -131,3 +303,61 @@ pub enum AuthError {
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{compute_password_hash, PasswordHashing};
+    use argon2::{password_hash::SaltString, Algorithm, Argon2, Params, PasswordHasher, Version};
+    use secrecy::Secret;
+
+    #[test]
+    fn hash_computed_with_configured_params_does_not_need_rehash() {
+        // given
+        let params = Params::new(4096, 2, 1, None).unwrap();
+        let password_hashing = PasswordHashing::new(params.clone()).unwrap();
+        let password_hash = compute_password_hash(password(), &params).unwrap();
+
+        // when
+        let result = password_hashing.needs_rehash(&password_hash);
+
+        // then
+        assert!(!result);
+    }
+
+    #[test]
+    fn hash_computed_with_weaker_params_needs_rehash() {
+        // given
+        let password_hashing =
+            PasswordHashing::new(Params::new(4096, 2, 1, None).unwrap()).unwrap();
+        let password_hash =
+            compute_password_hash(password(), &Params::new(4096, 1, 1, None).unwrap()).unwrap();
+
+        // when
+        let result = password_hashing.needs_rehash(&password_hash);
+
+        // then
+        assert!(result);
+    }
+
+    #[test]
+    fn hash_computed_with_a_different_algorithm_needs_rehash() {
+        // given
+        let params = Params::new(4096, 2, 1, None).unwrap();
+        let password_hashing = PasswordHashing::new(params.clone()).unwrap();
+        let salt = SaltString::generate(&mut rand::thread_rng());
+        let password_hash = Argon2::new(Algorithm::Argon2i, Version::V0x13, params)
+            .hash_password(b"password", &salt)
+            .unwrap()
+            .to_string();
+
+        // when
+        let result = password_hashing.needs_rehash(&Secret::new(password_hash));
+
+        // then
+        assert!(result);
+    }
+
+    fn password() -> Secret<String> {
+        Secret::new("password".into())
+    }
+}
@@ -0,0 +1,254 @@
+use anyhow::Context;
+use secrecy::{ExposeSecret, Secret};
+use sha1::{Digest, Sha1};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    sync::Arc,
+};
+
+const MIN_LENGTH: usize = 12;
+const MAX_LENGTH: usize = 128;
+const PREFIX_LENGTH: usize = 5;
+
+#[derive(Clone)]
+pub struct PasswordPolicy {
+    minimum_entropy_bits: f64,
+    breached_passwords: Option<Arc<BreachedPasswords>>,
+}
+
+impl PasswordPolicy {
+    pub fn new(minimum_entropy_bits: f64, breached_passwords: Option<BreachedPasswords>) -> Self {
+        Self {
+            minimum_entropy_bits,
+            breached_passwords: breached_passwords.map(Arc::new),
+        }
+    }
+
+    pub fn validate(
+        &self,
+        username: &str,
+        password: &Secret<String>,
+    ) -> Result<(), PasswordPolicyViolation> {
+        let password = password.expose_secret();
+        let length = password.chars().count();
+
+        if length < MIN_LENGTH {
+            return Err(PasswordPolicyViolation::TooShort);
+        }
+
+        if length > MAX_LENGTH {
+            return Err(PasswordPolicyViolation::TooLong);
+        }
+
+        if !username.is_empty() && password.to_lowercase().contains(&username.to_lowercase()) {
+            return Err(PasswordPolicyViolation::ContainsUsername);
+        }
+
+        if estimate_entropy_bits(password) < self.minimum_entropy_bits {
+            return Err(PasswordPolicyViolation::TooWeak);
+        }
+
+        if let Some(breached_passwords) = &self.breached_passwords {
+            if breached_passwords.contains(password) {
+                return Err(PasswordPolicyViolation::Breached);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Estimates password entropy from the size of the character pool it draws from.
+/// Characters that repeat the previous one or continue a sequence (like `abc` or `321`)
+/// add nothing to the estimate.
+fn estimate_entropy_bits(password: &str) -> f64 {
+    let (mut lowercase, mut uppercase, mut digits, mut symbols, mut other) =
+        (false, false, false, false, false);
+    let mut effective_length = 0;
+    let mut previous: Option<char> = None;
+
+    for c in password.chars() {
+        match c {
+            _ if c.is_ascii_lowercase() => lowercase = true,
+            _ if c.is_ascii_uppercase() => uppercase = true,
+            _ if c.is_ascii_digit() => digits = true,
+            _ if c.is_ascii() => symbols = true,
+            _ => other = true,
+        }
+
+        let predictable = previous.is_some_and(|p| (p as i64 - c as i64).abs() <= 1);
+        if !predictable {
+            effective_length += 1;
+        }
+        previous = Some(c);
+    }
+
+    let pool_size = [
+        (lowercase, 26),
+        (uppercase, 26),
+        (digits, 10),
+        (symbols, 33),
+        (other, 100),
+    ]
+    .iter()
+    .filter(|(present, _)| *present)
+    .map(|(_, size)| size)
+    .sum::<u32>();
+
+    if pool_size == 0 {
+        return 0.0;
+    }
+
+    effective_length as f64 * f64::from(pool_size).log2()
+}
+
+/// Known-breached passwords as upper case hex SHA-1 hashes, grouped by their
+/// 5 character prefixes like in the Pwned Passwords k-anonymity range API.
+pub struct BreachedPasswords {
+    ranges: HashMap<String, HashSet<String>>,
+}
+
+impl BreachedPasswords {
+    /// Loads hashes from a file with one `HASH` or `HASH:COUNT` entry per line.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, anyhow::Error> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read breached passwords from {path:?}"))?;
+
+        Self::parse(&contents)
+    }
+
+    fn parse(contents: &str) -> Result<Self, anyhow::Error> {
+        let mut ranges: HashMap<String, HashSet<String>> = HashMap::new();
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let hash = line.split(':').next().unwrap_or_default().to_uppercase();
+            if hash.len() != 40 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                anyhow::bail!(
+                    "Invalid SHA-1 hash in line {} of breached passwords file",
+                    number + 1
+                );
+            }
+
+            let (prefix, suffix) = hash.split_at(PREFIX_LENGTH);
+            ranges
+                .entry(prefix.to_string())
+                .or_default()
+                .insert(suffix.to_string());
+        }
+
+        Ok(Self { ranges })
+    }
+
+    fn contains(&self, password: &str) -> bool {
+        let hash = hex::encode_upper(Sha1::digest(password.as_bytes()));
+        let (prefix, suffix) = hash.split_at(PREFIX_LENGTH);
+
+        self.ranges
+            .get(prefix)
+            .is_some_and(|suffixes| suffixes.contains(suffix))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PasswordPolicyViolation {
+    #[error("Password must be at least 12 characters long.")]
+    TooShort,
+    #[error("Password must be at most 128 characters long.")]
+    TooLong,
+    #[error("Password must not contain your username.")]
+    ContainsUsername,
+    #[error(
+        "Password is too weak - use more varied characters \
+        and avoid repetitions and sequences."
+    )]
+    TooWeak,
+    #[error(
+        "Password has appeared in a data breach \
+        - please choose a different one."
+    )]
+    Breached,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BreachedPasswords, PasswordPolicy, PasswordPolicyViolation};
+    use claims::{assert_err, assert_matches, assert_ok};
+    use secrecy::Secret;
+
+    #[test]
+    fn a_strong_password_is_accepted() {
+        // given
+        let policy = PasswordPolicy::new(50.0, None);
+
+        // when
+        let result = policy.validate("admin", &password("correct-Horse-battery-7"));
+
+        // then
+        assert_ok!(result);
+    }
+
+    #[test]
+    fn a_password_containing_the_username_is_rejected() {
+        // given
+        let policy = PasswordPolicy::new(50.0, None);
+
+        // when
+        let result = policy.validate("admin", &password("my-ADMIN-password-7"));
+
+        // then
+        assert_matches!(result, Err(PasswordPolicyViolation::ContainsUsername));
+    }
+
+    #[test]
+    fn repeated_and_sequential_characters_are_rejected_as_weak() {
+        // given
+        let policy = PasswordPolicy::new(50.0, None);
+
+        for candidate in ["aaaaaaaaaaaaaaaa", "abcdefghijklmnop", "9876543210987654"] {
+            // when
+            let result = policy.validate("admin", &password(candidate));
+
+            // then
+            assert_matches!(result, Err(PasswordPolicyViolation::TooWeak));
+        }
+    }
+
+    #[test]
+    fn a_breached_password_is_rejected() {
+        // given
+        // the second entry is a lower case SHA-1 of `correct-Horse-battery-7`
+        let breached_passwords = BreachedPasswords::parse(
+            "0000000000000000000000000000000000000000:1\n\
+            de48e1e00c3233292146a16e8394247472587dc0:3\n\
+            FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF:12\n",
+        )
+        .unwrap();
+        let policy = PasswordPolicy::new(50.0, Some(breached_passwords));
+
+        // when
+        let result = policy.validate("admin", &password("correct-Horse-battery-7"));
+
+        // then
+        assert_matches!(result, Err(PasswordPolicyViolation::Breached));
+    }
+
+    #[test]
+    fn invalid_breached_passwords_file_is_rejected() {
+        // when
+        let result = BreachedPasswords::parse("not-a-sha1-hash:1\n");
+
+        // then
+        assert_err!(result);
+    }
+
+    fn password(s: &str) -> Secret<String> {
+        Secret::new(s.to_string())
+    }
+}
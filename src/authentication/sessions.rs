@@ -0,0 +1,215 @@
+use crate::client_info::ClientInfo;
+use anyhow::Context;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use tower_sessions::{session::Id, SessionStore};
+use uuid::Uuid;
+
+#[tracing::instrument(skip(db_pool, session_id, client_info))]
+pub async fn register_session(
+    db_pool: &PgPool,
+    user_id: Uuid,
+    session_id: &Id,
+    client_info: &ClientInfo,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO user_sessions (
+            user_session_id,
+            session_id,
+            user_id,
+            ip_address,
+            user_agent,
+            created_at,
+            last_seen_at
+        )
+        VALUES ($1, $2, $3, $4, $5, now(), now())
+        "#,
+        Uuid::new_v4(),
+        session_id.to_string(),
+        user_id,
+        client_info.ip_address,
+        client_info.user_agent,
+    )
+    .execute(db_pool)
+    .await
+    .context("Failed to register user session")?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip(db_pool, session_id))]
+pub async fn touch_session(db_pool: &PgPool, session_id: &Id) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE user_sessions
+        SET last_seen_at = now()
+        WHERE session_id = $1
+        "#,
+        session_id.to_string(),
+    )
+    .execute(db_pool)
+    .await
+    .context("Failed to update user session last seen time")?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip(db_pool, session_id))]
+pub async fn forget_session(db_pool: &PgPool, session_id: &Id) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        DELETE FROM user_sessions
+        WHERE session_id = $1
+        "#,
+        session_id.to_string(),
+    )
+    .execute(db_pool)
+    .await
+    .context("Failed to remove user session from the index")?;
+
+    Ok(())
+}
+
+/// Lists sessions of a given user, pruning index entries of sessions
+/// that have already expired in the session store.
+#[tracing::instrument(skip(db_pool, store, current_session_id))]
+pub async fn list_sessions(
+    db_pool: &PgPool,
+    store: &impl SessionStore,
+    user_id: Uuid,
+    current_session_id: Option<&Id>,
+) -> Result<Vec<UserSession>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT
+            user_session_id,
+            session_id,
+            ip_address,
+            user_agent,
+            created_at,
+            last_seen_at
+        FROM user_sessions
+        WHERE user_id = $1
+        ORDER BY last_seen_at DESC
+        "#,
+        user_id,
+    )
+    .fetch_all(db_pool)
+    .await
+    .context("Failed to retrieve user sessions")?;
+
+    let mut sessions = Vec::with_capacity(rows.len());
+    for row in rows {
+        let session_id: Id = row
+            .session_id
+            .parse()
+            .context("Failed to parse session id")?;
+
+        if store
+            .load(&session_id)
+            .await
+            .context("Failed to load session from the store")?
+            .is_none()
+        {
+            forget_session(db_pool, &session_id).await?;
+            continue;
+        }
+
+        sessions.push(UserSession {
+            user_session_id: row.user_session_id,
+            ip_address: row.ip_address,
+            user_agent: row.user_agent,
+            created_at: row.created_at,
+            last_seen_at: row.last_seen_at,
+            is_current: current_session_id == Some(&session_id),
+        });
+    }
+
+    Ok(sessions)
+}
+
+/// Revokes a single session of a given user. Returns `false` if the user
+/// has no such session.
+#[tracing::instrument(skip(db_pool, store))]
+pub async fn revoke_session(
+    db_pool: &PgPool,
+    store: &impl SessionStore,
+    user_id: Uuid,
+    user_session_id: Uuid,
+) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        DELETE FROM user_sessions
+        WHERE
+            user_session_id = $1 AND
+            user_id = $2
+        RETURNING session_id
+        "#,
+        user_session_id,
+        user_id,
+    )
+    .fetch_optional(db_pool)
+    .await
+    .context("Failed to remove user session from the index")?;
+
+    match row {
+        Some(row) => {
+            delete_from_store(store, &row.session_id).await?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Revokes all sessions of a given user except for the current one.
+/// Returns the number of revoked sessions.
+#[tracing::instrument(skip(db_pool, store, current_session_id))]
+pub async fn revoke_other_sessions(
+    db_pool: &PgPool,
+    store: &impl SessionStore,
+    user_id: Uuid,
+    current_session_id: Option<&Id>,
+) -> Result<usize, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"
+        DELETE FROM user_sessions
+        WHERE
+            user_id = $1 AND
+            session_id IS DISTINCT FROM $2
+        RETURNING session_id
+        "#,
+        user_id,
+        current_session_id.map(Id::to_string),
+    )
+    .fetch_all(db_pool)
+    .await
+    .context("Failed to remove user sessions from the index")?;
+
+    for row in &rows {
+        delete_from_store(store, &row.session_id).await?;
+    }
+
+    Ok(rows.len())
+}
+
+async fn delete_from_store(
+    store: &impl SessionStore,
+    session_id: &str,
+) -> Result<(), anyhow::Error> {
+    let session_id: Id = session_id.parse().context("Failed to parse session id")?;
+
+    store
+        .delete(&session_id)
+        .await
+        .context("Failed to delete session from the store")
+}
+
+pub struct UserSession {
+    pub user_session_id: Uuid,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: OffsetDateTime,
+    pub last_seen_at: OffsetDateTime,
+    pub is_current: bool,
+}
@@ -0,0 +1,217 @@
+use anyhow::Context;
+use clap::{Parser, Subcommand};
+use secrecy::Secret;
+use serde::Serialize;
+use serde_json::json;
+use std::{io::IsTerminal, path::PathBuf};
+use uuid::Uuid;
+use zero2prod::{
+    authentication::password::{change_password, create_user, get_user_id},
+    configuration::{get_configuration, Settings},
+    delivery_queue::{
+        delivery_stats, list_queued_tasks, purge_queued_tasks, requeue_failed_deliveries,
+    },
+    migrations::run_migrations,
+    startup::get_pg_connection_pool,
+    subscriber_import::{import_subscribers, parse_csv},
+    telemetry::{get_subscriber, init_subscriber},
+};
+
+#[derive(Parser)]
+#[command(version, about = "Administers the newsletter delivery service")]
+struct Cli {
+    /// Prints results as JSON, for scripting.
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Manages admin users.
+    #[command(subcommand)]
+    Users(UsersCommand),
+    /// Inspects the issue delivery queue.
+    #[command(subcommand)]
+    Queue(QueueCommand),
+    /// Shows delivery statistics of an issue.
+    IssueStats { issue_id: Uuid },
+    /// Imports confirmed subscribers from a CSV file with `email` and `name`
+    /// columns. Addresses which are already subscribed are skipped.
+    ImportSubscribers { file: PathBuf },
+    /// Applies pending database migrations.
+    Migrate,
+}
+
+/// Passwords are prompted for, or read from the first line of the standard
+/// input when it is not a terminal.
+#[derive(Subcommand)]
+enum UsersCommand {
+    /// Creates an admin user.
+    Create { username: String },
+    /// Sets a new password for an admin user.
+    ResetPassword { username: String },
+}
+
+#[derive(Subcommand)]
+enum QueueCommand {
+    /// Lists emails waiting to be sent.
+    List {
+        /// Lists the emails of this issue only.
+        #[arg(long)]
+        issue: Option<Uuid>,
+        #[arg(long, default_value_t = 100)]
+        limit: i64,
+    },
+    /// Queues the failed deliveries of an issue again.
+    Requeue { issue_id: Uuid },
+    /// Drops queued emails, so that they are never sent.
+    Purge {
+        /// Drops the emails of this issue.
+        #[arg(long, required_unless_present = "all")]
+        issue: Option<Uuid>,
+        /// Drops the emails of all issues.
+        #[arg(long, conflicts_with = "issue")]
+        all: bool,
+    },
+}
+
+#[tokio::main]
+async fn main() -> Result<(), anyhow::Error> {
+    let cli = Cli::parse();
+
+    // Keeps the standard output for results.
+    let subscriber = get_subscriber("zero2prod-admin".into(), "warn".into(), std::io::stderr);
+    init_subscriber(subscriber);
+
+    let config = get_configuration().expect("Failed to read configuration");
+    let db_pool = get_pg_connection_pool(&config.database);
+
+    match cli.command {
+        Command::Users(UsersCommand::Create { username }) => {
+            let password = read_password(&config, &username)?;
+            let password_hashing = config.password_hashing.password_hashing()?;
+            let user_id = create_user(&db_pool, &password_hashing, &username, password).await?;
+            output(
+                cli.json,
+                json!({ "user_id": user_id, "username": username }),
+                |_| println!("Created user `{username}` with id {user_id}"),
+            )
+        }
+        Command::Users(UsersCommand::ResetPassword { username }) => {
+            let user_id = get_user_id(&db_pool, &username)
+                .await?
+                .with_context(|| format!("User `{username}` does not exist"))?;
+            let password = read_password(&config, &username)?;
+            let password_hashing = config.password_hashing.password_hashing()?;
+            change_password(&db_pool, &password_hashing, user_id, password).await?;
+            output(
+                cli.json,
+                json!({ "user_id": user_id, "username": username }),
+                |_| println!("Changed the password of user `{username}`"),
+            )
+        }
+        Command::Queue(QueueCommand::List { issue, limit }) => {
+            let tasks = list_queued_tasks(&db_pool, issue, limit).await?;
+            output(cli.json, tasks, |tasks| {
+                for task in tasks {
+                    println!(
+                        "{}\t{}\t{}",
+                        task.issue_id, task.canonical_email, task.title
+                    );
+                }
+            })
+        }
+        Command::Queue(QueueCommand::Requeue { issue_id }) => {
+            let requeued = requeue_failed_deliveries(&db_pool, issue_id).await?;
+            output(cli.json, json!({ "requeued": requeued }), |_| {
+                println!("Queued {requeued} failed deliveries again")
+            })
+        }
+        Command::Queue(QueueCommand::Purge { issue, .. }) => {
+            let purged = purge_queued_tasks(&db_pool, issue).await?;
+            output(cli.json, json!({ "purged": purged }), |_| {
+                println!("Dropped {purged} queued emails")
+            })
+        }
+        Command::IssueStats { issue_id } => {
+            let stats = delivery_stats(&db_pool, issue_id)
+                .await?
+                .with_context(|| format!("Issue `{issue_id}` does not exist"))?;
+            output(cli.json, stats, |stats| {
+                println!("Pending:       {}", stats.pending);
+                println!("Sent:          {}", stats.sent);
+                println!("Failed:        {}", stats.failed);
+                println!("Skipped:       {}", stats.skipped);
+                println!(
+                    "Opens:         {} ({} unique)",
+                    stats.opens, stats.unique_opens
+                );
+                println!(
+                    "Clicks:        {} ({} unique)",
+                    stats.clicks, stats.unique_clicks
+                );
+            })
+        }
+        Command::ImportSubscribers { file } => {
+            let csv = std::fs::read_to_string(&file)
+                .with_context(|| format!("Failed to read `{}`", file.display()))?;
+            let subscribers =
+                parse_csv(&csv, &config.local_part_rules).map_err(anyhow::Error::msg)?;
+            let summary = import_subscribers(&db_pool, &subscribers).await?;
+            output(cli.json, summary, |summary| {
+                println!(
+                    "Imported {} subscribers, skipped {} already subscribed",
+                    summary.imported, summary.skipped
+                )
+            })
+        }
+        Command::Migrate => {
+            run_migrations(&db_pool, &config).await?;
+            output(cli.json, json!({ "migrated": true }), |_| {
+                println!("The database has been migrated")
+            })
+        }
+    }
+}
+
+/// Prints the result as JSON with `--json`, for humans otherwise.
+fn output<T: Serialize>(
+    json: bool,
+    result: T,
+    print: impl FnOnce(&T),
+) -> Result<(), anyhow::Error> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(&result)?);
+    } else {
+        print(&result);
+    }
+
+    Ok(())
+}
+
+/// Reads a new password, which has to satisfy the password policy.
+fn read_password(config: &Settings, username: &str) -> Result<Secret<String>, anyhow::Error> {
+    let password = if std::io::stdin().is_terminal() {
+        let password = rpassword::prompt_password("New password: ")?;
+        if rpassword::prompt_password("Repeat the password: ")? != password {
+            anyhow::bail!("The passwords do not match");
+        }
+        password
+    } else {
+        let mut line = String::new();
+        std::io::stdin()
+            .read_line(&mut line)
+            .context("Failed to read the password")?;
+        line.trim_end_matches(['\r', '\n']).to_string()
+    };
+    let password = Secret::new(password);
+
+    config
+        .password_policy
+        .password_policy()?
+        .validate(username, &password)?;
+
+    Ok(password)
+}
@@ -0,0 +1,157 @@
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header::USER_AGENT, request::Parts, HeaderMap, HeaderName},
+};
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+};
+
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+
+/// Number of reverse proxies in front of the application, each appending the
+/// address it received the request from to `X-Forwarded-For`. Inserted as a
+/// request extension by `startup::run`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TrustedProxies(pub usize);
+
+#[derive(Clone, Debug)]
+pub struct ClientInfo {
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientInfo
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let TrustedProxies(trusted_proxies) = parts
+            .extensions
+            .get::<TrustedProxies>()
+            .copied()
+            .unwrap_or_default();
+
+        let ip_address = forwarded_for(&parts.headers, trusted_proxies)
+            .or_else(|| {
+                parts
+                    .extensions
+                    .get::<ConnectInfo<SocketAddr>>()
+                    .map(|ConnectInfo(addr)| addr.ip())
+            })
+            .map(|ip| ip.to_string());
+
+        let user_agent = parts
+            .headers
+            .get(USER_AGENT)
+            .and_then(|value| match value.to_str() {
+                Ok(value) => Some(value.to_string()),
+                Err(e) => {
+                    tracing::warn!("Failed to convert user-agent to str: {e:?}");
+                    None
+                }
+            });
+
+        Ok(Self {
+            ip_address,
+            user_agent,
+        })
+    }
+}
+
+/// The address the outermost trusted proxy received the request from. Entries
+/// further left are set by the client, so they cannot be trusted.
+fn forwarded_for(headers: &HeaderMap, trusted_proxies: usize) -> Option<IpAddr> {
+    if trusted_proxies == 0 {
+        return None;
+    }
+
+    let addresses: Vec<&str> = headers
+        .get_all(X_FORWARDED_FOR)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .collect();
+
+    let address = addresses.len().checked_sub(trusted_proxies)?;
+    match addresses[address].parse() {
+        Ok(ip) => Some(ip),
+        Err(e) => {
+            tracing::warn!("Failed to parse forwarded client address: {e:?}");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{forwarded_for, X_FORWARDED_FOR};
+    use axum::http::{HeaderMap, HeaderValue};
+    use claims::{assert_none, assert_some_eq};
+    use std::net::IpAddr;
+
+    fn headers(values: &[&'static str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(X_FORWARDED_FOR, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    #[test]
+    fn address_appended_by_the_trusted_proxy_is_used() {
+        // given
+        let headers = headers(&["203.0.113.7, 198.51.100.1"]);
+
+        // when
+        let client = forwarded_for(&headers, 1);
+
+        // then
+        assert_some_eq!(client, ip("198.51.100.1"));
+    }
+
+    #[test]
+    fn addresses_are_counted_across_repeated_headers() {
+        // given
+        let headers = headers(&["203.0.113.7", "198.51.100.1", "10.0.0.1"]);
+
+        // when
+        let client = forwarded_for(&headers, 2);
+
+        // then
+        assert_some_eq!(client, ip("198.51.100.1"));
+    }
+
+    #[test]
+    fn header_is_ignored_without_trusted_proxies() {
+        // given
+        let headers = headers(&["203.0.113.7"]);
+
+        // when
+        let client = forwarded_for(&headers, 0);
+
+        // then
+        assert_none!(client);
+    }
+
+    #[test]
+    fn header_shorter_than_the_proxy_chain_is_ignored() {
+        // given
+        let headers = headers(&["203.0.113.7"]);
+
+        // when
+        let client = forwarded_for(&headers, 2);
+
+        // then
+        assert_none!(client);
+    }
+}
@@ -1,4 +1,17 @@
-use crate::{domain::SubscriberEmail, email_client::EmailClient};
+use crate::{
+    authentication::{
+        password::PasswordHashing,
+        password_policy::{BreachedPasswords, PasswordPolicy},
+    },
+    delivery_rate_limit::DeliveryRateLimit,
+    domain::{LocalPartRules, SubscriberEmail},
+    email_client::EmailClient,
+    email_events::{SuppressionThresholds, WebhookCredentials},
+    email_verification::{DisposableDomains, DnsResolver, EmailVerifier, MailHostResolver},
+    security_headers::SecurityHeaders,
+    subscribe_protection::{HashcashProofOfWork, ProofOfWork, SubscribeProtection},
+};
+use argon2::Params;
 use secrecy::{ExposeSecret, Secret};
 use serde::Deserialize;
 use serde_aux::field_attributes::deserialize_number_from_string;
@@ -6,7 +19,7 @@ use sqlx::{
     postgres::{PgConnectOptions, PgSslMode},
     ConnectOptions,
 };
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 use tracing_log::log::LevelFilter;
 
 #[derive(Clone, Deserialize)]
@@ -14,6 +27,18 @@ pub struct Settings {
     pub application: ApplicationSettings,
     pub database: DatabaseSettings,
     pub email_client: EmailClientSettings,
+    pub password_hashing: PasswordHashingSettings,
+    pub password_policy: PasswordPolicySettings,
+    pub security_headers: SecurityHeadersSettings,
+    pub subscribe_protection: SubscribeProtectionSettings,
+    pub email_verification: EmailVerificationSettings,
+    pub local_part_rules: LocalPartRules,
+    pub postmark_webhook: PostmarkWebhookSettings,
+    pub tracking: TrackingSettings,
+    pub delivery_rate_limit: DeliveryRateLimitSettings,
+    pub worker: WorkerSettings,
+    pub health_check: HealthCheckSettings,
+    pub environment: Environment,
 }
 
 #[derive(Clone, Deserialize)]
@@ -24,6 +49,19 @@ pub struct ApplicationSettings {
     pub base_url: String,
     pub hmac_secret: Secret<String>,
     pub redis_uri: Secret<String>,
+    /// How long in-flight requests and deliveries get to finish on shutdown.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub shutdown_timeout_seconds: u64,
+    /// Number of reverse proxies in front of the application, whose
+    /// `X-Forwarded-For` entries identify clients. `0` uses peer addresses.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub trusted_proxies: usize,
+}
+
+impl ApplicationSettings {
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.shutdown_timeout_seconds)
+    }
 }
 
 #[derive(Clone, Deserialize)]
@@ -35,6 +73,9 @@ pub struct DatabaseSettings {
     pub password: Secret<String>,
     pub database_name: String,
     pub require_ssl: bool,
+    /// Apply pending migrations before serving or delivering, instead of
+    /// leaving it to `zero2prod migrate`.
+    pub migrate_on_startup: bool,
 }
 
 impl DatabaseSettings {
@@ -87,6 +128,261 @@ impl EmailClientSettings {
     }
 }
 
+#[derive(Clone, Deserialize)]
+pub struct PasswordHashingSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub memory_cost_kib: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub time_cost: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub parallelism: u32,
+}
+
+impl PasswordHashingSettings {
+    pub fn password_hashing(&self) -> Result<PasswordHashing, anyhow::Error> {
+        let params = Params::new(self.memory_cost_kib, self.time_cost, self.parallelism, None)
+            .map_err(|e| anyhow::anyhow!("Invalid password hashing parameters: {e}"))?;
+
+        PasswordHashing::new(params)
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct PasswordPolicySettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub minimum_entropy_bits: f64,
+    pub breached_passwords_file: Option<String>,
+}
+
+impl PasswordPolicySettings {
+    pub fn password_policy(&self) -> Result<PasswordPolicy, anyhow::Error> {
+        let breached_passwords = self
+            .breached_passwords_file
+            .as_ref()
+            .map(BreachedPasswords::load)
+            .transpose()?;
+
+        Ok(PasswordPolicy::new(
+            self.minimum_entropy_bits,
+            breached_passwords,
+        ))
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct SecurityHeadersSettings {
+    /// `{nonce}` is replaced with a fresh nonce on every request.
+    pub content_security_policy: String,
+    pub frame_options: String,
+    pub referrer_policy: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub hsts_max_age_seconds: u64,
+}
+
+impl SecurityHeadersSettings {
+    /// HSTS is only sent in production, where the application sits behind TLS.
+    pub fn security_headers(
+        &self,
+        environment: Environment,
+    ) -> Result<SecurityHeaders, anyhow::Error> {
+        let hsts_max_age = match environment {
+            Environment::Production => Some(self.hsts_max_age_seconds),
+            Environment::Local => None,
+        };
+
+        SecurityHeaders::new(
+            &self.content_security_policy,
+            &self.frame_options,
+            &self.referrer_policy,
+            hsts_max_age,
+        )
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct SubscribeProtectionSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub attempts_per_ip_per_hour: i64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub attempts_per_email_per_hour: i64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub confirmation_emails_per_day: i64,
+    /// `0` disables the check.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub minimum_fill_time_milliseconds: u64,
+    /// Proof of work is disabled unless a difficulty is set.
+    pub proof_of_work_difficulty_bits: Option<u32>,
+}
+
+impl SubscribeProtectionSettings {
+    pub fn subscribe_protection(&self, secret: &Secret<String>) -> SubscribeProtection {
+        let proof_of_work = self.proof_of_work_difficulty_bits.map(|difficulty_bits| {
+            Box::new(HashcashProofOfWork::new(difficulty_bits, secret.clone()))
+                as Box<dyn ProofOfWork>
+        });
+
+        SubscribeProtection::new(
+            secret.clone(),
+            self.attempts_per_ip_per_hour,
+            self.attempts_per_email_per_hour,
+            self.confirmation_emails_per_day,
+            Duration::from_millis(self.minimum_fill_time_milliseconds),
+            proof_of_work,
+        )
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct EmailVerificationSettings {
+    pub disposable_domains_file: Option<String>,
+    /// Look up MX, A and AAAA records of subscriber domains.
+    pub check_mail_hosts: bool,
+}
+
+impl EmailVerificationSettings {
+    pub fn email_verifier(&self) -> Result<EmailVerifier, anyhow::Error> {
+        let disposable_domains = self
+            .disposable_domains_file
+            .as_ref()
+            .map(DisposableDomains::load)
+            .transpose()?;
+
+        let resolver = if self.check_mail_hosts {
+            Some(Arc::new(DnsResolver::from_system_conf()?) as Arc<dyn MailHostResolver>)
+        } else {
+            None
+        };
+
+        Ok(EmailVerifier::new(disposable_domains, resolver))
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct PostmarkWebhookSettings {
+    pub username: String,
+    /// Sent by Postmark either as the basic auth password or in the
+    /// `X-Webhook-Secret` header.
+    pub secret: Secret<String>,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub hard_bounce_threshold: i64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub soft_bounce_threshold: i64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub spam_complaint_threshold: i64,
+    /// Only events from this many recent days count towards the thresholds.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub threshold_window_days: i64,
+}
+
+/// The secret in `base.yaml`, which is public and so only fit for local use.
+const PLACEHOLDER_WEBHOOK_SECRET: &str = "shared-secret-configured-on-the-postmark-webhook";
+
+impl PostmarkWebhookSettings {
+    /// Fails in production while the secret is the public placeholder, which
+    /// would let anyone suppress subscribers by faking bounces.
+    pub fn credentials(
+        &self,
+        environment: Environment,
+    ) -> Result<WebhookCredentials, anyhow::Error> {
+        if environment == Environment::Production
+            && self.secret.expose_secret() == PLACEHOLDER_WEBHOOK_SECRET
+        {
+            anyhow::bail!(
+                "The Postmark webhook secret is the placeholder from base.yaml. \
+                Set `APP_POSTMARK_WEBHOOK__SECRET`."
+            );
+        }
+
+        Ok(WebhookCredentials::new(
+            self.username.clone(),
+            self.secret.clone(),
+        ))
+    }
+
+    pub fn thresholds(&self) -> SuppressionThresholds {
+        SuppressionThresholds {
+            hard_bounces: self.hard_bounce_threshold,
+            soft_bounces: self.soft_bounce_threshold,
+            spam_complaints: self.spam_complaint_threshold,
+            window: time::Duration::days(self.threshold_window_days),
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct TrackingSettings {
+    /// Turns off open and click tracking of all issues, for privacy.
+    pub enabled: bool,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct DeliveryRateLimitSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub messages_per_second: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub messages_per_hour: u32,
+    /// How long to pause on a 429 response without a `Retry-After` header.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub default_retry_after_seconds: u64,
+}
+
+impl DeliveryRateLimitSettings {
+    pub fn rate_limit(&self) -> DeliveryRateLimit {
+        DeliveryRateLimit::new(
+            self.messages_per_second,
+            self.messages_per_hour,
+            Duration::from_secs(self.default_retry_after_seconds),
+        )
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct WorkerSettings {
+    /// Number of delivery tasks running concurrently in one process.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub concurrency: usize,
+    /// How often idle workers look for tasks becoming due, like digests.
+    /// Enqueued tasks wake them up right away.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub poll_interval_seconds: u64,
+    /// How often idle workers look for tasks while they cannot listen for
+    /// enqueued ones.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub fallback_poll_interval_seconds: u64,
+}
+
+impl WorkerSettings {
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_secs(self.poll_interval_seconds)
+    }
+
+    pub fn fallback_poll_interval(&self) -> Duration {
+        Duration::from_secs(self.fallback_poll_interval_seconds)
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct HealthCheckSettings {
+    /// How long each dependency gets to respond to a readiness check.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub timeout_milliseconds: u64,
+    /// Also require the email provider to accept our token to be ready.
+    pub check_email_provider: bool,
+    /// How long the outcome of the email provider check is reused for.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub email_provider_cache_seconds: u64,
+}
+
+impl HealthCheckSettings {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_milliseconds)
+    }
+
+    pub fn email_provider_cache_ttl(&self) -> Duration {
+        Duration::from_secs(self.email_provider_cache_seconds)
+    }
+}
+
 pub fn get_configuration() -> Result<Settings, config::ConfigError> {
     let config_dir = std::env::current_dir()
         .map(|dir| dir.join("configuration"))
@@ -107,11 +403,14 @@ pub fn get_configuration() -> Result<Settings, config::ConfigError> {
                 .prefix_separator("_")
                 .separator("__"),
         )
+        .set_override("environment", environment.as_str())?
         .build()?;
 
     settings.try_deserialize()
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Environment {
     Local,
     Production,
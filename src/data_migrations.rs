@@ -0,0 +1,210 @@
+use crate::{
+    domain::{LocalPartRules, SubscriptionToken},
+    routes::subscriptions::delete_subscriber,
+};
+use anyhow::Context;
+use secrecy::Secret;
+use sqlx::{Connection, Executor, PgConnection, Postgres, Transaction};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// Replaces tokens stored in plaintext before they were hashed with their
+/// hashes. Needs the application secret, so it cannot be done in a migration.
+#[tracing::instrument(name = "Hash plaintext subscription tokens", skip_all)]
+pub async fn hash_plaintext_tokens(
+    connection: &mut PgConnection,
+    secret: &Secret<String>,
+) -> Result<(), anyhow::Error> {
+    let plaintext_tokens = sqlx::query!(
+        r#"
+        SELECT subscription_token AS "subscription_token!"
+        FROM subscription_tokens
+        WHERE subscription_token IS NOT NULL
+        "#,
+    )
+    .fetch_all(&mut *connection)
+    .await
+    .context("Failed to fetch plaintext subscription tokens")?;
+
+    for row in plaintext_tokens {
+        let query = match SubscriptionToken::parse(row.subscription_token.clone()) {
+            Ok(token) => sqlx::query!(
+                r#"
+                UPDATE subscription_tokens
+                SET subscription_token_hash = $1, subscription_token = NULL
+                WHERE subscription_token = $2
+                "#,
+                token.hash(secret),
+                row.subscription_token,
+            ),
+            // Such a token could never be confirmed anyway.
+            Err(e) => {
+                tracing::warn!("Deleting invalid subscription token: {e}");
+                sqlx::query!(
+                    r#"
+                    DELETE FROM subscription_tokens
+                    WHERE subscription_token = $1
+                    "#,
+                    row.subscription_token,
+                )
+            }
+        };
+
+        query
+            .execute(&mut *connection)
+            .await
+            .context("Failed to hash plaintext subscription token")?;
+    }
+
+    Ok(())
+}
+
+/// Recomputes the canonical emails of subscribers, and of their queued
+/// deliveries and pending email changes, if the local part rules have changed
+/// since they were last computed. Subscribers ending up with the same
+/// canonical email are merged like in the migration adding it: a confirmed
+/// subscription is kept over a pending one, and the oldest one among those.
+#[tracing::instrument(name = "Recanonicalise subscriber emails", skip_all)]
+pub async fn recanonicalise_emails(
+    connection: &mut PgConnection,
+    rules: &LocalPartRules,
+) -> Result<(), anyhow::Error> {
+    let rules_json =
+        serde_json::to_string(rules).context("Failed to serialise local part rules")?;
+    let mut transaction = connection
+        .begin()
+        .await
+        .context("Failed to begin transaction")?;
+
+    let computed_with = sqlx::query_scalar!("SELECT rules FROM canonical_email_rules")
+        .fetch_one(&mut *transaction)
+        .await
+        .context("Failed to fetch local part rules of canonical emails")?;
+    if computed_with == rules_json {
+        return Ok(());
+    }
+
+    let subscribers = sqlx::query!(
+        r#"
+        SELECT id, email, canonical_email
+        FROM subscriptions
+        ORDER BY status = 'confirmed' DESC, subscribed_at, id
+        "#,
+    )
+    .fetch_all(&mut *transaction)
+    .await
+    .context("Failed to fetch subscribers")?;
+
+    let mut taken = HashSet::new();
+    let mut changed = Vec::new();
+    let mut merged = 0;
+    for subscriber in subscribers {
+        let canonical_email = rules.canonicalise(&subscriber.email);
+        if !taken.insert(canonical_email.clone()) {
+            tracing::warn!(
+                subscriber_id = %subscriber.id,
+                "Merging subscriber with the same canonical email as another one"
+            );
+            delete_subscriber(&mut transaction, subscriber.id).await?;
+            merged += 1;
+        } else if canonical_email != subscriber.canonical_email {
+            changed.push((subscriber.id, subscriber.canonical_email, canonical_email));
+        }
+    }
+
+    // Moved through a placeholder first, as canonical emails are unique and
+    // one subscriber may get the one another is giving up.
+    for (subscriber_id, old_canonical_email, _) in &changed {
+        move_canonical_email(
+            &mut transaction,
+            *subscriber_id,
+            old_canonical_email,
+            &subscriber_id.to_string(),
+        )
+        .await?;
+    }
+    for (subscriber_id, _, new_canonical_email) in &changed {
+        move_canonical_email(
+            &mut transaction,
+            *subscriber_id,
+            &subscriber_id.to_string(),
+            new_canonical_email,
+        )
+        .await?;
+    }
+
+    let email_changes =
+        sqlx::query!("SELECT subscription_token_hash, new_email FROM email_changes")
+            .fetch_all(&mut *transaction)
+            .await
+            .context("Failed to fetch email changes")?;
+    for email_change in email_changes {
+        transaction
+            .execute(sqlx::query!(
+                r#"
+                UPDATE email_changes
+                SET new_canonical_email = $1
+                WHERE subscription_token_hash = $2
+                "#,
+                rules.canonicalise(&email_change.new_email),
+                email_change.subscription_token_hash,
+            ))
+            .await
+            .context("Failed to update email change")?;
+    }
+
+    transaction
+        .execute(sqlx::query!(
+            "UPDATE canonical_email_rules SET rules = $1",
+            rules_json,
+        ))
+        .await
+        .context("Failed to store local part rules of canonical emails")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit transaction")?;
+
+    tracing::info!(
+        changed = changed.len(),
+        merged,
+        "Recomputed canonical emails with new local part rules"
+    );
+
+    Ok(())
+}
+
+async fn move_canonical_email(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    from: &str,
+    to: &str,
+) -> Result<(), anyhow::Error> {
+    transaction
+        .execute(sqlx::query!(
+            r#"
+            UPDATE subscriptions
+            SET canonical_email = $1
+            WHERE id = $2
+            "#,
+            to,
+            subscriber_id,
+        ))
+        .await
+        .context("Failed to update canonical email")?;
+
+    transaction
+        .execute(sqlx::query!(
+            r#"
+            UPDATE issue_delivery_queue
+            SET canonical_email = $1
+            WHERE canonical_email = $2
+            "#,
+            to,
+            from,
+        ))
+        .await
+        .context("Failed to move pending deliveries")?;
+
+    Ok(())
+}
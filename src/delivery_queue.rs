@@ -0,0 +1,182 @@
+use crate::queue_listener::notify_workers;
+use anyhow::Context;
+use serde::Serialize;
+use sqlx::PgPool;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// An email waiting to be sent to a subscriber.
+#[derive(Debug, Serialize)]
+pub struct QueuedTask {
+    pub issue_id: Uuid,
+    pub title: String,
+    pub canonical_email: String,
+}
+
+/// Lists queued tasks, oldest issues first, optionally those of one issue only.
+#[tracing::instrument(skip(db_pool))]
+pub async fn list_queued_tasks(
+    db_pool: &PgPool,
+    issue_id: Option<Uuid>,
+    limit: i64,
+) -> Result<Vec<QueuedTask>, anyhow::Error> {
+    sqlx::query_as!(
+        QueuedTask,
+        r#"
+        SELECT
+            q.newsletter_issue_id AS issue_id,
+            i.title,
+            q.canonical_email
+        FROM issue_delivery_queue q
+        JOIN newsletter_issues i USING (newsletter_issue_id)
+        WHERE $1::uuid IS NULL OR q.newsletter_issue_id = $1
+        ORDER BY i.published_at, q.canonical_email
+        LIMIT $2
+        "#,
+        issue_id,
+        limit,
+    )
+    .fetch_all(db_pool)
+    .await
+    .context("Failed to retrieve queued tasks")
+}
+
+/// Queues the failed deliveries of an issue again, dropping their outcome.
+/// Returns how many deliveries have been queued.
+#[tracing::instrument(skip(db_pool))]
+pub async fn requeue_failed_deliveries(
+    db_pool: &PgPool,
+    issue_id: Uuid,
+) -> Result<u64, anyhow::Error> {
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to begin transaction")?;
+
+    // Outcomes are recorded with the address the email was sent to, while
+    // tasks are queued by canonical address.
+    let requeued = sqlx::query!(
+        r#"
+        WITH failed AS (
+            DELETE FROM issue_deliveries
+            WHERE newsletter_issue_id = $1 AND outcome = 'failed'
+            RETURNING newsletter_issue_id, subscriber_email
+        )
+        INSERT INTO issue_delivery_queue (newsletter_issue_id, canonical_email)
+        SELECT f.newsletter_issue_id, COALESCE(s.canonical_email, f.subscriber_email)
+        FROM failed f
+        LEFT JOIN subscriptions s ON s.email = f.subscriber_email
+        ON CONFLICT DO NOTHING
+        "#,
+        issue_id,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to requeue failed deliveries")?
+    .rows_affected();
+
+    notify_workers(&mut *transaction).await?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit transaction")?;
+
+    Ok(requeued)
+}
+
+/// Drops queued tasks, of one issue or of all of them, so that they are
+/// never sent. Returns how many tasks have been dropped.
+#[tracing::instrument(skip(db_pool))]
+pub async fn purge_queued_tasks(
+    db_pool: &PgPool,
+    issue_id: Option<Uuid>,
+) -> Result<u64, anyhow::Error> {
+    let purged = sqlx::query!(
+        r#"
+        DELETE FROM issue_delivery_queue
+        WHERE $1::uuid IS NULL OR newsletter_issue_id = $1
+        "#,
+        issue_id,
+    )
+    .execute(db_pool)
+    .await
+    .context("Failed to purge queued tasks")?
+    .rows_affected();
+
+    Ok(purged)
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DeliveryStats {
+    pub issue_id: Uuid,
+    pub pending: i64,
+    pub sent: i64,
+    pub failed: i64,
+    pub skipped: i64,
+    pub opens: i64,
+    pub unique_opens: i64,
+    pub clicks: i64,
+    pub unique_clicks: i64,
+}
+
+/// Returns `None` if the issue does not exist.
+#[tracing::instrument(skip(db_pool))]
+pub async fn delivery_stats(
+    db_pool: &PgPool,
+    issue_id: Uuid,
+) -> Result<Option<DeliveryStats>, anyhow::Error> {
+    let stats = sqlx::query!(
+        r#"
+        SELECT
+            (
+                SELECT COUNT(*)
+                FROM issue_delivery_queue
+                WHERE newsletter_issue_id = $1
+            ) AS "pending!",
+            COUNT(*) FILTER (WHERE d.outcome = 'sent') AS "sent!",
+            COUNT(*) FILTER (WHERE d.outcome = 'failed') AS "failed!",
+            COUNT(*) FILTER (WHERE d.outcome = 'skipped') AS "skipped!",
+            (
+                SELECT COUNT(*)
+                FROM issue_tracking_events
+                WHERE newsletter_issue_id = $1 AND event_type = 'open'
+            ) AS "opens!",
+            (
+                SELECT COUNT(DISTINCT subscriber_id)
+                FROM issue_tracking_events
+                WHERE newsletter_issue_id = $1 AND event_type = 'open'
+            ) AS "unique_opens!",
+            (
+                SELECT COUNT(*)
+                FROM issue_tracking_events
+                WHERE newsletter_issue_id = $1 AND event_type = 'click'
+            ) AS "clicks!",
+            (
+                SELECT COUNT(DISTINCT subscriber_id)
+                FROM issue_tracking_events
+                WHERE newsletter_issue_id = $1 AND event_type = 'click'
+            ) AS "unique_clicks!"
+        FROM newsletter_issues i
+        LEFT JOIN issue_deliveries d USING (newsletter_issue_id)
+        WHERE i.newsletter_issue_id = $1
+        GROUP BY i.newsletter_issue_id
+        "#,
+        issue_id,
+    )
+    .fetch_optional(db_pool)
+    .await
+    .context("Failed to retrieve issue delivery stats")?
+    .map(|row| DeliveryStats {
+        issue_id,
+        pending: row.pending,
+        sent: row.sent,
+        failed: row.failed,
+        skipped: row.skipped,
+        opens: row.opens,
+        unique_opens: row.unique_opens,
+        clicks: row.clicks,
+        unique_clicks: row.unique_clicks,
+    });
+
+    Ok(stats)
+}
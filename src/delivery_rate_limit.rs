@@ -0,0 +1,224 @@
+use anyhow::Context;
+use sqlx::PgPool;
+use std::time::Duration;
+
+/// Token buckets limiting how many emails the workers send, per second and
+/// per hour. The buckets live in the database, so that all worker instances
+/// share the same budget.
+#[derive(Clone, Copy, Debug)]
+pub struct DeliveryRateLimit {
+    messages_per_second: f64,
+    messages_per_hour: f64,
+    default_retry_after: Duration,
+}
+
+impl DeliveryRateLimit {
+    /// Both limits are at least one message. `default_retry_after` is how
+    /// long sending pauses when the provider rate limits us without saying
+    /// for how long.
+    pub fn new(
+        messages_per_second: u32,
+        messages_per_hour: u32,
+        default_retry_after: Duration,
+    ) -> Self {
+        Self {
+            messages_per_second: messages_per_second.max(1).into(),
+            messages_per_hour: messages_per_hour.max(1).into(),
+            default_retry_after,
+        }
+    }
+
+    /// Takes a token for sending one email. Returns how long to wait instead
+    /// when the budget is exhausted or sending has been paused.
+    #[tracing::instrument(skip_all)]
+    pub async fn acquire(&self, db_pool: &PgPool) -> Result<Option<Duration>, anyhow::Error> {
+        let mut transaction = db_pool
+            .begin()
+            .await
+            .context("Failed to begin transaction")?;
+        let row = sqlx::query!(
+            r#"
+            SELECT second_tokens, hour_tokens, refilled_at, paused_until, now() AS "now!"
+            FROM delivery_rate_limit
+            FOR UPDATE
+            "#,
+        )
+        .fetch_one(&mut *transaction)
+        .await
+        .context("Failed to fetch the delivery rate limit")?;
+
+        if let Some(paused_until) = row.paused_until.filter(|until| *until > row.now) {
+            let wait = (paused_until - row.now).unsigned_abs();
+            return Ok(Some(wait));
+        }
+
+        let mut buckets = TokenBuckets {
+            second_tokens: row.second_tokens,
+            hour_tokens: row.hour_tokens,
+        };
+        let wait = self.take(&mut buckets, (row.now - row.refilled_at).as_seconds_f64());
+
+        sqlx::query!(
+            r#"
+            UPDATE delivery_rate_limit
+            SET second_tokens = $1, hour_tokens = $2, refilled_at = $3
+            "#,
+            buckets.second_tokens,
+            buckets.hour_tokens,
+            row.now,
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to update the delivery rate limit")?;
+
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit transaction")?;
+
+        Ok(wait)
+    }
+
+    /// Stops all workers from sending after the provider rate limited us,
+    /// for as long as it asked to. Returns how long sending is paused for.
+    #[tracing::instrument(skip(self, db_pool))]
+    pub async fn pause(
+        &self,
+        db_pool: &PgPool,
+        retry_after: Option<Duration>,
+    ) -> Result<Duration, anyhow::Error> {
+        let pause = retry_after.unwrap_or(self.default_retry_after);
+        sqlx::query!(
+            r#"
+            UPDATE delivery_rate_limit
+            SET paused_until = GREATEST(paused_until, now() + make_interval(secs => $1))
+            "#,
+            pause.as_secs_f64(),
+        )
+        .execute(db_pool)
+        .await
+        .context("Failed to pause delivery")?;
+
+        tracing::warn!(
+            pause_seconds = pause.as_secs_f64(),
+            "The email provider is rate limiting us. Pausing delivery."
+        );
+
+        Ok(pause)
+    }
+
+    /// Refills the buckets for the seconds elapsed since they were last
+    /// refilled, then takes a token from both, if both have one.
+    fn take(&self, buckets: &mut TokenBuckets, elapsed_seconds: f64) -> Option<Duration> {
+        let elapsed_seconds = elapsed_seconds.max(0.0);
+        let hourly_rate = self.messages_per_hour / 3600.0;
+
+        buckets.second_tokens = (buckets.second_tokens
+            + elapsed_seconds * self.messages_per_second)
+            .min(self.messages_per_second);
+        buckets.hour_tokens =
+            (buckets.hour_tokens + elapsed_seconds * hourly_rate).min(self.messages_per_hour);
+
+        if buckets.second_tokens >= 1.0 && buckets.hour_tokens >= 1.0 {
+            buckets.second_tokens -= 1.0;
+            buckets.hour_tokens -= 1.0;
+            return None;
+        }
+
+        let second_wait = (1.0 - buckets.second_tokens) / self.messages_per_second;
+        let hour_wait = (1.0 - buckets.hour_tokens) / hourly_rate;
+        Some(Duration::from_secs_f64(second_wait.max(hour_wait).max(0.0)))
+    }
+}
+
+#[derive(Debug)]
+struct TokenBuckets {
+    second_tokens: f64,
+    hour_tokens: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DeliveryRateLimit, TokenBuckets};
+    use claims::{assert_none, assert_some};
+    use std::time::Duration;
+
+    fn rate_limit(messages_per_second: u32, messages_per_hour: u32) -> DeliveryRateLimit {
+        DeliveryRateLimit::new(
+            messages_per_second,
+            messages_per_hour,
+            Duration::from_secs(60),
+        )
+    }
+
+    fn empty_buckets() -> TokenBuckets {
+        TokenBuckets {
+            second_tokens: 0.0,
+            hour_tokens: 0.0,
+        }
+    }
+
+    #[test]
+    fn a_full_second_bucket_allows_a_burst_of_its_size() {
+        // given
+        let rate_limit = rate_limit(5, 1000);
+        let mut buckets = empty_buckets();
+
+        // when
+        assert_none!(rate_limit.take(&mut buckets, 3600.0));
+        for _ in 0..4 {
+            assert_none!(rate_limit.take(&mut buckets, 0.0));
+        }
+        let wait = rate_limit.take(&mut buckets, 0.0);
+
+        // then
+        let wait = assert_some!(wait);
+        assert!((wait.as_secs_f64() - 0.2).abs() < 1e-6, "{wait:?}");
+    }
+
+    #[test]
+    fn the_hourly_budget_caps_sending_across_seconds() {
+        // given
+        let rate_limit = rate_limit(10, 2);
+        let mut buckets = empty_buckets();
+        assert_none!(rate_limit.take(&mut buckets, 3600.0));
+        assert_none!(rate_limit.take(&mut buckets, 1.0));
+
+        // when
+        let wait = rate_limit.take(&mut buckets, 1.0);
+
+        // then
+        let wait = assert_some!(wait);
+        assert!(wait > Duration::from_secs(1700), "{wait:?}");
+    }
+
+    #[test]
+    fn waiting_as_told_refills_enough_for_one_message() {
+        // given
+        let rate_limit = rate_limit(2, 3600);
+        let mut buckets = empty_buckets();
+        let wait = assert_some!(rate_limit.take(&mut buckets, 0.0));
+
+        // when
+        let result = rate_limit.take(&mut buckets, wait.as_secs_f64());
+
+        // then
+        assert_none!(result);
+    }
+
+    #[test]
+    fn clock_going_backwards_does_not_drain_the_buckets() {
+        // given
+        let rate_limit = rate_limit(1, 3600);
+        let mut buckets = TokenBuckets {
+            second_tokens: 1.0,
+            hour_tokens: 1.0,
+        };
+
+        // when
+        let result = rate_limit.take(&mut buckets, -5.0);
+
+        // then
+        assert_none!(result);
+    }
+}
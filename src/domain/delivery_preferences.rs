@@ -0,0 +1,65 @@
+use serde::Deserialize;
+
+/// Parts of an issue sent to a subscriber.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum ContentFormat {
+    Html,
+    PlainText,
+}
+
+impl AsRef<str> for ContentFormat {
+    fn as_ref(&self) -> &'static str {
+        match self {
+            ContentFormat::Html => "html",
+            ContentFormat::PlainText => "plain_text",
+        }
+    }
+}
+
+impl TryFrom<String> for ContentFormat {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.as_ref() {
+            "html" => Ok(ContentFormat::Html),
+            "plain_text" => Ok(ContentFormat::PlainText),
+            other => Err(format!("`{other}` is not a valid variant of ContentFormat")),
+        }
+    }
+}
+
+/// How often issues are sent to a subscriber. Issues published between
+/// digests are sent together in a single email.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum DigestFrequency {
+    Immediate,
+    Daily,
+    Weekly,
+}
+
+impl AsRef<str> for DigestFrequency {
+    fn as_ref(&self) -> &'static str {
+        match self {
+            DigestFrequency::Immediate => "immediate",
+            DigestFrequency::Daily => "daily",
+            DigestFrequency::Weekly => "weekly",
+        }
+    }
+}
+
+impl TryFrom<String> for DigestFrequency {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.as_ref() {
+            "immediate" => Ok(DigestFrequency::Immediate),
+            "daily" => Ok(DigestFrequency::Daily),
+            "weekly" => Ok(DigestFrequency::Weekly),
+            other => Err(format!(
+                "`{other}` is not a valid variant of DigestFrequency"
+            )),
+        }
+    }
+}
@@ -1,11 +1,17 @@
+mod delivery_preferences;
 mod new_subscriber;
+mod preferences_token;
 mod subscriber_email;
 mod subscriber_name;
 mod subscription_status;
 mod subscription_token;
+mod tracking_token;
 
+pub use delivery_preferences::{ContentFormat, DigestFrequency};
 pub use new_subscriber::NewSubscriber;
-pub use subscriber_email::SubscriberEmail;
+pub use preferences_token::{PreferencesLinks, PreferencesToken};
+pub use subscriber_email::{LocalPartRules, SubscriberEmail};
 pub use subscriber_name::SubscriberName;
 pub use subscription_status::SubscriptionStatus;
 pub use subscription_token::{token_regex, SubscriptionToken};
+pub use tracking_token::{issue_links, unescape_link, TrackedEvent, TrackingLinks, TrackingToken};
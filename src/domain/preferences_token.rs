@@ -0,0 +1,131 @@
+use axum::http::Uri;
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use uuid::Uuid;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Long-lived token giving a subscriber access to their preferences. It is
+/// the subscriber id signed with the application secret, so that it can be
+/// put in every delivered issue without being stored.
+#[derive(Clone, Debug)]
+pub struct PreferencesToken(String);
+
+impl PreferencesToken {
+    pub fn issue(subscriber_id: Uuid, secret: &Secret<String>) -> Self {
+        let signature = hex::encode(mac(subscriber_id, secret).finalize().into_bytes());
+        Self(format!("{}.{signature}", subscriber_id.simple()))
+    }
+
+    /// Returns the subscriber id if the token has been issued with `secret`.
+    pub fn verify(token: &str, secret: &Secret<String>) -> Option<Uuid> {
+        let (subscriber_id, signature) = token.split_once('.')?;
+        let subscriber_id = Uuid::try_parse(subscriber_id).ok()?;
+        let signature = hex::decode(signature).ok()?;
+
+        mac(subscriber_id, secret)
+            .verify_slice(&signature)
+            .ok()
+            .map(|_| subscriber_id)
+    }
+}
+
+fn mac(subscriber_id: Uuid, secret: &Secret<String>) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret.expose_secret().as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(b"preferences:");
+    mac.update(subscriber_id.as_bytes());
+    mac
+}
+
+impl AsRef<str> for PreferencesToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Builds links to the preferences page, added to emails sent to subscribers.
+#[derive(Clone, Debug)]
+pub struct PreferencesLinks {
+    base_url: Uri,
+    secret: Secret<String>,
+}
+
+impl PreferencesLinks {
+    pub fn new(base_url: Uri, secret: Secret<String>) -> Self {
+        Self { base_url, secret }
+    }
+
+    pub fn link(&self, subscriber_id: Uuid) -> String {
+        format!(
+            "{}subscriptions/preferences?token={}",
+            self.base_url,
+            PreferencesToken::issue(subscriber_id, &self.secret).as_ref()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PreferencesToken;
+    use claims::{assert_none, assert_some_eq};
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    fn secret() -> Secret<String> {
+        Secret::new("secret".to_string())
+    }
+
+    #[test]
+    fn issued_tokens_verify_to_the_subscriber_id() {
+        // given
+        let subscriber_id = Uuid::new_v4();
+        let token = PreferencesToken::issue(subscriber_id, &secret());
+
+        // when
+        let result = PreferencesToken::verify(token.as_ref(), &secret());
+
+        // then
+        assert_some_eq!(result, subscriber_id);
+    }
+
+    #[test]
+    fn tokens_issued_with_another_secret_are_rejected() {
+        // given
+        let token = PreferencesToken::issue(Uuid::new_v4(), &Secret::new("another".to_string()));
+
+        // when
+        let result = PreferencesToken::verify(token.as_ref(), &secret());
+
+        // then
+        assert_none!(result);
+    }
+
+    #[test]
+    fn tokens_with_a_swapped_subscriber_id_are_rejected() {
+        // given
+        let token = PreferencesToken::issue(Uuid::new_v4(), &secret());
+        let (_, signature) = token.as_ref().split_once('.').unwrap();
+        let forged = format!("{}.{signature}", Uuid::new_v4().simple());
+
+        // when
+        let result = PreferencesToken::verify(&forged, &secret());
+
+        // then
+        assert_none!(result);
+    }
+
+    #[test]
+    fn malformed_tokens_are_rejected() {
+        for token in [
+            "",
+            ".",
+            "not-a-token",
+            "abc.def",
+            &Uuid::new_v4().to_string(),
+        ] {
+            assert_none!(PreferencesToken::verify(token, &secret()), "{token}");
+        }
+    }
+}
@@ -1,23 +1,121 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 use validator::validate_email;
 
+/// A subscriber's address, together with its canonical form identifying the
+/// subscriber: addresses with the same canonical form reach the same mailbox.
 #[derive(Clone, Debug, Deserialize)]
-pub struct SubscriberEmail(String);
+#[serde(try_from = "String")]
+pub struct SubscriberEmail {
+    address: String,
+    canonical: String,
+}
+
+/// How local parts of addresses are canonicalised. Domains are always
+/// lowercased, as they are case-insensitive, and punycode-encoded.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LocalPartRules {
+    /// Treat `Jan.Kowalski` and `jan.kowalski` as the same mailbox. Mail
+    /// servers are allowed to tell them apart, but practically none do.
+    pub case_insensitive: bool,
+    /// Drop subaddresses, so that `jan+news` becomes `jan`.
+    pub strip_plus_tags: bool,
+    /// Domains ignoring dots in local parts, like `gmail.com`.
+    #[serde(default)]
+    pub dot_insensitive_domains: Vec<String>,
+}
+
+impl Default for LocalPartRules {
+    fn default() -> Self {
+        Self {
+            case_insensitive: true,
+            strip_plus_tags: false,
+            dot_insensitive_domains: Vec::new(),
+        }
+    }
+}
+
+impl LocalPartRules {
+    pub fn canonicalise(&self, address: &str) -> String {
+        let (local_part, domain) = address.rsplit_once('@').unwrap_or(("", address));
+        let domain = idna::domain_to_ascii(domain).unwrap_or_else(|_| domain.to_lowercase());
+
+        let mut local_part = local_part.to_string();
+        if self.strip_plus_tags {
+            if let Some((mailbox, _tag)) = local_part.split_once('+') {
+                local_part = mailbox.to_string();
+            }
+        }
+        if self
+            .dot_insensitive_domains
+            .iter()
+            .any(|dot_insensitive| dot_insensitive.eq_ignore_ascii_case(&domain))
+        {
+            local_part = local_part.replace('.', "");
+        }
+        if self.case_insensitive {
+            local_part = local_part.to_lowercase();
+        }
+
+        format!("{local_part}@{domain}")
+    }
+}
 
 impl SubscriberEmail {
+    /// Parses an address, canonicalising it with the default rules.
     pub fn parse(s: String) -> Result<Self, String> {
+        Self::parse_with(s, &LocalPartRules::default())
+    }
+
+    pub fn parse_with(s: String, rules: &LocalPartRules) -> Result<Self, String> {
         if validate_email(&s) {
-            Ok(Self(s))
+            Ok(Self {
+                canonical: rules.canonicalise(&s),
+                address: s,
+            })
         } else {
             Err(format!("`{s}` email has invalid format"))
         }
     }
+
+    /// Recomputes the canonical form with given rules.
+    pub fn with_rules(self, rules: &LocalPartRules) -> Self {
+        Self {
+            canonical: rules.canonicalise(&self.address),
+            address: self.address,
+        }
+    }
+
+    pub fn canonical(&self) -> &str {
+        &self.canonical
+    }
+
+    pub fn domain(&self) -> &str {
+        self.address
+            .rsplit_once('@')
+            .map(|(_, domain)| domain)
+            .unwrap_or_default()
+    }
+
+    /// Brings the domain to a single form: lowercase ASCII, with
+    /// internationalised labels normalised and punycode-encoded. The local
+    /// part is left as is, it can only contain ASCII characters anyway.
+    pub fn normalise(&self) -> Result<Self, String> {
+        let (local_part, domain) = self
+            .address
+            .rsplit_once('@')
+            .ok_or_else(|| format!("`{}` email has no domain", self.address))?;
+
+        let domain = idna::domain_to_ascii(domain)
+            .map_err(|_| format!("`{domain}` is not a valid email domain"))?;
+
+        Self::parse(format!("{local_part}@{domain}"))
+    }
 }
 
 impl AsRef<str> for SubscriberEmail {
     fn as_ref(&self) -> &str {
-        &self.0
+        &self.address
     }
 }
 
@@ -31,13 +129,13 @@ impl TryFrom<String> for SubscriberEmail {
 
 impl Display for SubscriberEmail {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.0.fmt(f)
+        self.address.fmt(f)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::SubscriberEmail;
+    use super::{LocalPartRules, SubscriberEmail};
     use claims::{assert_err, assert_ok};
     use helpers::valid_emails;
     use proptest::prelude::proptest;
@@ -89,6 +187,89 @@ mod tests {
         assert_err!(result);
     }
 
+    #[test]
+    fn normalised_domain_is_lowercase_ascii() {
+        // given
+        let email = SubscriberEmail::parse("Imie.Nazwisko@EXAMPLE.com".to_string()).unwrap();
+
+        // when
+        let result = email.normalise().unwrap();
+
+        // then
+        assert_eq!(result.as_ref(), "Imie.Nazwisko@example.com");
+    }
+
+    #[test]
+    fn internationalised_domain_is_punycode_encoded() {
+        // given
+        let email = SubscriberEmail::parse("imie@Żółw.pl".to_string()).unwrap();
+
+        // when
+        let result = email.normalise().unwrap();
+
+        // then
+        assert_eq!(result.as_ref(), "imie@xn--w-uga1v8h.pl");
+        assert_eq!(result.domain(), "xn--w-uga1v8h.pl");
+    }
+
+    #[test]
+    fn differently_composed_unicode_domains_are_normalised_to_the_same_form() {
+        // given
+        let composed = SubscriberEmail::parse("zoe@caf\u{e9}.fr".to_string()).unwrap();
+        let decomposed = SubscriberEmail::parse("zoe@cafe\u{301}.fr".to_string()).unwrap();
+
+        // when
+        let composed = composed.normalise().unwrap();
+        let decomposed = decomposed.normalise().unwrap();
+
+        // then
+        assert_eq!(composed.as_ref(), decomposed.as_ref());
+    }
+
+    #[test]
+    fn canonical_form_is_lowercase_by_default() {
+        // when
+        let email = SubscriberEmail::parse("Imie.Nazwisko@Example.COM".to_string()).unwrap();
+
+        // then
+        assert_eq!(email.as_ref(), "Imie.Nazwisko@Example.COM");
+        assert_eq!(email.canonical(), "imie.nazwisko@example.com");
+    }
+
+    #[test]
+    fn case_sensitive_local_parts_keep_their_case() {
+        // given
+        let rules = LocalPartRules {
+            case_insensitive: false,
+            ..Default::default()
+        };
+
+        // when
+        let email =
+            SubscriberEmail::parse_with("Imie.Nazwisko@Example.COM".to_string(), &rules).unwrap();
+
+        // then
+        assert_eq!(email.canonical(), "Imie.Nazwisko@example.com");
+    }
+
+    #[test]
+    fn plus_tags_and_dots_are_dropped_when_configured() {
+        // given
+        let rules = LocalPartRules {
+            case_insensitive: true,
+            strip_plus_tags: true,
+            dot_insensitive_domains: vec!["gmail.com".to_string()],
+        };
+
+        // when
+        let gmail = SubscriberEmail::parse_with("Imie.Nazwisko+news@GMail.com".into(), &rules);
+        let other = SubscriberEmail::parse_with("imie.nazwisko+news@example.com".into(), &rules);
+
+        // then
+        assert_eq!(gmail.unwrap().canonical(), "imienazwisko@gmail.com");
+        assert_eq!(other.unwrap().canonical(), "imie.nazwisko@example.com");
+    }
+
     mod helpers {
         use fake::{
             faker::internet::en::{FreeEmail, SafeEmail},
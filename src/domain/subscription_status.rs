@@ -2,6 +2,11 @@
 pub enum SubscriptionStatus {
     PendingConfirmation,
     Confirmed,
+    Unsubscribed,
+    /// Suppressed after the address bounced too often.
+    Bounced,
+    /// Suppressed after the subscriber marked our email as spam.
+    Complained,
 }
 
 impl AsRef<str> for SubscriptionStatus {
@@ -9,6 +14,9 @@ impl AsRef<str> for SubscriptionStatus {
         match self {
             SubscriptionStatus::PendingConfirmation => "pending_confirmation",
             SubscriptionStatus::Confirmed => "confirmed",
+            SubscriptionStatus::Unsubscribed => "unsubscribed",
+            SubscriptionStatus::Bounced => "bounced",
+            SubscriptionStatus::Complained => "complained",
         }
     }
 }
@@ -20,6 +28,9 @@ impl TryFrom<String> for SubscriptionStatus {
         match s.as_ref() {
             "pending_confirmation" => Ok(SubscriptionStatus::PendingConfirmation),
             "confirmed" => Ok(SubscriptionStatus::Confirmed),
+            "unsubscribed" => Ok(SubscriptionStatus::Unsubscribed),
+            "bounced" => Ok(SubscriptionStatus::Bounced),
+            "complained" => Ok(SubscriptionStatus::Complained),
             other => Err(format!(
                 "`{other}` is not a valid variant of SubscriptionStatus",
             )),
@@ -1,10 +1,14 @@
+use hmac::{Hmac, Mac};
 use once_cell::sync::Lazy;
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
 use regex::Regex;
 use secrecy::{ExposeSecret, Secret};
 use serde::Deserialize;
+use sha2::Sha256;
 use std::iter::repeat_with;
 
+type HmacSha256 = Hmac<Sha256>;
+
 const TOKEN_CHARS: &str = r"[[:alnum:]]";
 const TOKEN_LENGTH: usize = 25;
 
@@ -42,6 +46,27 @@ impl SubscriptionToken {
             Err(format!("Invalid subscription token: `{s}`"))
         }
     }
+
+    /// HMAC of the token keyed with the application secret. Only the hash is
+    /// stored, so that leaked rows cannot be used to confirm subscriptions.
+    pub fn hash(&self, secret: &Secret<String>) -> String {
+        hex::encode(self.mac(secret).finalize().into_bytes())
+    }
+
+    /// Checks the token against a stored hash in constant time.
+    pub fn verify(&self, secret: &Secret<String>, hash: &str) -> bool {
+        match hex::decode(hash) {
+            Ok(hash) => self.mac(secret).verify_slice(&hash).is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    fn mac(&self, secret: &Secret<String>) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(secret.expose_secret().as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(self.expose_secret().as_bytes());
+        mac
+    }
 }
 
 impl ExposeSecret<String> for SubscriptionToken {
@@ -64,7 +89,7 @@ mod tests {
     use claims::{assert_err, assert_ok};
     use helpers::{invalid_length_tokens, non_alnum_tokens, valid_tokens};
     use proptest::prelude::proptest;
-    use secrecy::ExposeSecret;
+    use secrecy::{ExposeSecret, Secret};
 
     proptest! {
         #[test]
@@ -122,6 +147,62 @@ mod tests {
         }
     }
 
+    proptest! {
+        #[test]
+        fn tokens_verify_against_their_own_hash(token in valid_tokens()) {
+            // given
+            let secret = Secret::new("secret".to_string());
+
+            // when
+            let hash = token.hash(&secret);
+
+            // then
+            assert!(token.verify(&secret, &hash));
+        }
+    }
+
+    #[test]
+    fn hash_does_not_contain_the_token() {
+        // given
+        let secret = Secret::new("secret".to_string());
+        let token = SubscriptionToken::generate();
+
+        // when
+        let hash = token.hash(&secret);
+
+        // then
+        assert!(!hash.contains(token.expose_secret().as_str()));
+        assert_eq!(hash, token.hash(&secret));
+    }
+
+    #[test]
+    fn tokens_do_not_verify_against_hashes_keyed_with_another_secret() {
+        // given
+        let token = SubscriptionToken::generate();
+        let hash = token.hash(&Secret::new("another secret".to_string()));
+
+        // when
+        let result = token.verify(&Secret::new("secret".to_string()), &hash);
+
+        // then
+        assert!(!result);
+    }
+
+    #[test]
+    fn tokens_do_not_verify_against_other_tokens_hashes() {
+        // given
+        let secret = Secret::new("secret".to_string());
+        let token = SubscriptionToken::generate();
+        let hash = SubscriptionToken::generate().hash(&secret);
+
+        // when
+        let result = token.verify(&secret, &hash);
+
+        // then
+        assert!(!result);
+        assert!(!token.verify(&secret, "not a hex string"));
+    }
+
     mod helpers {
         use super::super::TOKEN_LENGTH;
         use crate::domain::SubscriptionToken;
@@ -0,0 +1,290 @@
+use axum::http::Uri;
+use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use uuid::Uuid;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Absolute links in `href` attributes, quoted either way.
+static LINK_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?i)\bhref\s*=\s*(?:"(https?://[^"]*)"|'(https?://[^']*)')"#)
+        .expect("Failed to compile link regex")
+});
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrackedEvent {
+    Open,
+    /// A click on the link at the given index of `issue_links`.
+    Click(usize),
+}
+
+/// Token identifying a subscriber opening an issue or clicking one of its
+/// links. Like `PreferencesToken`, it is signed rather than stored.
+#[derive(Clone, Debug)]
+pub struct TrackingToken(String);
+
+impl TrackingToken {
+    pub fn issue(
+        issue_id: Uuid,
+        subscriber_id: Uuid,
+        event: TrackedEvent,
+        secret: &Secret<String>,
+    ) -> Self {
+        let payload = payload(issue_id, subscriber_id, event);
+        let signature = hex::encode(mac(&payload, secret).finalize().into_bytes());
+        Self(format!("{payload}.{signature}"))
+    }
+
+    /// Returns the issue id, subscriber id and event if the token has been
+    /// issued with `secret`.
+    pub fn verify(token: &str, secret: &Secret<String>) -> Option<(Uuid, Uuid, TrackedEvent)> {
+        let (payload, signature) = token.rsplit_once('.')?;
+        let signature = hex::decode(signature).ok()?;
+        mac(payload, secret).verify_slice(&signature).ok()?;
+
+        let mut parts = payload.split('.');
+        let issue_id = Uuid::try_parse(parts.next()?).ok()?;
+        let subscriber_id = Uuid::try_parse(parts.next()?).ok()?;
+        let event = match parts.next()? {
+            "o" => TrackedEvent::Open,
+            event => TrackedEvent::Click(event.strip_prefix('c')?.parse().ok()?),
+        };
+
+        Some((issue_id, subscriber_id, event))
+    }
+}
+
+fn payload(issue_id: Uuid, subscriber_id: Uuid, event: TrackedEvent) -> String {
+    let event = match event {
+        TrackedEvent::Open => "o".to_string(),
+        TrackedEvent::Click(index) => format!("c{index}"),
+    };
+    format!("{}.{}.{event}", issue_id.simple(), subscriber_id.simple())
+}
+
+fn mac(payload: &str, secret: &Secret<String>) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret.expose_secret().as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(b"tracking:");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+impl AsRef<str> for TrackingToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Distinct absolute links of an issue's HTML content, in order of
+/// appearance. Click tokens refer to links by their index in this list, so
+/// that the redirector only ever sends people to links of the issue.
+pub fn issue_links(html_content: &str) -> Vec<String> {
+    let mut links: Vec<String> = Vec::new();
+    for captures in LINK_REGEX.captures_iter(html_content) {
+        let link = quoted_link(&captures);
+        if !links.iter().any(|known| known == link) {
+            links.push(link.to_string());
+        }
+    }
+    links
+}
+
+/// Turns a link, as written in HTML, into the URL it points to.
+pub fn unescape_link(link: &str) -> String {
+    link.replace("&amp;", "&")
+}
+
+fn quoted_link<'h>(captures: &Captures<'h>) -> &'h str {
+    captures
+        .get(1)
+        .or_else(|| captures.get(2))
+        .map(|link| link.as_str())
+        .unwrap_or_default()
+}
+
+/// Adds open and click tracking to issues sent to subscribers, unless it has
+/// been disabled globally.
+#[derive(Clone, Debug)]
+pub struct TrackingLinks {
+    base_url: Uri,
+    secret: Secret<String>,
+    enabled: bool,
+}
+
+impl TrackingLinks {
+    pub fn new(base_url: Uri, secret: Secret<String>, enabled: bool) -> Self {
+        Self {
+            base_url,
+            secret,
+            enabled,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Points links at the redirector and appends the pixel, as chosen for
+    /// the issue.
+    pub fn instrument(
+        &self,
+        html_content: &str,
+        issue_id: Uuid,
+        subscriber_id: Uuid,
+        track_opens: bool,
+        track_clicks: bool,
+    ) -> String {
+        if !self.enabled {
+            return html_content.to_string();
+        }
+
+        let mut html_content = if track_clicks {
+            let links = issue_links(html_content);
+            LINK_REGEX
+                .replace_all(html_content, |captures: &Captures| {
+                    let link = quoted_link(captures);
+                    let index = links
+                        .iter()
+                        .position(|known| known == link)
+                        .expect("Links are extracted with the same regex");
+                    let token = self.token(issue_id, subscriber_id, TrackedEvent::Click(index));
+                    format!(r#"href="{}r/{token}""#, self.base_url)
+                })
+                .into_owned()
+        } else {
+            html_content.to_string()
+        };
+
+        if track_opens {
+            let token = self.token(issue_id, subscriber_id, TrackedEvent::Open);
+            html_content.push_str(&format!(
+                r#"<img src="{}o/{token}" width="1" height="1" alt="">"#,
+                self.base_url
+            ));
+        }
+
+        html_content
+    }
+
+    fn token(&self, issue_id: Uuid, subscriber_id: Uuid, event: TrackedEvent) -> String {
+        TrackingToken::issue(issue_id, subscriber_id, event, &self.secret)
+            .as_ref()
+            .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{issue_links, TrackedEvent, TrackingLinks, TrackingToken};
+    use axum::http::Uri;
+    use claims::{assert_none, assert_some_eq};
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    fn secret() -> Secret<String> {
+        Secret::new("secret".to_string())
+    }
+
+    fn tracking_links(enabled: bool) -> TrackingLinks {
+        TrackingLinks::new(Uri::from_static("https://example.com/"), secret(), enabled)
+    }
+
+    #[test]
+    fn issued_tokens_verify_to_the_issue_subscriber_and_event() {
+        // given
+        let (issue_id, subscriber_id) = (Uuid::new_v4(), Uuid::new_v4());
+
+        for event in [TrackedEvent::Open, TrackedEvent::Click(3)] {
+            let token = TrackingToken::issue(issue_id, subscriber_id, event, &secret());
+
+            // when
+            let result = TrackingToken::verify(token.as_ref(), &secret());
+
+            // then
+            assert_some_eq!(result, (issue_id, subscriber_id, event));
+        }
+    }
+
+    #[test]
+    fn tampered_tokens_are_rejected() {
+        // given
+        let token = TrackingToken::issue(
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            TrackedEvent::Click(0),
+            &secret(),
+        );
+        let (payload, signature) = token.as_ref().rsplit_once('.').unwrap();
+        let forged = format!("{}1.{signature}", payload);
+
+        // when
+        let result = TrackingToken::verify(&forged, &secret());
+
+        // then
+        assert_none!(result);
+    }
+
+    #[test]
+    fn malformed_tokens_are_rejected() {
+        for token in ["", ".", "not-a-token", "abc.def.o.0123"] {
+            assert_none!(TrackingToken::verify(token, &secret()), "{token}");
+        }
+    }
+
+    #[test]
+    fn issue_links_are_distinct_absolute_links() {
+        // given
+        let html = r#"<a href="https://a.example/">A</a> <a href='http://b.example/?x=1&amp;y=2'>B</a>
+            <a href="https://a.example/">A again</a> <a href="/relative">R</a>
+            <a href="mailto:imie@example.com">M</a>"#;
+
+        // when
+        let links = issue_links(html);
+
+        // then
+        assert_eq!(
+            links,
+            ["https://a.example/", "http://b.example/?x=1&amp;y=2"]
+        );
+    }
+
+    #[test]
+    fn instrumented_html_points_links_at_the_redirector_and_has_a_pixel() {
+        // given
+        let html = r#"<a href="https://a.example/">A</a><a href="/relative">R</a>"#;
+
+        // when
+        let instrumented =
+            tracking_links(true).instrument(html, Uuid::new_v4(), Uuid::new_v4(), true, true);
+
+        // then
+        assert!(!instrumented.contains("https://a.example/"));
+        assert!(instrumented.contains(r#"href="https://example.com/r/"#));
+        assert!(instrumented.contains(r#"href="/relative""#));
+        assert!(instrumented.contains(r#"<img src="https://example.com/o/"#));
+    }
+
+    #[test]
+    fn html_is_left_alone_when_tracking_is_disabled() {
+        // given
+        let html = r#"<a href="https://a.example/">A</a>"#;
+
+        for (enabled, track_opens, track_clicks) in [(false, true, true), (true, false, false)] {
+            // when
+            let instrumented = tracking_links(enabled).instrument(
+                html,
+                Uuid::new_v4(),
+                Uuid::new_v4(),
+                track_opens,
+                track_clicks,
+            );
+
+            // then
+            assert_eq!(instrumented, html);
+        }
+    }
+}
@@ -0,0 +1,372 @@
+use crate::{
+    delivery_rate_limit::DeliveryRateLimit,
+    domain::{ContentFormat, PreferencesLinks, SubscriberEmail, SubscriptionStatus},
+    email_client::{EmailClient, SendEmailError},
+    issue_delivery_worker::{send_issues, ExecutionOutcome, NewsletterIssue},
+    suppression_list::check_suppression,
+};
+use anyhow::Context;
+use sqlx::{PgExecutor, PgPool, Postgres, Transaction};
+use time::{Duration, OffsetDateTime};
+use tracing::Span;
+use uuid::Uuid;
+
+type PgTransaction = Transaction<'static, Postgres>;
+
+/// How many times sending a step is attempted before it is skipped.
+const MAX_SEND_ATTEMPTS: i32 = 5;
+
+/// How long to wait before sending a step again after failing to.
+const RETRY_DELAY: Duration = Duration::minutes(15);
+
+/// Enters a newly confirmed subscriber into every active sequence. The
+/// worker schedules the first step, so that delays are computed in one place.
+/// Subscribers who have been through a sequence before do not enter it again.
+#[tracing::instrument(name = "Enroll subscriber in drip sequences", skip(executor))]
+pub(crate) async fn enroll_subscriber<'e>(
+    executor: impl PgExecutor<'e>,
+    subscriber_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO drip_enrollments (
+            drip_sequence_id,
+            subscriber_id,
+            enrolled_at,
+            next_send_at
+        )
+        SELECT drip_sequence_id, $1, now(), now()
+        FROM drip_sequences
+        WHERE active
+        ON CONFLICT DO NOTHING
+        "#,
+        subscriber_id,
+    )
+    .execute(executor)
+    .await
+    .context("Failed to enroll subscriber in drip sequences")?;
+
+    Ok(())
+}
+
+/// Stops all sequences a subscriber is still going through.
+#[tracing::instrument(name = "Stop drip sequences of subscriber", skip(executor))]
+pub(crate) async fn stop_enrollments<'e>(
+    executor: impl PgExecutor<'e>,
+    subscriber_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE drip_enrollments
+        SET stopped_at = now(), next_send_at = NULL
+        WHERE subscriber_id = $1 AND next_send_at IS NOT NULL
+        "#,
+        subscriber_id,
+    )
+    .execute(executor)
+    .await
+    .context("Failed to stop drip sequences")?;
+
+    Ok(())
+}
+
+#[tracing::instrument(
+    skip_all,
+    fields(
+        drip_sequence_id=tracing::field::Empty,
+        subscriber_id=tracing::field::Empty),
+    err
+)]
+pub async fn try_execute_drip_step(
+    db_pool: &PgPool,
+    email_client: &EmailClient,
+    rate_limit: &DeliveryRateLimit,
+    preferences_links: &PreferencesLinks,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let Some((mut transaction, enrollment)) = dequeue_enrollment(db_pool).await? else {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    };
+    Span::current()
+        .record("drip_sequence_id", enrollment.drip_sequence_id.to_string())
+        .record("subscriber_id", enrollment.subscriber_id.to_string());
+
+    let subscriber = get_subscriber(&mut transaction, enrollment.subscriber_id).await?;
+    if subscriber.status != SubscriptionStatus::Confirmed.as_ref() {
+        tracing::info!("Subscriber is no longer confirmed. Stopping the sequence.");
+        stop_enrollments(&mut *transaction, enrollment.subscriber_id).await?;
+        transaction.commit().await?;
+        return Ok(ExecutionOutcome::TaskCompleted);
+    }
+
+    let Some(step) = get_step(&mut transaction, &enrollment, enrollment.next_position).await?
+    else {
+        complete_enrollment(&mut transaction, &enrollment).await?;
+        transaction.commit().await?;
+        return Ok(ExecutionOutcome::TaskCompleted);
+    };
+
+    let now = OffsetDateTime::now_utc();
+    let due_at = enrollment.enrolled_at + Duration::days(step.delay_days.into());
+    // Steps falling into a pause are sent once the pause is over.
+    let send_at = subscriber
+        .paused_until
+        .map_or(due_at, |until| until.max(due_at));
+    if send_at > now {
+        reschedule(&mut transaction, &enrollment, send_at).await?;
+        transaction.commit().await?;
+        return Ok(ExecutionOutcome::TaskCompleted);
+    }
+
+    let suppression = check_suppression(
+        &mut *transaction,
+        &subscriber.email,
+        &subscriber.canonical_email,
+    )
+    .await?;
+    if let Some(reason) = suppression {
+        tracing::info!(
+            reason = %reason,
+            "Subscriber is on the suppression list. Skipping the step."
+        );
+        advance(&mut transaction, &enrollment).await?;
+        transaction.commit().await?;
+        return Ok(ExecutionOutcome::TaskCompleted);
+    }
+
+    match SubscriberEmail::parse(subscriber.email) {
+        Ok(email) => {
+            if let Some(wait) = rate_limit.acquire(db_pool).await? {
+                return Ok(ExecutionOutcome::RateLimited(wait));
+            }
+            let issue = NewsletterIssue {
+                title: step.title,
+                text_content: step.text_content,
+                html_content: step.html_content,
+            };
+            if let Err(e) = send_issues(
+                email_client,
+                &email,
+                subscriber.content_format,
+                &issue.title,
+                std::slice::from_ref(&issue),
+                &preferences_links.link(enrollment.subscriber_id),
+            )
+            .await
+            {
+                if let Some(SendEmailError::RateLimited { retry_after }) = e.downcast_ref() {
+                    let pause = rate_limit.pause(db_pool, *retry_after).await?;
+                    return Ok(ExecutionOutcome::RateLimited(pause));
+                }
+                // The step stays pending, as the provider may well accept
+                // it later, unless it keeps failing.
+                if enrollment.failed_attempts + 1 < MAX_SEND_ATTEMPTS {
+                    tracing::warn!(
+                        error_cause_chain = ?e,
+                        error.message = %e,
+                        "Failed to send drip step to a confirmed subscriber. Retrying later."
+                    );
+                    record_failed_attempt(&mut transaction, &enrollment, now + RETRY_DELAY).await?;
+                    transaction.commit().await?;
+                    return Ok(ExecutionOutcome::TaskCompleted);
+                }
+                tracing::error!(
+                    error_cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to send drip step to a confirmed subscriber. Skipping."
+                );
+            }
+        }
+        Err(e) => tracing::error!(
+            error_cause_chain = ?e,
+            error.message = %e,
+            "Failed to send drip step to a confirmed subscriber. Their email is invalid."
+        ),
+    }
+
+    advance(&mut transaction, &enrollment).await?;
+    transaction.commit().await?;
+
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+struct Enrollment {
+    drip_sequence_id: Uuid,
+    subscriber_id: Uuid,
+    enrolled_at: OffsetDateTime,
+    next_position: i32,
+    failed_attempts: i32,
+}
+
+#[tracing::instrument(skip_all)]
+async fn dequeue_enrollment(
+    db_pool: &PgPool,
+) -> Result<Option<(PgTransaction, Enrollment)>, anyhow::Error> {
+    let mut transaction = db_pool.begin().await?;
+    let enrollment = sqlx::query_as!(
+        Enrollment,
+        r#"
+        SELECT drip_sequence_id, subscriber_id, enrolled_at, next_position, failed_attempts
+        FROM drip_enrollments
+        WHERE next_send_at <= now()
+        FOR UPDATE
+        SKIP LOCKED
+        LIMIT 1
+        "#,
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+
+    Ok(enrollment.map(|enrollment| (transaction, enrollment)))
+}
+
+struct Subscriber {
+    email: String,
+    canonical_email: String,
+    status: String,
+    content_format: ContentFormat,
+    paused_until: Option<OffsetDateTime>,
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_subscriber(
+    transaction: &mut PgTransaction,
+    subscriber_id: Uuid,
+) -> Result<Subscriber, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT email, canonical_email, status, content_format, paused_until
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id,
+    )
+    .fetch_one(&mut **transaction)
+    .await?;
+
+    Ok(Subscriber {
+        email: row.email,
+        canonical_email: row.canonical_email,
+        status: row.status,
+        content_format: row.content_format.try_into().map_err(anyhow::Error::msg)?,
+        paused_until: row.paused_until,
+    })
+}
+
+struct Step {
+    delay_days: i32,
+    title: String,
+    text_content: String,
+    html_content: String,
+}
+
+#[tracing::instrument(skip(transaction, enrollment))]
+async fn get_step(
+    transaction: &mut PgTransaction,
+    enrollment: &Enrollment,
+    position: i32,
+) -> Result<Option<Step>, anyhow::Error> {
+    let step = sqlx::query_as!(
+        Step,
+        r#"
+        SELECT delay_days, title, text_content, html_content
+        FROM drip_steps
+        WHERE drip_sequence_id = $1 AND position = $2
+        "#,
+        enrollment.drip_sequence_id,
+        position,
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+
+    Ok(step)
+}
+
+#[tracing::instrument(skip_all)]
+async fn reschedule(
+    transaction: &mut PgTransaction,
+    enrollment: &Enrollment,
+    send_at: OffsetDateTime,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE drip_enrollments
+        SET next_send_at = $3
+        WHERE drip_sequence_id = $1 AND subscriber_id = $2
+        "#,
+        enrollment.drip_sequence_id,
+        enrollment.subscriber_id,
+        send_at,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Leaves the step to be sent again at `retry_at`.
+#[tracing::instrument(skip_all)]
+async fn record_failed_attempt(
+    transaction: &mut PgTransaction,
+    enrollment: &Enrollment,
+    retry_at: OffsetDateTime,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE drip_enrollments
+        SET failed_attempts = failed_attempts + 1, next_send_at = $3
+        WHERE drip_sequence_id = $1 AND subscriber_id = $2
+        "#,
+        enrollment.drip_sequence_id,
+        enrollment.subscriber_id,
+        retry_at,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Moves on to the next step, to be scheduled when it is picked up.
+#[tracing::instrument(skip_all)]
+async fn advance(
+    transaction: &mut PgTransaction,
+    enrollment: &Enrollment,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE drip_enrollments
+        SET
+            next_position = next_position + 1,
+            next_send_at = now(),
+            last_sent_at = now(),
+            failed_attempts = 0
+        WHERE drip_sequence_id = $1 AND subscriber_id = $2
+        "#,
+        enrollment.drip_sequence_id,
+        enrollment.subscriber_id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn complete_enrollment(
+    transaction: &mut PgTransaction,
+    enrollment: &Enrollment,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE drip_enrollments
+        SET completed_at = now(), next_send_at = NULL
+        WHERE drip_sequence_id = $1 AND subscriber_id = $2
+        "#,
+        enrollment.drip_sequence_id,
+        enrollment.subscriber_id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
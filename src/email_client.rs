@@ -1,5 +1,5 @@
 use crate::domain::SubscriberEmail;
-use reqwest::{Client, Error};
+use reqwest::{header::RETRY_AFTER, Client, StatusCode};
 use secrecy::{ExposeSecret, Secret};
 use serde::Serialize;
 use std::time::Duration;
@@ -35,7 +35,46 @@ impl EmailClient {
         subject: &str,
         html_content: &str,
         text_content: &str,
-    ) -> Result<(), Error> {
+    ) -> Result<(), SendEmailError> {
+        self.send(recipient, subject, Some(html_content), text_content)
+            .await
+    }
+
+    /// Sends an email without an HTML part, for recipients preferring plain text.
+    pub async fn send_text_email(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        text_content: &str,
+    ) -> Result<(), SendEmailError> {
+        self.send(recipient, subject, None, text_content).await
+    }
+
+    /// Checks that the provider is reachable and accepts our token, without
+    /// sending anything.
+    pub async fn check_server(&self) -> Result<(), reqwest::Error> {
+        let url = format!("{}/server", &self.base_url);
+
+        self.http_client
+            .get(&url)
+            .header(
+                "X-Postmark-Server-Token",
+                self.authorization_token.expose_secret(),
+            )
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    async fn send(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: Option<&str>,
+        text_content: &str,
+    ) -> Result<(), SendEmailError> {
         let url = format!("{}/email", &self.base_url);
         let request_body = SendEmailRequest {
             from: self.sender.as_ref(),
@@ -45,7 +84,8 @@ impl EmailClient {
             text_body: text_content,
         };
 
-        self.http_client
+        let response = self
+            .http_client
             .post(&url)
             .header(
                 "X-Postmark-Server-Token",
@@ -53,27 +93,51 @@ impl EmailClient {
             )
             .json(&request_body)
             .send()
-            .await?
-            .error_for_status()?;
+            .await?;
+
+        if response.status() == StatusCode::TOO_MANY_REQUESTS {
+            // Only the delay in seconds form of the header is understood.
+            let retry_after = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse().ok())
+                .map(Duration::from_secs);
+            return Err(SendEmailError::RateLimited { retry_after });
+        }
+
+        response.error_for_status()?;
 
         Ok(())
     }
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum SendEmailError {
+    #[error("The email provider is rate limiting requests")]
+    RateLimited { retry_after: Option<Duration> },
+    #[error(transparent)]
+    RequestFailed(#[from] reqwest::Error),
+}
+
 #[derive(Serialize)]
 #[serde(rename_all = "PascalCase")]
 struct SendEmailRequest<'a> {
     from: &'a str,
     to: &'a str,
     subject: &'a str,
-    html_body: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    html_body: Option<&'a str>,
     text_body: &'a str,
 }
 
 #[cfg(test)]
 mod tests {
-    use claims::{assert_err, assert_ok};
-    use helpers::{content, email, email_client, subject, SendEmailBodyMatcher};
+    use super::SendEmailError;
+    use claims::{assert_err, assert_matches, assert_ok};
+    use helpers::{
+        content, email, email_client, subject, SendEmailBodyMatcher, TextEmailBodyMatcher,
+    };
     use std::time::Duration;
     use wiremock::{
         matchers::{any, header, header_exists, method, path},
@@ -105,6 +169,29 @@ mod tests {
         assert_ok!(response);
     }
 
+    #[tokio::test]
+    async fn send_text_email_sends_no_html_body() {
+        // given
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(path("/email"))
+            .and(method("POST"))
+            .and(TextEmailBodyMatcher)
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // when
+        let response = email_client
+            .send_text_email(&email(), &subject(), &content())
+            .await;
+
+        // then
+        assert_ok!(response);
+    }
+
     #[tokio::test]
     async fn send_email_fails_if_the_server_returns_500() {
         // given
@@ -126,6 +213,52 @@ mod tests {
         assert_err!(response);
     }
 
+    #[tokio::test]
+    async fn send_email_reports_retry_after_if_the_server_returns_429() {
+        // given
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "30"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // when
+        let response = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        // then
+        assert_matches!(
+            response,
+            Err(SendEmailError::RateLimited { retry_after: Some(retry_after) })
+                if retry_after == Duration::from_secs(30)
+        );
+    }
+
+    #[tokio::test]
+    async fn check_server_authenticates_without_sending_an_email() {
+        // given
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(header_exists("X-Postmark-Server-Token"))
+            .and(path("/server"))
+            .and(method("GET"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // when
+        let response = email_client.check_server().await;
+
+        // then
+        assert_ok!(response);
+    }
+
     #[tokio::test]
     async fn send_email_times_out_if_the_server_takes_too_long() {
         // given
@@ -177,6 +310,17 @@ mod tests {
             }
         }
 
+        pub struct TextEmailBodyMatcher;
+
+        impl Match for TextEmailBodyMatcher {
+            fn matches(&self, request: &Request) -> bool {
+                match from_slice::<Value>(&request.body) {
+                    Ok(body) => body.get("TextBody").is_some() && body.get("HtmlBody").is_none(),
+                    Err(_) => false,
+                }
+            }
+        }
+
         pub fn email_client(base_url: String) -> EmailClient {
             EmailClient::new(
                 base_url,
@@ -0,0 +1,209 @@
+use crate::{domain::SubscriptionStatus, drip_sequences::stop_enrollments};
+use anyhow::Context;
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use sqlx::{Postgres, Transaction};
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Delivery problems reported by the email provider.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EmailEventType {
+    HardBounce,
+    SoftBounce,
+    SpamComplaint,
+    /// The provider started or stopped suppressing an address on its side.
+    SubscriptionChange,
+}
+
+impl AsRef<str> for EmailEventType {
+    fn as_ref(&self) -> &'static str {
+        match self {
+            EmailEventType::HardBounce => "hard_bounce",
+            EmailEventType::SoftBounce => "soft_bounce",
+            EmailEventType::SpamComplaint => "spam_complaint",
+            EmailEventType::SubscriptionChange => "subscription_change",
+        }
+    }
+}
+
+pub struct EmailEvent {
+    /// Lets redelivered webhooks be recorded only once.
+    pub provider_event_id: Option<String>,
+    pub provider_message_id: Option<String>,
+    pub event_type: EmailEventType,
+    pub email: String,
+    pub canonical_email: Option<String>,
+    pub details: Option<String>,
+    pub occurred_at: OffsetDateTime,
+}
+
+/// Credentials the email provider authenticates its webhooks with, either
+/// through basic auth or as a shared secret in a header.
+#[derive(Clone)]
+pub struct WebhookCredentials {
+    username: String,
+    secret: Secret<String>,
+}
+
+impl WebhookCredentials {
+    pub fn new(username: String, secret: Secret<String>) -> Self {
+        Self { username, secret }
+    }
+
+    /// Compares the secrets in constant time. The username is not secret.
+    pub fn verify(&self, username: Option<&str>, secret: &str) -> bool {
+        if username.is_some_and(|username| username != self.username) {
+            return false;
+        }
+
+        let expected = self
+            .mac(self.secret.expose_secret())
+            .finalize()
+            .into_bytes();
+        self.mac(secret).verify_slice(&expected).is_ok()
+    }
+
+    fn mac(&self, secret: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(self.username.as_bytes())
+            .expect("HMAC can take key of any size");
+        mac.update(secret.as_bytes());
+        mac
+    }
+}
+
+/// How many events within the window suppress an address.
+#[derive(Clone, Copy, Debug)]
+pub struct SuppressionThresholds {
+    pub hard_bounces: i64,
+    pub soft_bounces: i64,
+    pub spam_complaints: i64,
+    pub window: Duration,
+}
+
+/// Records an event, returning the id of the subscriber it concerns. Returns
+/// `None` for unknown addresses and for events that have been recorded before.
+#[tracing::instrument(skip_all, fields(event_type = event.event_type.as_ref()))]
+pub(crate) async fn record_event(
+    transaction: &mut Transaction<'_, Postgres>,
+    event: &EmailEvent,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let subscriber_id = sqlx::query!(
+        r#"
+        SELECT id
+        FROM subscriptions
+        WHERE email = $1 OR canonical_email = $2
+        ORDER BY email = $1 DESC
+        LIMIT 1
+        FOR UPDATE
+        "#,
+        event.email,
+        event.canonical_email,
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .context("Failed to fetch the subscriber of an email event")?
+    .map(|row| row.id);
+
+    let inserted = sqlx::query!(
+        r#"
+        INSERT INTO email_events (
+            email_event_id,
+            provider_event_id,
+            provider_message_id,
+            subscriber_id,
+            email,
+            event_type,
+            details,
+            occurred_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        ON CONFLICT DO NOTHING
+        "#,
+        Uuid::new_v4(),
+        event.provider_event_id,
+        event.provider_message_id,
+        subscriber_id,
+        event.email,
+        event.event_type.as_ref(),
+        event.details,
+        event.occurred_at,
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to record email event")?
+    .rows_affected()
+        > 0;
+
+    Ok(subscriber_id.filter(|_| inserted))
+}
+
+/// Suppresses a subscriber once their recent bounces or complaints reach the
+/// thresholds. Complaints take precedence, as they hurt our reputation most.
+#[tracing::instrument(skip(transaction, thresholds))]
+pub(crate) async fn apply_thresholds(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    thresholds: &SuppressionThresholds,
+) -> Result<(), anyhow::Error> {
+    let counts = sqlx::query!(
+        r#"
+        SELECT
+            COUNT(*) FILTER (WHERE event_type = 'hard_bounce') AS "hard_bounces!",
+            COUNT(*) FILTER (WHERE event_type = 'soft_bounce') AS "soft_bounces!",
+            COUNT(*) FILTER (WHERE event_type = 'spam_complaint') AS "spam_complaints!"
+        FROM email_events
+        WHERE subscriber_id = $1 AND occurred_at > $2
+        "#,
+        subscriber_id,
+        OffsetDateTime::now_utc() - thresholds.window,
+    )
+    .fetch_one(&mut **transaction)
+    .await
+    .context("Failed to count email events")?;
+
+    if counts.spam_complaints >= thresholds.spam_complaints {
+        suppress(transaction, subscriber_id, SubscriptionStatus::Complained).await
+    } else if counts.hard_bounces >= thresholds.hard_bounces
+        || counts.soft_bounces >= thresholds.soft_bounces
+    {
+        suppress(transaction, subscriber_id, SubscriptionStatus::Bounced).await
+    } else {
+        Ok(())
+    }
+}
+
+/// Stops all sending to a subscriber who has not unsubscribed already.
+#[tracing::instrument(skip(transaction, status), fields(status = status.as_ref()))]
+pub(crate) async fn suppress(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    status: SubscriptionStatus,
+) -> Result<(), anyhow::Error> {
+    let suppressed = sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET status = $1
+        WHERE id = $2 AND status IN ($3, $4)
+        "#,
+        status.as_ref(),
+        subscriber_id,
+        SubscriptionStatus::PendingConfirmation.as_ref(),
+        SubscriptionStatus::Confirmed.as_ref(),
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to suppress subscriber")?
+    .rows_affected()
+        > 0;
+
+    if suppressed {
+        tracing::info!("Subscriber has been suppressed");
+        stop_enrollments(&mut **transaction, subscriber_id).await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,286 @@
+use crate::domain::SubscriberEmail;
+use anyhow::Context;
+use axum::async_trait;
+use hickory_resolver::{error::ResolveErrorKind, TokioAsyncResolver};
+use std::{collections::HashSet, path::Path, sync::Arc};
+
+/// Looks up whether a domain can receive email.
+#[async_trait]
+pub trait MailHostResolver: Send + Sync {
+    /// A domain accepts email if it has MX records, other than a null MX,
+    /// or, lacking those, A or AAAA records (RFC 5321, section 5.1).
+    async fn accepts_mail(&self, domain: &str) -> Result<bool, anyhow::Error>;
+}
+
+pub struct DnsResolver(TokioAsyncResolver);
+
+impl DnsResolver {
+    pub fn from_system_conf() -> Result<Self, anyhow::Error> {
+        TokioAsyncResolver::tokio_from_system_conf()
+            .map(Self)
+            .context("Failed to create DNS resolver from the system configuration")
+    }
+}
+
+#[async_trait]
+impl MailHostResolver for DnsResolver {
+    async fn accepts_mail(&self, domain: &str) -> Result<bool, anyhow::Error> {
+        // Fully qualified, so that search domains are not appended.
+        let fqdn = format!("{}.", domain.trim_end_matches('.'));
+
+        match self.0.mx_lookup(fqdn.as_str()).await {
+            Ok(records) => return Ok(records.iter().any(|mx| !mx.exchange().is_root())),
+            Err(e) if matches!(e.kind(), ResolveErrorKind::NoRecordsFound { .. }) => {}
+            Err(e) => return Err(e).context("Failed to look up MX records"),
+        }
+
+        match self.0.lookup_ip(fqdn.as_str()).await {
+            Ok(addresses) => Ok(addresses.iter().next().is_some()),
+            Err(e) if matches!(e.kind(), ResolveErrorKind::NoRecordsFound { .. }) => Ok(false),
+            Err(e) => Err(e).context("Failed to look up A and AAAA records"),
+        }
+    }
+}
+
+/// Resolver answering from a fixed set of domains, for tests and local runs.
+#[derive(Debug, Default)]
+pub struct InMemoryResolver {
+    mail_domains: HashSet<String>,
+}
+
+impl InMemoryResolver {
+    pub fn new<I, S>(mail_domains: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            mail_domains: mail_domains.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+#[async_trait]
+impl MailHostResolver for InMemoryResolver {
+    async fn accepts_mail(&self, domain: &str) -> Result<bool, anyhow::Error> {
+        Ok(self.mail_domains.contains(domain))
+    }
+}
+
+/// Domains of disposable email providers. Subdomains of listed domains are
+/// considered disposable too.
+#[derive(Debug)]
+pub struct DisposableDomains {
+    domains: HashSet<String>,
+}
+
+impl DisposableDomains {
+    /// Loads domains from a file with one domain per line. Empty lines and
+    /// lines starting with `#` are skipped.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, anyhow::Error> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read disposable domains from {path:?}"))?;
+
+        Self::parse(&contents)
+    }
+
+    fn parse(contents: &str) -> Result<Self, anyhow::Error> {
+        let mut domains = HashSet::new();
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let domain = idna::domain_to_ascii_strict(line).map_err(|_| {
+                anyhow::anyhow!(
+                    "Invalid domain in line {} of disposable domains file",
+                    number + 1
+                )
+            })?;
+            domains.insert(domain);
+        }
+
+        Ok(Self { domains })
+    }
+
+    fn contains(&self, domain: &str) -> bool {
+        let mut domain = domain;
+        loop {
+            if self.domains.contains(domain) {
+                return true;
+            }
+            match domain.split_once('.') {
+                Some((_, parent)) => domain = parent,
+                None => return false,
+            }
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum EmailVerificationError {
+    #[error("{0}")]
+    InvalidEmail(String),
+    #[error("`{0}` is a disposable email domain, please use a permanent address")]
+    DisposableDomain(String),
+    #[error("`{0}` does not accept email")]
+    UndeliverableDomain(String),
+}
+
+/// Checks beyond the syntax of an address, run when someone subscribes.
+#[derive(Clone, Default)]
+pub struct EmailVerifier {
+    disposable_domains: Option<Arc<DisposableDomains>>,
+    resolver: Option<Arc<dyn MailHostResolver>>,
+}
+
+impl EmailVerifier {
+    pub fn new(
+        disposable_domains: Option<DisposableDomains>,
+        resolver: Option<Arc<dyn MailHostResolver>>,
+    ) -> Self {
+        Self {
+            disposable_domains: disposable_domains.map(Arc::new),
+            resolver,
+        }
+    }
+
+    /// Returns the normalised address if it passes the enabled checks.
+    /// DNS failures other than missing records let the address through,
+    /// so that a resolver outage does not stop people from subscribing.
+    #[tracing::instrument(skip(self))]
+    pub async fn verify(
+        &self,
+        email: &SubscriberEmail,
+    ) -> Result<SubscriberEmail, EmailVerificationError> {
+        let email = email
+            .normalise()
+            .map_err(EmailVerificationError::InvalidEmail)?;
+        let domain = email.domain();
+
+        if let Some(disposable_domains) = &self.disposable_domains {
+            if disposable_domains.contains(domain) {
+                return Err(EmailVerificationError::DisposableDomain(domain.to_string()));
+            }
+        }
+
+        if let Some(resolver) = &self.resolver {
+            match resolver.accepts_mail(domain).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    return Err(EmailVerificationError::UndeliverableDomain(
+                        domain.to_string(),
+                    ))
+                }
+                Err(e) => tracing::warn!("Failed to check mail hosts of `{domain}`: {e:?}"),
+            }
+        }
+
+        Ok(email)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        DisposableDomains, EmailVerificationError, EmailVerifier, InMemoryResolver,
+        MailHostResolver,
+    };
+    use crate::domain::SubscriberEmail;
+    use axum::async_trait;
+    use claims::{assert_err, assert_matches, assert_ok};
+    use std::sync::Arc;
+
+    struct FailingResolver;
+
+    #[async_trait]
+    impl MailHostResolver for FailingResolver {
+        async fn accepts_mail(&self, _domain: &str) -> Result<bool, anyhow::Error> {
+            anyhow::bail!("DNS is down")
+        }
+    }
+
+    fn email(s: &str) -> SubscriberEmail {
+        SubscriberEmail::parse(s.to_string()).unwrap()
+    }
+
+    fn disposable_domains() -> DisposableDomains {
+        DisposableDomains::parse("# Disposable providers\n\nmailinator.com\nŻółw.example\n")
+            .unwrap()
+    }
+
+    #[test]
+    fn disposable_domains_match_subdomains_and_internationalised_names() {
+        // given
+        let domains = disposable_domains();
+
+        // then
+        assert!(domains.contains("mailinator.com"));
+        assert!(domains.contains("eu.mailinator.com"));
+        assert!(domains.contains("xn--w-uga1v8h.example"));
+        assert!(!domains.contains("notmailinator.com"));
+        assert!(!domains.contains("com"));
+    }
+
+    #[test]
+    fn invalid_disposable_domains_file_is_rejected() {
+        // when
+        let result = DisposableDomains::parse("mailinator.com\nnot a domain\n");
+
+        // then
+        assert_err!(result);
+    }
+
+    #[tokio::test]
+    async fn address_at_a_disposable_domain_is_rejected() {
+        // given
+        let verifier = EmailVerifier::new(Some(disposable_domains()), None);
+
+        // when
+        let result = verifier.verify(&email("imie@Mailinator.COM")).await;
+
+        // then
+        assert_matches!(result, Err(EmailVerificationError::DisposableDomain(_)));
+    }
+
+    #[tokio::test]
+    async fn address_at_a_domain_without_mail_hosts_is_rejected() {
+        // given
+        let resolver = InMemoryResolver::new(["example.com"]);
+        let verifier = EmailVerifier::new(None, Some(Arc::new(resolver)));
+
+        // when
+        let result = verifier.verify(&email("imie@example.org")).await;
+
+        // then
+        assert_matches!(result, Err(EmailVerificationError::UndeliverableDomain(_)));
+    }
+
+    #[tokio::test]
+    async fn mail_hosts_are_looked_up_by_the_normalised_domain() {
+        // given
+        let resolver = InMemoryResolver::new(["xn--w-uga1v8h.pl"]);
+        let verifier = EmailVerifier::new(Some(disposable_domains()), Some(Arc::new(resolver)));
+
+        // when
+        let result = verifier.verify(&email("imie@ŻÓŁW.pl")).await;
+
+        // then
+        assert_eq!(assert_ok!(result).as_ref(), "imie@xn--w-uga1v8h.pl");
+    }
+
+    #[tokio::test]
+    async fn resolver_failures_let_the_address_through() {
+        // given
+        let verifier = EmailVerifier::new(None, Some(Arc::new(FailingResolver)));
+
+        // when
+        let result = verifier.verify(&email("imie@example.com")).await;
+
+        // then
+        assert_ok!(result);
+    }
+}
@@ -1,28 +1,192 @@
 use crate::{
-    configuration::Settings, domain::SubscriberEmail, email_client::EmailClient,
+    configuration::Settings,
+    delivery_rate_limit::DeliveryRateLimit,
+    domain::{
+        ContentFormat, DigestFrequency, PreferencesLinks, SubscriberEmail, SubscriptionStatus,
+        TrackingLinks,
+    },
+    drip_sequences::try_execute_drip_step,
+    email_client::{EmailClient, SendEmailError},
+    queue_listener::{listen_for_tasks, QueueWakeup},
+    routes::subscriptions_email::delete_expired_email_changes,
     startup::get_pg_connection_pool,
+    subscribe_protection::{delete_expired_attempts, delete_expired_form_tokens},
+    suppression_list::check_suppression,
 };
-use sqlx::{Executor, PgPool, Postgres, Row, Transaction};
-use std::time::Duration;
+use anyhow::{anyhow, Context};
+use askama::Template;
+use axum::http::Uri;
+use sqlx::{Executor, FromRow, PgPool, Postgres, Row, Transaction};
+use std::{str::FromStr, sync::Arc, time::Duration};
+use time::OffsetDateTime;
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
 use tracing::Span;
 use uuid::Uuid;
 
-pub async fn run_worker_until_stopped(config: Settings) -> Result<(), anyhow::Error> {
-    let connection_pool = get_pg_connection_pool(&config.database);
-    let email_client = config.email_client.client();
-    worker_loop(&connection_pool, &email_client).await
+/// How often expired records, like those kept around for rate limiting, are
+/// deleted.
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Runs the configured number of delivery tasks, and one deleting expired
+/// records, until `shutdown` is cancelled. In-flight deliveries then get the
+/// shutdown timeout to finish. Those that do not are rolled back and stay in
+/// the queue.
+pub async fn run_worker_until_stopped(
+    config: Settings,
+    shutdown: CancellationToken,
+) -> Result<(), anyhow::Error> {
+    let base_url =
+        Uri::from_str(&config.application.base_url).context("Failed to parse base url")?;
+    let context = Arc::new(WorkerContext {
+        db_pool: get_pg_connection_pool(&config.database),
+        email_client: config.email_client.client(),
+        rate_limit: config.delivery_rate_limit.rate_limit(),
+        preferences_links: PreferencesLinks::new(
+            base_url.clone(),
+            config.application.hmac_secret.clone(),
+        ),
+        tracking_links: TrackingLinks::new(
+            base_url,
+            config.application.hmac_secret,
+            config.tracking.enabled,
+        ),
+    });
+
+    // Stops the other workers if one of them crashes.
+    let stop = shutdown.child_token();
+    let (wakeup, listener) =
+        listen_for_tasks(context.db_pool.clone(), &config.worker, stop.clone());
+    let mut workers = JoinSet::new();
+    for _ in 0..config.worker.concurrency.max(1) {
+        workers.spawn(worker_loop(context.clone(), wakeup.clone(), stop.clone()));
+    }
+    workers.spawn(cleanup_loop(context.db_pool.clone(), stop.clone()));
+
+    let crashed = tokio::select! {
+        _ = stop.cancelled() => None,
+        Some(outcome) = workers.join_next() => Some(outcome),
+    };
+    stop.cancel();
+
+    let drained = tokio::time::timeout(config.application.shutdown_timeout(), async {
+        while let Some(outcome) = workers.join_next().await {
+            if let Err(e) = outcome {
+                tracing::error!(error.message = %e, "Delivery worker failed");
+            }
+        }
+    })
+    .await;
+    if drained.is_err() {
+        tracing::warn!("In-flight deliveries did not finish in time. Rolling them back.");
+        workers.shutdown().await;
+    }
+    listener.await.context("Queue listener failed")?;
+
+    match crashed {
+        Some(outcome) => Err(anyhow!(
+            "A delivery worker stopped unexpectedly: {outcome:?}"
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Everything the delivery tasks need, shared by all of them.
+struct WorkerContext {
+    db_pool: PgPool,
+    email_client: EmailClient,
+    rate_limit: DeliveryRateLimit,
+    preferences_links: PreferencesLinks,
+    tracking_links: TrackingLinks,
 }
 
-async fn worker_loop(db_pool: &PgPool, email_client: &EmailClient) -> Result<(), anyhow::Error> {
+/// Executes tasks until `stop` is cancelled. A task being executed is always
+/// finished before checking for it.
+async fn worker_loop(
+    context: Arc<WorkerContext>,
+    mut wakeup: QueueWakeup,
+    stop: CancellationToken,
+) {
+    while !stop.is_cancelled() {
+        let issue = try_execute_task(
+            &context.db_pool,
+            &context.email_client,
+            &context.rate_limit,
+            &context.preferences_links,
+            &context.tracking_links,
+        )
+        .await;
+        let drip_step = try_execute_drip_step(
+            &context.db_pool,
+            &context.email_client,
+            &context.rate_limit,
+            &context.preferences_links,
+        )
+        .await;
+
+        let pause = match (issue, drip_step) {
+            (Err(_), _) | (_, Err(_)) => Duration::from_secs(1),
+            (Ok(ExecutionOutcome::RateLimited(wait)), _)
+            | (_, Ok(ExecutionOutcome::RateLimited(wait))) => {
+                tracing::debug!(
+                    wait_seconds = wait.as_secs_f64(),
+                    "Delivery budget exhausted. Waiting."
+                );
+                wait
+            }
+            (Ok(ExecutionOutcome::EmptyQueue), Ok(ExecutionOutcome::EmptyQueue)) => {
+                tokio::select! {
+                    _ = stop.cancelled() => {}
+                    _ = wakeup.wait_for_tasks() => {}
+                }
+                continue;
+            }
+            _ => continue,
+        };
+
+        tokio::select! {
+            _ = stop.cancelled() => {}
+            _ = tokio::time::sleep(pause) => {}
+        }
+    }
+}
+
+/// Deletes expired records until `stop` is cancelled. Failures are retried
+/// on the next run.
+async fn cleanup_loop(db_pool: PgPool, stop: CancellationToken) {
+    let mut interval = tokio::time::interval(CLEANUP_INTERVAL);
+
     loop {
-        match try_execute_task(db_pool, email_client).await {
-            Ok(ExecutionOutcome::TaskCompleted) => {}
-            Ok(ExecutionOutcome::EmptyQueue) => tokio::time::sleep(Duration::from_secs(10)).await,
-            Err(_) => tokio::time::sleep(Duration::from_secs(1)).await,
+        tokio::select! {
+            _ = stop.cancelled() => return,
+            _ = interval.tick() => {}
+        }
+
+        if let Err(e) = delete_expired_records(&db_pool).await {
+            tracing::error!(
+                error_cause_chain = ?e,
+                error.message = %e,
+                "Failed to delete expired records"
+            );
         }
     }
 }
 
+#[tracing::instrument(skip_all)]
+async fn delete_expired_records(db_pool: &PgPool) -> Result<(), anyhow::Error> {
+    let attempts = delete_expired_attempts(db_pool).await?;
+    let form_tokens = delete_expired_form_tokens(db_pool).await?;
+    let email_changes = delete_expired_email_changes(db_pool).await?;
+    tracing::info!(
+        attempts,
+        form_tokens,
+        email_changes,
+        "Deleted expired records"
+    );
+
+    Ok(())
+}
+
 #[tracing::instrument(
     skip_all,
     fields(
@@ -33,42 +197,126 @@ async fn worker_loop(db_pool: &PgPool, email_client: &EmailClient) -> Result<(),
 pub async fn try_execute_task(
     db_pool: &PgPool,
     email_client: &EmailClient,
+    rate_limit: &DeliveryRateLimit,
+    preferences_links: &PreferencesLinks,
+    tracking_links: &TrackingLinks,
 ) -> Result<ExecutionOutcome, anyhow::Error> {
-    if let Some((transaction, issue_id, email)) = dequeue_task(db_pool).await? {
+    if let Some((mut transaction, issue_id, canonical_email)) = dequeue_task(db_pool).await? {
         Span::current()
             .record("newsletter_issue_id", issue_id.to_string())
-            .record("subscriber_email", email.clone());
+            .record("subscriber_email", canonical_email.clone());
+
+        let subscriber = get_subscriber(&mut transaction, &canonical_email).await?;
 
-        match SubscriberEmail::parse(email.clone()) {
-            Ok(email) => {
-                let issue = get_issue(db_pool, issue_id).await?;
-                if let Err(e) = email_client
-                    .send_email(
+        // Subscribers receiving digests get all their queued issues at once.
+        let issue_ids = match &subscriber {
+            Some(subscriber) if subscriber.digest_frequency != DigestFrequency::Immediate => {
+                dequeue_digest(&mut transaction, &canonical_email).await?
+            }
+            _ => vec![issue_id],
+        };
+
+        let suppression = match &subscriber {
+            Some(subscriber) => {
+                check_suppression(&mut *transaction, &subscriber.email, &canonical_email).await?
+            }
+            None => None,
+        };
+
+        let outcome = match &subscriber {
+            Some(_) if suppression.is_some() => {
+                tracing::info!(
+                    reason = suppression.as_deref(),
+                    "Subscriber is on the suppression list. Skipping."
+                );
+                DeliveryOutcome::Skipped
+            }
+            Some(subscriber)
+                if subscriber
+                    .paused_until
+                    .is_some_and(|until| until > OffsetDateTime::now_utc()) =>
+            {
+                tracing::info!("Subscriber has paused delivery. Skipping.");
+                DeliveryOutcome::Skipped
+            }
+            Some(subscriber) => match SubscriberEmail::parse(subscriber.email.clone()) {
+                Ok(email) => {
+                    // Returning leaves the task in the queue, to be retried
+                    // once there is budget for it.
+                    if let Some(wait) = rate_limit.acquire(db_pool).await? {
+                        return Ok(ExecutionOutcome::RateLimited(wait));
+                    }
+                    let issues = get_issues(db_pool, &issue_ids)
+                        .await?
+                        .into_iter()
+                        .map(|issue| issue.into_tracked(tracking_links, subscriber.id))
+                        .collect::<Vec<_>>();
+                    let subject = match issues.as_slice() {
+                        [issue] => issue.title.clone(),
+                        _ => format!(
+                            "Your {} digest: {} new issues",
+                            subscriber.digest_frequency.as_ref(),
+                            issues.len()
+                        ),
+                    };
+                    match send_issues(
+                        email_client,
                         &email,
-                        &issue.title,
-                        &issue.html_content,
-                        &issue.text_content,
+                        subscriber.content_format,
+                        &subject,
+                        &issues,
+                        &preferences_links.link(subscriber.id),
                     )
                     .await
-                {
+                    {
+                        Ok(()) => DeliveryOutcome::Sent,
+                        Err(e) => {
+                            if let Some(SendEmailError::RateLimited { retry_after }) =
+                                e.downcast_ref()
+                            {
+                                let pause = rate_limit.pause(db_pool, *retry_after).await?;
+                                return Ok(ExecutionOutcome::RateLimited(pause));
+                            }
+                            tracing::error!(
+                                error_cause_chain = ?e,
+                                error.message = %e,
+                                "Failed to deliver issue to a confirmed subscriber. Skipping."
+                            );
+                            DeliveryOutcome::Failed
+                        }
+                    }
+                }
+                Err(e) => {
                     tracing::error!(
                         error_cause_chain = ?e,
                         error.message = %e,
-                        "Failed to deliver issue to a confirmed subscriber. Skipping."
+                        "Failed to deliver issue to a confirmed subscriber. \
+                        Their email is invalid."
                     );
+                    DeliveryOutcome::Skipped
                 }
+            },
+            None => {
+                tracing::info!("Subscriber is no longer confirmed. Skipping.");
+                DeliveryOutcome::Skipped
             }
-            Err(e) => {
-                tracing::error!(
-                    error_cause_chain = ?e,
-                    error.message = %e,
-                    "Failed to deliver issue to a confirmed subscriber. \
-                    Their email is invalid."
-                );
+        };
+
+        let delivered_to = subscriber
+            .as_ref()
+            .map_or(canonical_email.as_str(), |subscriber| &subscriber.email);
+        for &issue_id in &issue_ids {
+            record_delivery(&mut transaction, issue_id, delivered_to, outcome).await?;
+            delete_task(&mut transaction, issue_id, &canonical_email).await?;
+        }
+
+        if let Some(subscriber) = &subscriber {
+            if subscriber.digest_frequency != DigestFrequency::Immediate {
+                record_digest_sent(&mut transaction, subscriber.id).await?;
             }
         }
 
-        delete_task(transaction, issue_id, &email).await?;
+        transaction.commit().await?;
 
         Ok(ExecutionOutcome::TaskCompleted)
     } else {
@@ -78,6 +326,7 @@ pub async fn try_execute_task(
 
 type PgTransaction = Transaction<'static, Postgres>;
 
+/// Picks a task, leaving those of subscribers whose next digest is not due yet.
 #[tracing::instrument(skip_all)]
 async fn dequeue_task(
     db_pool: &PgPool,
@@ -85,9 +334,19 @@ async fn dequeue_task(
     let mut transaction = db_pool.begin().await?;
     let query = sqlx::query!(
         r#"
-        SELECT newsletter_issue_id, subscriber_email
-        FROM issue_delivery_queue
-        FOR UPDATE
+        SELECT q.newsletter_issue_id, q.canonical_email
+        FROM issue_delivery_queue q
+        LEFT JOIN subscriptions s ON s.canonical_email = q.canonical_email
+        WHERE
+            s.id IS NULL OR
+            s.status <> 'confirmed' OR
+            s.digest_frequency = 'immediate' OR
+            s.last_digest_sent_at IS NULL OR
+            s.last_digest_sent_at <= now() - CASE s.digest_frequency
+                WHEN 'daily' THEN interval '1 day'
+                ELSE interval '7 days'
+            END
+        FOR UPDATE OF q
         SKIP LOCKED
         LIMIT 1
         "#,
@@ -97,59 +356,279 @@ async fn dequeue_task(
         Some(row) => Ok(Some((
             transaction,
             row.try_get("newsletter_issue_id")?,
-            row.try_get("subscriber_email")?,
+            row.try_get("canonical_email")?,
         ))),
         None => Ok(None),
     }
 }
 
+/// Locks all tasks of a subscriber, including the one already dequeued.
 #[tracing::instrument(skip_all)]
-async fn delete_task(
-    mut transaction: PgTransaction,
+async fn dequeue_digest(
+    transaction: &mut PgTransaction,
+    canonical_email: &str,
+) -> Result<Vec<Uuid>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id
+        FROM issue_delivery_queue
+        WHERE canonical_email = $1
+        FOR UPDATE
+        SKIP LOCKED
+        "#,
+        canonical_email,
+    )
+    .fetch_all(&mut **transaction)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| row.newsletter_issue_id)
+        .collect())
+}
+
+/// A confirmed subscriber, queued by the canonical form of their email.
+#[tracing::instrument(skip_all)]
+async fn get_subscriber(
+    transaction: &mut PgTransaction,
+    canonical_email: &str,
+) -> Result<Option<Subscriber>, anyhow::Error> {
+    let query = sqlx::query!(
+        r#"
+        SELECT id, email, content_format, digest_frequency, paused_until
+        FROM subscriptions
+        WHERE canonical_email = $1 AND status = $2
+        "#,
+        canonical_email,
+        SubscriptionStatus::Confirmed.as_ref(),
+    );
+
+    match transaction.fetch_optional(query).await? {
+        Some(row) => Ok(Some(
+            Subscriber::from_row(&row).context("Failed to instantiate subscriber")?,
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Sends issues in a single email in the format chosen by the subscriber,
+/// followed by a link to their preferences.
+#[tracing::instrument(skip_all)]
+pub(crate) async fn send_issues(
+    email_client: &EmailClient,
+    email: &SubscriberEmail,
+    content_format: ContentFormat,
+    subject: &str,
+    issues: &[NewsletterIssue],
+    preferences_link: &str,
+) -> Result<(), anyhow::Error> {
+    let text_body = IssueTextTemplate {
+        issues,
+        preferences_link,
+    }
+    .render()
+    .context("Failed to render plain text template")?;
+
+    let result = match content_format {
+        ContentFormat::Html => {
+            let html_body = IssueHtmlTemplate {
+                issues,
+                preferences_link,
+            }
+            .render()
+            .context("Failed to render html template")?;
+
+            email_client
+                .send_email(email, subject, &html_body, &text_body)
+                .await
+        }
+        ContentFormat::PlainText => {
+            email_client
+                .send_text_email(email, subject, &text_body)
+                .await
+        }
+    };
+
+    result.context("Failed to send email")
+}
+
+#[tracing::instrument(skip_all)]
+async fn record_delivery(
+    transaction: &mut PgTransaction,
     issue_id: Uuid,
     email: &str,
+    outcome: DeliveryOutcome,
+) -> Result<(), anyhow::Error> {
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO issue_deliveries (
+            newsletter_issue_id,
+            subscriber_email,
+            outcome,
+            delivered_at
+        )
+        VALUES ($1, $2, $3, now())
+        ON CONFLICT (newsletter_issue_id, subscriber_email) DO UPDATE
+        SET
+            outcome = EXCLUDED.outcome,
+            delivered_at = EXCLUDED.delivered_at
+        "#,
+        issue_id,
+        email,
+        outcome.as_ref(),
+    );
+
+    transaction.execute(query).await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn record_digest_sent(
+    transaction: &mut PgTransaction,
+    subscriber_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    let query = sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET last_digest_sent_at = now()
+        WHERE id = $1
+        "#,
+        subscriber_id,
+    );
+
+    transaction.execute(query).await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn delete_task(
+    transaction: &mut PgTransaction,
+    issue_id: Uuid,
+    canonical_email: &str,
 ) -> Result<(), anyhow::Error> {
     let query = sqlx::query!(
         r#"
         DELETE FROM issue_delivery_queue
         WHERE
             newsletter_issue_id = $1 AND
-            subscriber_email = $2
+            canonical_email = $2
         "#,
         issue_id,
-        email
+        canonical_email
     );
 
     transaction.execute(query).await?;
-    transaction.commit().await?;
 
     Ok(())
 }
 
 #[tracing::instrument(skip_all)]
-async fn get_issue(db_pool: &PgPool, issue_id: Uuid) -> Result<NewsletterIssue, anyhow::Error> {
-    let issue = sqlx::query_as!(
-        NewsletterIssue,
+async fn get_issues(
+    db_pool: &PgPool,
+    issue_ids: &[Uuid],
+) -> Result<Vec<StoredIssue>, anyhow::Error> {
+    let issues = sqlx::query_as!(
+        StoredIssue,
         r#"
-        SELECT title, text_content, html_content
+        SELECT
+            newsletter_issue_id,
+            title,
+            text_content,
+            html_content,
+            track_opens,
+            track_clicks
         FROM newsletter_issues
-        WHERE newsletter_issue_id = $1
+        WHERE newsletter_issue_id = ANY($1)
+        ORDER BY published_at
         "#,
-        issue_id,
+        issue_ids,
     )
-    .fetch_one(db_pool)
+    .fetch_all(db_pool)
     .await?;
 
-    Ok(issue)
+    Ok(issues)
 }
 
 pub enum ExecutionOutcome {
     TaskCompleted,
     EmptyQueue,
+    /// Nothing has been sent, as the delivery budget is exhausted for the
+    /// given time.
+    RateLimited(Duration),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum DeliveryOutcome {
+    Sent,
+    Failed,
+    Skipped,
 }
 
-struct NewsletterIssue {
+impl AsRef<str> for DeliveryOutcome {
+    fn as_ref(&self) -> &'static str {
+        match self {
+            DeliveryOutcome::Sent => "sent",
+            DeliveryOutcome::Failed => "failed",
+            DeliveryOutcome::Skipped => "skipped",
+        }
+    }
+}
+
+#[derive(FromRow)]
+struct Subscriber {
+    id: Uuid,
+    email: String,
+    #[sqlx(try_from = "String")]
+    content_format: ContentFormat,
+    #[sqlx(try_from = "String")]
+    digest_frequency: DigestFrequency,
+    paused_until: Option<OffsetDateTime>,
+}
+
+struct StoredIssue {
+    newsletter_issue_id: Uuid,
     title: String,
     text_content: String,
     html_content: String,
+    track_opens: bool,
+    track_clicks: bool,
+}
+
+impl StoredIssue {
+    /// Adds the tracking chosen for the issue to its HTML content.
+    fn into_tracked(self, tracking_links: &TrackingLinks, subscriber_id: Uuid) -> NewsletterIssue {
+        NewsletterIssue {
+            html_content: tracking_links.instrument(
+                &self.html_content,
+                self.newsletter_issue_id,
+                subscriber_id,
+                self.track_opens,
+                self.track_clicks,
+            ),
+            title: self.title,
+            text_content: self.text_content,
+        }
+    }
+}
+
+pub(crate) struct NewsletterIssue {
+    pub(crate) title: String,
+    pub(crate) text_content: String,
+    pub(crate) html_content: String,
+}
+
+#[derive(Template)]
+#[template(path = "email/issue.html")]
+struct IssueHtmlTemplate<'a> {
+    issues: &'a [NewsletterIssue],
+    preferences_link: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/issue.txt")]
+struct IssueTextTemplate<'a> {
+    issues: &'a [NewsletterIssue],
+    preferences_link: &'a str,
 }
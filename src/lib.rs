@@ -1,13 +1,28 @@
 pub mod app_state;
+pub mod audit;
 pub mod authentication;
+pub mod client_info;
 pub mod configuration;
+pub mod data_migrations;
+pub mod delivery_queue;
+pub mod delivery_rate_limit;
 pub mod domain;
+pub mod drip_sequences;
 pub mod email_client;
+pub mod email_events;
+pub mod email_verification;
 pub mod idempotency;
 pub mod issue_delivery_worker;
+pub mod migrations;
+pub mod queue_listener;
 pub mod request_id;
 pub mod routes;
+pub mod security_headers;
 pub mod session_state;
+pub mod shutdown;
 pub mod startup;
+pub mod subscribe_protection;
+pub mod subscriber_import;
+pub mod suppression_list;
 pub mod telemetry;
 pub mod utils;
@@ -1,37 +1,222 @@
-use std::fmt::{Debug, Display};
-use tokio::task::JoinError;
+use clap::{Parser, Subcommand};
+use std::{
+    fmt::{Debug, Display},
+    future::Future,
+};
+use tokio::task::{JoinError, JoinHandle};
+use tokio_util::sync::CancellationToken;
 use zero2prod::{
     configuration::get_configuration,
     issue_delivery_worker::run_worker_until_stopped,
-    startup::Application,
+    migrations::{check_schema_version, run_migrations},
+    shutdown::cancel_on_signal,
+    startup::{get_pg_connection_pool, Application},
     telemetry::{get_subscriber, init_subscriber},
 };
 
+#[derive(Parser)]
+#[command(version, about = "Newsletter delivery service")]
+struct Cli {
+    /// What to run. Defaults to `all`.
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+impl Cli {
+    fn command(&self) -> Command {
+        self.command.unwrap_or(Command::All)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Subcommand)]
+enum Command {
+    /// Serves the HTTP API and web pages.
+    Serve,
+    /// Delivers newsletter issues and drip sequence emails.
+    Worker,
+    /// Serves HTTP and delivers emails in one process.
+    All,
+    /// Applies pending database migrations and exits.
+    Migrate,
+}
+
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
+    let cli = Cli::parse();
+
     let subscriber = get_subscriber("zero2prod".into(), "info".into(), std::io::stdout);
     init_subscriber(subscriber);
 
     let config = get_configuration().expect("Failed to read configuration");
-    let app = tokio::spawn(Application::build(config.clone()).await.run_until_stopped());
-    let worker = tokio::spawn(run_worker_until_stopped(config));
+    let command = cli.command();
+
+    let db_pool = get_pg_connection_pool(&config.database);
+    if let Command::Migrate = command {
+        run_migrations(&db_pool, &config).await?;
+        tracing::info!("The database has been migrated");
+        return Ok(());
+    }
+
+    if config.database.migrate_on_startup {
+        run_migrations(&db_pool, &config).await?;
+    } else {
+        check_schema_version(&db_pool).await?;
+    }
+    db_pool.close().await;
 
-    tokio::select! {
-        o = app => report_exit("API", o),
-        o = worker => report_exit("Background worker", o),
-    };
+    let shutdown = CancellationToken::new();
+    tokio::spawn(cancel_on_signal(shutdown.clone()));
 
-    Ok(())
+    let mut tasks = Vec::new();
+    if let Command::Serve | Command::All = command {
+        let app = Application::build(config.clone()).await;
+        tasks.push(spawn_until_stopped(
+            "API",
+            app.run_until_stopped(shutdown.clone()),
+            &shutdown,
+        ));
+    }
+    if let Command::Worker | Command::All = command {
+        tasks.push(spawn_until_stopped(
+            "Background worker",
+            run_worker_until_stopped(config, shutdown.clone()),
+            &shutdown,
+        ));
+    }
+
+    // Every task is awaited, so that a failing one does not cut the graceful
+    // shutdown of the others short, and the process exits non-zero after.
+    let mut outcome = Ok(());
+    for task in tasks {
+        outcome = outcome.and(task.await?);
+    }
+
+    outcome
+}
+
+/// Runs a task, shutting the other ones down once it exits, e.g. after failing.
+fn spawn_until_stopped<E>(
+    task_name: &'static str,
+    task: impl Future<Output = Result<(), E>> + Send + 'static,
+    shutdown: &CancellationToken,
+) -> JoinHandle<Result<(), anyhow::Error>>
+where
+    E: Debug + Display + Send + 'static,
+{
+    let task = tokio::spawn(task);
+    let shutdown = shutdown.clone();
+
+    tokio::spawn(async move {
+        let outcome = report_exit(task_name, task.await);
+        shutdown.cancel();
+        outcome
+    })
 }
 
-fn report_exit(task_name: &str, outcome: Result<Result<(), impl Debug + Display>, JoinError>) {
+fn report_exit(
+    task_name: &str,
+    outcome: Result<Result<(), impl Debug + Display>, JoinError>,
+) -> Result<(), anyhow::Error> {
     match outcome {
-        Ok(Ok(())) => tracing::info!("{task_name} has exited"),
+        Ok(Ok(())) => {
+            tracing::info!("{task_name} has exited");
+            Ok(())
+        }
         Ok(Err(e)) => {
-            tracing::error!(eror.cause_chain = ?e, error.message = %e, "{task_name} failed")
+            tracing::error!(eror.cause_chain = ?e, error.message = %e, "{task_name} failed");
+            Err(anyhow::anyhow!("{task_name} failed: {e}"))
         }
         Err(e) => {
-            tracing::error!(eror.cause_chain = ?e, error.message = %e, "{task_name} task failed to complete")
+            tracing::error!(eror.cause_chain = ?e, error.message = %e, "{task_name} task failed to complete");
+            Err(anyhow::anyhow!("{task_name} task failed to complete: {e}"))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{report_exit, Cli, Command};
+    use claims::{assert_err, assert_ok};
+    use clap::Parser;
+    use tokio::task::JoinError;
+
+    #[test]
+    fn command_defaults_to_all() {
+        // given
+        let args = ["zero2prod"];
+
+        // when
+        let cli = Cli::try_parse_from(args).unwrap();
+
+        // then
+        assert_eq!(cli.command(), Command::All);
+    }
+
+    #[test]
+    fn subcommands_are_parsed() {
+        for (subcommand, command) in [
+            ("serve", Command::Serve),
+            ("worker", Command::Worker),
+            ("all", Command::All),
+            ("migrate", Command::Migrate),
+        ] {
+            // given
+            let args = ["zero2prod", subcommand];
+
+            // when
+            let cli = Cli::try_parse_from(args).unwrap();
+
+            // then
+            assert_eq!(cli.command(), command, "{subcommand}");
+        }
+    }
+
+    #[test]
+    fn unknown_subcommand_is_rejected() {
+        // given
+        let args = ["zero2prod", "deliver"];
+
+        // when
+        let cli = Cli::try_parse_from(args);
+
+        // then
+        assert_err!(cli);
+    }
+
+    #[test]
+    fn task_exiting_cleanly_is_not_a_failure() {
+        // given
+        let outcome: Result<Result<(), String>, JoinError> = Ok(Ok(()));
+
+        // when
+        let exit = report_exit("Task", outcome);
+
+        // then
+        assert_ok!(exit);
+    }
+
+    #[test]
+    fn task_returning_an_error_is_a_failure() {
+        // given
+        let outcome: Result<Result<(), String>, JoinError> = Ok(Err("boom".into()));
+
+        // when
+        let exit = report_exit("Task", outcome);
+
+        // then
+        assert_err!(exit);
+    }
+
+    #[tokio::test]
+    async fn panicking_task_is_a_failure() {
+        // given
+        let outcome: Result<Result<(), String>, JoinError> =
+            tokio::spawn(async { panic!("boom") }).await;
+
+        // when
+        let exit = report_exit("Task", outcome);
+
+        // then
+        assert_err!(exit);
+    }
+}
@@ -0,0 +1,113 @@
+use crate::{
+    configuration::Settings,
+    data_migrations::{hash_plaintext_tokens, recanonicalise_emails},
+};
+use anyhow::Context;
+use sqlx::{migrate::Migrator, Connection, PgConnection, PgPool};
+
+/// Migrations embedded in the binary, so that it can migrate the database it
+/// runs against without the sources around.
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Key of the advisory lock held while migrating, so that replicas starting
+/// at the same time do not race each other.
+const MIGRATION_LOCK_KEY: i64 = 0x7a65_726f_3270_726f;
+
+/// Version of the latest migration embedded in the binary.
+pub fn supported_schema_version() -> Option<i64> {
+    MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| migration.version)
+        .max()
+}
+
+/// Version of the latest migration applied to the database, if any.
+#[tracing::instrument(skip_all)]
+pub async fn schema_version(connection: &mut PgConnection) -> Result<Option<i64>, anyhow::Error> {
+    // The table is created by the first migration run.
+    let migrated =
+        sqlx::query_scalar!(r#"SELECT to_regclass('_sqlx_migrations') IS NOT NULL AS "migrated!""#)
+            .fetch_one(&mut *connection)
+            .await
+            .context("Failed to look up the migrations table")?;
+
+    if !migrated {
+        return Ok(None);
+    }
+
+    sqlx::query_scalar!("SELECT MAX(version) FROM _sqlx_migrations WHERE success")
+        .fetch_one(&mut *connection)
+        .await
+        .context("Failed to retrieve the schema version")
+}
+
+/// Applies pending migrations, followed by the data migrations needing the
+/// configuration, while holding the migration lock. Fails if the database has
+/// been migrated by a newer binary.
+#[tracing::instrument(skip_all)]
+pub async fn run_migrations(db_pool: &PgPool, config: &Settings) -> Result<(), anyhow::Error> {
+    // A connection of its own, as closing it is what releases the lock,
+    // should migrating fail halfway through.
+    let mut connection = db_pool
+        .acquire()
+        .await
+        .context("Failed to connect to the database")?
+        .detach();
+
+    sqlx::query!("SELECT FROM pg_advisory_lock($1)", MIGRATION_LOCK_KEY)
+        .execute(&mut connection)
+        .await
+        .context("Failed to take the migration lock")?;
+
+    ensure_schema_supported(&mut connection).await?;
+    MIGRATOR
+        .run(&mut connection)
+        .await
+        .context("Failed to migrate the database")?;
+    hash_plaintext_tokens(&mut connection, &config.application.hmac_secret)
+        .await
+        .context("Failed to hash plaintext subscription tokens")?;
+    recanonicalise_emails(&mut connection, &config.local_part_rules)
+        .await
+        .context("Failed to recompute canonical subscriber emails")?;
+
+    connection
+        .close()
+        .await
+        .context("Failed to release the migration lock")
+}
+
+/// Fails if the database has been migrated by a newer binary, which may have
+/// changed the schema in ways this one does not expect.
+#[tracing::instrument(skip_all)]
+pub async fn check_schema_version(db_pool: &PgPool) -> Result<(), anyhow::Error> {
+    let mut connection = db_pool
+        .acquire()
+        .await
+        .context("Failed to connect to the database")?;
+
+    ensure_schema_supported(&mut connection).await
+}
+
+async fn ensure_schema_supported(connection: &mut PgConnection) -> Result<(), anyhow::Error> {
+    let schema_version = schema_version(connection).await?;
+    let supported_version = supported_schema_version();
+
+    if schema_version > supported_version {
+        anyhow::bail!(
+            "The database schema version {} is newer than the latest one this binary supports, {}",
+            schema_version.unwrap_or_default(),
+            supported_version.unwrap_or_default(),
+        );
+    }
+    if schema_version < supported_version {
+        tracing::warn!(
+            schema_version,
+            supported_version,
+            "The database has pending migrations"
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,131 @@
+use crate::configuration::WorkerSettings;
+use sqlx::{postgres::PgListener, PgExecutor, PgPool};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{sync::watch, task::JoinHandle};
+use tokio_util::sync::CancellationToken;
+
+/// Channel notified whenever delivery tasks are enqueued.
+const DELIVERY_QUEUE_CHANNEL: &str = "issue_delivery_queue";
+
+/// Tells the workers that there are new delivery tasks. Listeners are only
+/// notified once the transaction commits.
+#[tracing::instrument(skip_all)]
+pub(crate) async fn notify_workers<'e>(executor: impl PgExecutor<'e>) -> Result<(), sqlx::Error> {
+    sqlx::query!("SELECT FROM pg_notify($1, '')", DELIVERY_QUEUE_CHANNEL)
+        .execute(executor)
+        .await?;
+
+    Ok(())
+}
+
+/// Lets idle workers sleep until tasks are enqueued, polling every now and
+/// then for tasks becoming due, like digests and drip steps. Workers poll more
+/// often while the listener connection is down.
+#[derive(Clone)]
+pub struct QueueWakeup {
+    notifications: watch::Receiver<u64>,
+    listening: Arc<AtomicBool>,
+    poll_interval: Duration,
+    fallback_poll_interval: Duration,
+}
+
+impl QueueWakeup {
+    /// Waits for tasks enqueued since the last wakeup or for the poll interval
+    /// to pass, whichever comes first.
+    pub async fn wait_for_tasks(&mut self) {
+        let interval = if self.listening.load(Ordering::Relaxed) {
+            self.poll_interval
+        } else {
+            self.fallback_poll_interval
+        };
+
+        if let Ok(Err(_)) = tokio::time::timeout(interval, self.notifications.changed()).await {
+            // The listener has stopped, so there is nothing to wait for.
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+/// Listens for enqueued tasks on a dedicated connection until `stop` is
+/// cancelled, reconnecting whenever the connection drops.
+pub fn listen_for_tasks(
+    db_pool: PgPool,
+    settings: &WorkerSettings,
+    stop: CancellationToken,
+) -> (QueueWakeup, JoinHandle<()>) {
+    let (sender, notifications) = watch::channel(0);
+    let listening = Arc::new(AtomicBool::new(false));
+    let wakeup = QueueWakeup {
+        notifications,
+        listening: listening.clone(),
+        poll_interval: settings.poll_interval(),
+        fallback_poll_interval: settings.fallback_poll_interval(),
+    };
+
+    let listener = tokio::spawn(listen_until_stopped(
+        db_pool,
+        sender,
+        listening,
+        wakeup.fallback_poll_interval,
+        stop,
+    ));
+
+    (wakeup, listener)
+}
+
+async fn listen_until_stopped(
+    db_pool: PgPool,
+    sender: watch::Sender<u64>,
+    listening: Arc<AtomicBool>,
+    retry_interval: Duration,
+    stop: CancellationToken,
+) {
+    while !stop.is_cancelled() {
+        let result = tokio::select! {
+            _ = stop.cancelled() => break,
+            result = listen(&db_pool, &sender, &listening) => result,
+        };
+        listening.store(false, Ordering::Relaxed);
+
+        match result {
+            Ok(()) => tracing::warn!("Listener connection dropped. Falling back to polling."),
+            Err(e) => tracing::warn!(
+                error.message = %e,
+                "Listening for delivery tasks failed. Falling back to polling."
+            ),
+        }
+
+        tokio::select! {
+            _ = stop.cancelled() => {}
+            _ = tokio::time::sleep(retry_interval) => {}
+        }
+    }
+}
+
+/// Returns once the connection drops.
+async fn listen(
+    db_pool: &PgPool,
+    sender: &watch::Sender<u64>,
+    listening: &AtomicBool,
+) -> Result<(), sqlx::Error> {
+    let mut listener = PgListener::connect_with(db_pool).await?;
+    listener.listen(DELIVERY_QUEUE_CHANNEL).await?;
+    listening.store(true, Ordering::Relaxed);
+    tracing::info!("Listening for delivery tasks");
+
+    loop {
+        // Also wakes the workers right after connecting, as notifications
+        // sent while the connection was down are lost.
+        sender.send_modify(|generation| *generation += 1);
+
+        if listener.try_recv().await?.is_none() {
+            return Ok(());
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use crate::{
+    app_state::AppState,
+    audit::{list_audit_events, AuditEvent, AuditEventFilter, AuditEventType},
+    utils::{e422, e500, HttpError},
+};
+use anyhow::Context;
+use askama_axum::Template;
+use axum::{
+    extract::{Query, State},
+    http::header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use time::{
+    format_description::well_known::Rfc3339, macros::format_description, Date, Duration,
+    OffsetDateTime,
+};
+
+const PAGE_LIMIT: i64 = 200;
+
+#[tracing::instrument(name = "Get audit log page", skip(app_state))]
+pub(in crate::routes::admin) async fn audit_page(
+    State(app_state): State<AppState>,
+    Query(query): Query<AuditQuery>,
+) -> Result<AuditPage<'static>, HttpError<anyhow::Error>> {
+    let filter = query.filter().map_err(e422)?;
+    let events = list_audit_events(&app_state.db_pool, &filter, Some(PAGE_LIMIT))
+        .await
+        .map_err(e500)?
+        .into_iter()
+        .map(AuditEventRow::from)
+        .collect();
+
+    Ok(AuditPage {
+        page_title: "Audit Log",
+        occurred_at_label: "Time",
+        event_type_label: "Event",
+        actor_label: "Actor",
+        request_id_label: "Request id",
+        ip_address_label: "IP address",
+        payload_label: "Details",
+        since_label: "Since",
+        until_label: "Until",
+        any_label: "any",
+        filter_button: "Filter",
+        export_button: "Export as CSV",
+        back_link: "Back",
+        event_types: AuditEventType::ALL
+            .iter()
+            .map(|event_type| EventTypeOption {
+                name: event_type.as_str(),
+                selected: query.event_type == event_type.as_str(),
+            })
+            .collect(),
+        query,
+        events,
+    })
+}
+
+#[tracing::instrument(name = "Export audit log", skip(app_state))]
+pub(in crate::routes::admin) async fn export_audit_events(
+    State(app_state): State<AppState>,
+    Query(query): Query<AuditQuery>,
+) -> Result<impl IntoResponse, HttpError<anyhow::Error>> {
+    let filter = query.filter().map_err(e422)?;
+    let events = list_audit_events(&app_state.db_pool, &filter, None)
+        .await
+        .map_err(e500)?;
+
+    let csv = to_csv(events).map_err(e500)?;
+
+    Ok((
+        [
+            (CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (
+                CONTENT_DISPOSITION,
+                "attachment; filename=\"audit_events.csv\"",
+            ),
+        ],
+        csv,
+    ))
+}
+
+fn to_csv(events: Vec<AuditEvent>) -> Result<Vec<u8>, anyhow::Error> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record([
+        "audit_event_id",
+        "occurred_at",
+        "event_type",
+        "actor_user_id",
+        "actor_username",
+        "request_id",
+        "ip_address",
+        "payload",
+    ])?;
+
+    for event in events {
+        writer.write_record(
+            [
+                event.audit_event_id.to_string(),
+                format_timestamp(event.occurred_at),
+                event.event_type,
+                event
+                    .actor_user_id
+                    .map(|id| id.to_string())
+                    .unwrap_or_default(),
+                event.actor_username.unwrap_or_default(),
+                event.request_id.unwrap_or_default(),
+                event.ip_address.unwrap_or_default(),
+                event.payload.to_string(),
+            ]
+            .map(escape_formula),
+        )?;
+    }
+
+    writer
+        .into_inner()
+        .context("Failed to write audit events as CSV")
+}
+
+/// Keeps spreadsheets from evaluating cells as formulas. Some of them come
+/// from requests, like the request id, so anyone can choose what they hold.
+fn escape_formula(cell: String) -> String {
+    if cell.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{cell}")
+    } else {
+        cell
+    }
+}
+
+/// Filters from the query string. Empty values, as sent by the filter form
+/// for untouched fields, mean no filtering.
+#[derive(Debug, Default, Deserialize)]
+pub(in crate::routes::admin) struct AuditQuery {
+    #[serde(default)]
+    event_type: String,
+    #[serde(default)]
+    actor: String,
+    #[serde(default)]
+    since: String,
+    #[serde(default)]
+    until: String,
+}
+
+impl AuditQuery {
+    fn filter(&self) -> Result<AuditEventFilter, anyhow::Error> {
+        let event_type = non_empty(&self.event_type).map(str::parse).transpose()?;
+        let since = non_empty(&self.since).map(parse_date).transpose()?;
+        // The `until` date is inclusive.
+        let until = non_empty(&self.until)
+            .map(parse_date)
+            .transpose()?
+            .map(|until| until + Duration::days(1));
+
+        Ok(AuditEventFilter {
+            event_type,
+            actor_username: non_empty(&self.actor).map(str::to_string),
+            since,
+            until,
+        })
+    }
+}
+
+fn non_empty(value: &str) -> Option<&str> {
+    Some(value.trim()).filter(|value| !value.is_empty())
+}
+
+fn parse_date(value: &str) -> Result<OffsetDateTime, anyhow::Error> {
+    let date = Date::parse(value, format_description!("[year]-[month]-[day]"))
+        .with_context(|| format!("`{value}` is not a valid date"))?;
+
+    Ok(date.midnight().assume_utc())
+}
+
+#[derive(Template)]
+#[template(path = "web/audit.html")]
+pub(in crate::routes::admin) struct AuditPage<'a> {
+    page_title: &'a str,
+    occurred_at_label: &'a str,
+    event_type_label: &'a str,
+    actor_label: &'a str,
+    request_id_label: &'a str,
+    ip_address_label: &'a str,
+    payload_label: &'a str,
+    since_label: &'a str,
+    until_label: &'a str,
+    any_label: &'a str,
+    filter_button: &'a str,
+    export_button: &'a str,
+    back_link: &'a str,
+    event_types: Vec<EventTypeOption>,
+    query: AuditQuery,
+    events: Vec<AuditEventRow>,
+}
+
+struct EventTypeOption {
+    name: &'static str,
+    selected: bool,
+}
+
+struct AuditEventRow {
+    occurred_at: String,
+    event_type: String,
+    actor: String,
+    request_id: String,
+    ip_address: String,
+    payload: String,
+}
+
+impl From<AuditEvent> for AuditEventRow {
+    fn from(event: AuditEvent) -> Self {
+        Self {
+            occurred_at: format_timestamp(event.occurred_at),
+            event_type: event.event_type,
+            actor: event
+                .actor_username
+                .or_else(|| event.actor_user_id.map(|id| id.to_string()))
+                .unwrap_or_default(),
+            request_id: event.request_id.unwrap_or_default(),
+            ip_address: event.ip_address.unwrap_or_default(),
+            payload: event.payload.to_string(),
+        }
+    }
+}
+
+fn format_timestamp(timestamp: OffsetDateTime) -> String {
+    timestamp
+        .format(&Rfc3339)
+        .unwrap_or_else(|_| timestamp.to_string())
+}
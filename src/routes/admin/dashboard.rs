@@ -1,6 +1,7 @@
 use crate::{
     app_state::AppState,
     authentication::extract::SessionUserId,
+    subscribe_protection::Rejection,
     utils::{e500, HttpError},
 };
 
@@ -19,14 +20,28 @@ pub(super) async fn admin_dashboard(
         .await
         .map_err(e500)?;
 
+    let metrics = app_state.subscribe_protection.metrics();
+    let subscribe_rejections = Rejection::ALL
+        .into_iter()
+        .map(|rejection| (rejection.as_str(), metrics.count(rejection)))
+        .collect();
+
     Ok(Dashboard {
         page_title: "Admin Dashboard",
         welcome: "Welcome",
         available_actions: "Available actions",
         send_newsletter: "Send newsletter",
         change_password: "Change password",
+        manage_sessions: "Active sessions",
+        manage_tokens: "API tokens",
+        manage_sequences: "Onboarding sequences",
+        manage_suppressions: "Suppression list",
+        audit_log: "Audit log",
+        system_status: "System status",
         logout: "Logout",
+        rejected_subscriptions: "Rejected subscription attempts since startup",
         username,
+        subscribe_rejections,
     })
 }
 
@@ -55,6 +70,14 @@ pub struct Dashboard<'a> {
     available_actions: &'a str,
     send_newsletter: &'a str,
     change_password: &'a str,
+    manage_sessions: &'a str,
+    manage_tokens: &'a str,
+    manage_sequences: &'a str,
+    manage_suppressions: &'a str,
+    audit_log: &'a str,
+    system_status: &'a str,
     logout: &'a str,
+    rejected_subscriptions: &'a str,
     username: String,
+    subscribe_rejections: Vec<(&'static str, u64)>,
 }
@@ -1,16 +1,36 @@
 use crate::{
+    app_state::AppState,
+    audit::{record_audit_event, AuditContext, AuditEventType},
+    authentication::sessions::forget_session,
     session_state::TypedSession,
     utils::{e500, HttpError},
 };
-use axum::response::Redirect;
+use axum::{extract::State, response::Redirect};
 use axum_messages::Messages;
+use serde_json::json;
 
-#[tracing::instrument(skip(session, messages))]
+#[tracing::instrument(skip(app_state, session, audit_context, messages))]
 pub(super) async fn log_out(
+    State(app_state): State<AppState>,
     session: TypedSession,
+    audit_context: AuditContext,
     messages: Messages,
 ) -> Result<Redirect, HttpError<anyhow::Error>> {
-    if session.get_user_id().await.map_err(e500)?.is_some() {
+    if let Some(user_id) = session.get_user_id().await.map_err(e500)? {
+        if let Some(session_id) = session.id() {
+            forget_session(&app_state.db_pool, &session_id)
+                .await
+                .map_err(e500)?;
+        }
+        record_audit_event(
+            &app_state.db_pool,
+            &audit_context,
+            Some(user_id),
+            AuditEventType::LoggedOut,
+            json!({}),
+        )
+        .await
+        .map_err(e500)?;
         session.flush().await.map_err(e500)?;
         messages.info("You have successfully logged out.");
     }
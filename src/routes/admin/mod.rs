@@ -1,29 +1,63 @@
 use crate::{app_state::AppState, authentication::middleware::AuthorizedSessionLayer};
+use audit::{audit_page, export_audit_events};
 use axum::{
     routing::{get, post},
     Router,
 };
 use dashboard::admin_dashboard;
+pub(crate) use dashboard::get_username;
 use logout::log_out;
+pub(crate) use newsletters::{enqueue_delivery_tasks, insert_newsletter_issue};
 use newsletters::{newsletter_form, publish_newsletter};
 use password::{change_password, change_password_form};
+use sequences::{add_step, create_sequence, sequences_page, set_sequence_active};
+use sessions::{log_out_other_sessions, log_out_session, sessions_page};
+use sqlx::PgPool;
+use status::status_page;
+use suppressions::{
+    add_suppression_entry, import_suppressions, remove_suppression_entry, suppressions_page,
+};
+use tokens::{create_token, revoke_token, tokens_page};
 
+mod audit;
 mod dashboard;
 mod logout;
 mod newsletters;
 mod password;
+mod sequences;
+mod sessions;
+mod status;
+mod suppressions;
+mod tokens;
 
-pub fn router() -> Router<AppState> {
+pub fn router(db_pool: PgPool) -> Router<AppState> {
     Router::new()
         .nest(
             "/admin",
             Router::new()
+                .route("/audit", get(audit_page))
+                .route("/audit/export", get(export_audit_events))
                 .route("/dashboard", get(admin_dashboard))
                 .route("/newsletters", get(newsletter_form))
                 .route("/newsletters", post(publish_newsletter))
                 .route("/password", get(change_password_form))
                 .route("/password", post(change_password))
+                .route("/sequences", get(sequences_page))
+                .route("/sequences", post(create_sequence))
+                .route("/sequences/active", post(set_sequence_active))
+                .route("/sequences/steps", post(add_step))
+                .route("/sessions", get(sessions_page))
+                .route("/sessions/logout", post(log_out_session))
+                .route("/sessions/logout_others", post(log_out_other_sessions))
+                .route("/status", get(status_page))
+                .route("/suppressions", get(suppressions_page))
+                .route("/suppressions", post(add_suppression_entry))
+                .route("/suppressions/import", post(import_suppressions))
+                .route("/suppressions/remove", post(remove_suppression_entry))
+                .route("/tokens", get(tokens_page))
+                .route("/tokens", post(create_token))
+                .route("/tokens/revoke", post(revoke_token))
                 .route("/logout", post(log_out)),
         )
-        .layer(AuthorizedSessionLayer)
+        .layer(AuthorizedSessionLayer::new(db_pool))
 }
@@ -1,9 +1,12 @@
+use crate::app_state::AppState;
 use askama_axum::Template;
+use axum::extract::State;
 use axum_messages::Messages;
 use uuid::Uuid;
 
-#[tracing::instrument(name = "Get newsletter form", skip(messages))]
+#[tracing::instrument(name = "Get newsletter form", skip_all)]
 pub(in crate::routes::admin) async fn newsletter_form(
+    State(app_state): State<AppState>,
     messages: Messages,
 ) -> NewsletterForm<'static> {
     let flashes = messages.map(|m| m.message).collect();
@@ -16,6 +19,9 @@ pub(in crate::routes::admin) async fn newsletter_form(
         html_content_placeholder: "Enter newsletter HTML content",
         text_content_label: "Newsletter text",
         text_content_placeholder: "Enter newsletter text",
+        tracking_enabled: app_state.tracking_enabled,
+        track_opens_label: "Track opens",
+        track_clicks_label: "Track link clicks",
         send_newsletter_button: "Send newsletter",
         back_link: "Back",
         idempotency_key: Uuid::new_v4().into(),
@@ -33,6 +39,9 @@ pub(in crate::routes::admin) struct NewsletterForm<'a> {
     html_content_placeholder: &'a str,
     text_content_label: &'a str,
     text_content_placeholder: &'a str,
+    tracking_enabled: bool,
+    track_opens_label: &'a str,
+    track_clicks_label: &'a str,
     send_newsletter_button: &'a str,
     back_link: &'a str,
     idempotency_key: String,
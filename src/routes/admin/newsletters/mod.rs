@@ -3,3 +3,4 @@ mod post;
 
 pub(super) use get::newsletter_form;
 pub(super) use post::publish_newsletter;
+pub(crate) use post::{enqueue_delivery_tasks, insert_newsletter_issue};
@@ -1,7 +1,9 @@
 use crate::{
     app_state::AppState,
+    audit::{record_audit_event, AuditContext, AuditEventType},
     authentication::extract::SessionUserId,
     idempotency::{save_response, try_processing, IdempotencyKey, NextAction},
+    queue_listener::notify_workers,
     utils::{e422, e500, HttpError},
 };
 use anyhow::Context;
@@ -9,6 +11,7 @@ use askama_axum::IntoResponse;
 use axum::{body::Body, extract::State, http::Response, response::Redirect, Form};
 use axum_messages::Messages;
 use serde::Deserialize;
+use serde_json::json;
 use sqlx::{Executor, Postgres, Transaction};
 use uuid::Uuid;
 
@@ -16,6 +19,7 @@ use uuid::Uuid;
 pub(in crate::routes::admin) async fn publish_newsletter(
     State(app_state): State<AppState>,
     SessionUserId(user_id): SessionUserId,
+    audit_context: AuditContext,
     messages: Messages,
     Form(form): Form<FormData>,
 ) -> Result<Response<Body>, HttpError<anyhow::Error>> {
@@ -37,6 +41,8 @@ pub(in crate::routes::admin) async fn publish_newsletter(
         &form.title,
         &form.text_content,
         &form.html_content,
+        form.track_opens && app_state.tracking_enabled,
+        form.track_clicks && app_state.tracking_enabled,
     )
     .await
     .context("Failed to store newsletter issue details")
@@ -47,6 +53,16 @@ pub(in crate::routes::admin) async fn publish_newsletter(
         .context("Failed to enqueue delivery tasks")
         .map_err(e500)?;
 
+    record_audit_event(
+        &mut *transaction,
+        &audit_context,
+        Some(user_id),
+        AuditEventType::NewsletterPublished,
+        json!({ "newsletter_issue_id": issue_id, "title": &form.title }),
+    )
+    .await
+    .map_err(e500)?;
+
     success_message(messages);
 
     let response = Redirect::to("/admin/newsletters").into_response();
@@ -56,11 +72,13 @@ pub(in crate::routes::admin) async fn publish_newsletter(
 }
 
 #[tracing::instrument(skip_all)]
-async fn insert_newsletter_issue(
+pub(crate) async fn insert_newsletter_issue(
     transaction: &mut Transaction<'_, Postgres>,
     title: &str,
     text_content: &str,
     html_content: &str,
+    track_opens: bool,
+    track_clicks: bool,
 ) -> Result<Uuid, sqlx::Error> {
     let newsletter_issue_id = Uuid::new_v4();
     let query = sqlx::query!(
@@ -70,14 +88,18 @@ async fn insert_newsletter_issue(
             title,
             text_content,
             html_content,
+            track_opens,
+            track_clicks,
             published_at
         )
-        VALUES ($1, $2, $3, $4, now())
+        VALUES ($1, $2, $3, $4, $5, $6, now())
         "#,
         newsletter_issue_id,
         title,
         text_content,
-        html_content
+        html_content,
+        track_opens,
+        track_clicks,
     );
 
     transaction.execute(query).await?;
@@ -86,7 +108,7 @@ async fn insert_newsletter_issue(
 }
 
 #[tracing::instrument(skip_all)]
-async fn enqueue_delivery_tasks(
+pub(crate) async fn enqueue_delivery_tasks(
     transaction: &mut Transaction<'_, Postgres>,
     newsletter_issue_id: Uuid,
 ) -> Result<(), sqlx::Error> {
@@ -94,9 +116,9 @@ async fn enqueue_delivery_tasks(
         r#"
         INSERT INTO issue_delivery_queue (
             newsletter_issue_id,
-            subscriber_email
+            canonical_email
         )
-        SELECT $1, email
+        SELECT $1, canonical_email
         FROM subscriptions
         WHERE status = 'confirmed'
         "#,
@@ -104,6 +126,7 @@ async fn enqueue_delivery_tasks(
     );
 
     transaction.execute(query).await?;
+    notify_workers(&mut **transaction).await?;
 
     Ok(())
 }
@@ -114,6 +137,10 @@ pub(in crate::routes::admin) struct FormData {
     html_content: String,
     text_content: String,
     idempotency_key: String,
+    #[serde(default)]
+    track_opens: bool,
+    #[serde(default)]
+    track_clicks: bool,
 }
 
 fn success_message(messages: Messages) {
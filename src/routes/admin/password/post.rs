@@ -1,23 +1,29 @@
 use crate::{
     app_state::AppState,
+    audit::{record_audit_event, AuditContext, AuditEventType},
     authentication::{
         extract::SessionUserId,
         password::{
             change_password as auth_change_password, validate_credentials, AuthError, Credentials,
         },
+        sessions::revoke_other_sessions,
     },
     routes::admin::dashboard::get_username,
+    session_state::TypedSession,
     utils::{e500, HttpError},
 };
 use axum::{extract::State, response::Redirect, Form};
 use axum_messages::Messages;
 use secrecy::{ExposeSecret, Secret};
 use serde::Deserialize;
+use serde_json::json;
 
-#[tracing::instrument(skip(app_state, user_id, messages, form))]
+#[tracing::instrument(skip(app_state, user_id, session, audit_context, messages, form))]
 pub(in crate::routes::admin) async fn change_password(
     State(app_state): State<AppState>,
     SessionUserId(user_id): SessionUserId,
+    session: TypedSession,
+    audit_context: AuditContext,
     messages: Messages,
     Form(form): Form<FormData>,
 ) -> Result<Redirect, HttpError<anyhow::Error>> {
@@ -29,15 +35,17 @@ pub(in crate::routes::admin) async fn change_password(
         return Ok(Redirect::to("/admin/password"));
     }
 
-    let credentials = get_username(&app_state.db_pool, user_id)
+    let username = get_username(&app_state.db_pool, user_id)
         .await
-        .map(|username| Credentials {
-            username,
-            password: form.current_password,
-        })
         .map_err(e500)?;
+    let credentials = Credentials {
+        username: username.clone(),
+        password: form.current_password,
+    };
 
-    if let Err(e) = validate_credentials(&app_state.db_pool, credentials).await {
+    if let Err(e) =
+        validate_credentials(&app_state.db_pool, &app_state.password_hashing, credentials).await
+    {
         return match e {
             AuthError::InvalidCredentials(_) => {
                 messages.error("The current password is incorrect.");
@@ -47,14 +55,41 @@ pub(in crate::routes::admin) async fn change_password(
         };
     }
 
-    if let Err(e) = validate_password(&form.new_password) {
-        messages.error(e);
+    if let Err(e) = app_state
+        .password_policy
+        .validate(&username, &form.new_password)
+    {
+        messages.error(e.to_string());
         return Ok(Redirect::to("/admin/password"));
     }
 
-    auth_change_password(&app_state.db_pool, user_id, form.new_password)
-        .await
-        .map_err(e500)?;
+    auth_change_password(
+        &app_state.db_pool,
+        &app_state.password_hashing,
+        user_id,
+        form.new_password,
+    )
+    .await
+    .map_err(e500)?;
+
+    let revoked_sessions = revoke_other_sessions(
+        &app_state.db_pool,
+        &app_state.session_store,
+        user_id,
+        session.id().as_ref(),
+    )
+    .await
+    .map_err(e500)?;
+
+    record_audit_event(
+        &app_state.db_pool,
+        &audit_context,
+        Some(user_id),
+        AuditEventType::PasswordChanged,
+        json!({ "revoked_sessions": revoked_sessions }),
+    )
+    .await
+    .map_err(e500)?;
 
     messages.info("Your password has been changed.");
 
@@ -67,17 +102,3 @@ pub(in crate::routes::admin) struct FormData {
     new_password: Secret<String>,
     new_password_check: Secret<String>,
 }
-
-fn validate_password(password: &Secret<String>) -> Result<(), &'static str> {
-    let password = password.expose_secret();
-
-    if password.len() < 12 {
-        return Err("Password must be at least 12 characters long.");
-    }
-
-    if password.len() > 128 {
-        return Err("Passwords must be at most 128 characters long.");
-    }
-
-    Ok(())
-}
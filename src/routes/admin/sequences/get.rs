@@ -0,0 +1,132 @@
+use crate::{
+    app_state::AppState,
+    utils::{e500, HttpError},
+};
+use anyhow::Context;
+use askama_axum::Template;
+use axum::extract::State;
+use axum_messages::Messages;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[tracing::instrument(name = "Get drip sequences page", skip_all)]
+pub(in crate::routes::admin) async fn sequences_page(
+    State(app_state): State<AppState>,
+    messages: Messages,
+) -> Result<SequencesPage<'static>, HttpError<anyhow::Error>> {
+    let flashes = messages.map(|m| m.message).collect();
+    let sequences = list_sequences(&app_state.db_pool).await.map_err(e500)?;
+
+    Ok(SequencesPage {
+        page_title: "Onboarding Sequences",
+        description: "Confirmed subscribers enter every active sequence once, \
+            and receive its steps the given number of days later.",
+        in_progress_label: "In progress",
+        completed_label: "Completed",
+        stopped_label: "Stopped",
+        delay_label: "Day",
+        title_label: "Title",
+        html_content_label: "HTML content",
+        text_content_label: "Text",
+        add_step_button: "Add step",
+        activate_button: "Activate",
+        deactivate_button: "Deactivate",
+        new_sequence_label: "New sequence",
+        name_label: "Name",
+        create_button: "Create sequence",
+        back_link: "Back",
+        sequences,
+        flashes,
+    })
+}
+
+#[tracing::instrument(skip_all)]
+async fn list_sequences(db_pool: &PgPool) -> Result<Vec<SequenceRow>, anyhow::Error> {
+    let sequences = sqlx::query!(
+        r#"
+        SELECT
+            s.drip_sequence_id,
+            s.name,
+            s.active,
+            COUNT(e.subscriber_id) FILTER (WHERE e.next_send_at IS NOT NULL) AS "in_progress!",
+            COUNT(e.subscriber_id) FILTER (WHERE e.completed_at IS NOT NULL) AS "completed!",
+            COUNT(e.subscriber_id) FILTER (WHERE e.stopped_at IS NOT NULL) AS "stopped!"
+        FROM drip_sequences s
+        LEFT JOIN drip_enrollments e ON e.drip_sequence_id = s.drip_sequence_id
+        GROUP BY s.drip_sequence_id
+        ORDER BY s.created_at
+        "#,
+    )
+    .fetch_all(db_pool)
+    .await
+    .context("Failed to retrieve drip sequences")?;
+
+    let steps = sqlx::query!(
+        r#"
+        SELECT drip_sequence_id, delay_days, title
+        FROM drip_steps
+        ORDER BY drip_sequence_id, position
+        "#,
+    )
+    .fetch_all(db_pool)
+    .await
+    .context("Failed to retrieve drip steps")?;
+
+    Ok(sequences
+        .into_iter()
+        .map(|sequence| SequenceRow {
+            steps: steps
+                .iter()
+                .filter(|step| step.drip_sequence_id == sequence.drip_sequence_id)
+                .map(|step| StepRow {
+                    delay_days: step.delay_days,
+                    title: step.title.clone(),
+                })
+                .collect(),
+            drip_sequence_id: sequence.drip_sequence_id,
+            name: sequence.name,
+            active: sequence.active,
+            in_progress: sequence.in_progress,
+            completed: sequence.completed,
+            stopped: sequence.stopped,
+        })
+        .collect())
+}
+
+#[derive(Template)]
+#[template(path = "web/sequences.html")]
+pub(in crate::routes::admin) struct SequencesPage<'a> {
+    page_title: &'a str,
+    description: &'a str,
+    in_progress_label: &'a str,
+    completed_label: &'a str,
+    stopped_label: &'a str,
+    delay_label: &'a str,
+    title_label: &'a str,
+    html_content_label: &'a str,
+    text_content_label: &'a str,
+    add_step_button: &'a str,
+    activate_button: &'a str,
+    deactivate_button: &'a str,
+    new_sequence_label: &'a str,
+    name_label: &'a str,
+    create_button: &'a str,
+    back_link: &'a str,
+    sequences: Vec<SequenceRow>,
+    flashes: Vec<String>,
+}
+
+struct SequenceRow {
+    drip_sequence_id: Uuid,
+    name: String,
+    active: bool,
+    in_progress: i64,
+    completed: i64,
+    stopped: i64,
+    steps: Vec<StepRow>,
+}
+
+struct StepRow {
+    delay_days: i32,
+    title: String,
+}
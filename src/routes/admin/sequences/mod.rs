@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub(super) use get::sequences_page;
+pub(super) use post::{add_step, create_sequence, set_sequence_active};
@@ -0,0 +1,197 @@
+use crate::{
+    app_state::AppState,
+    utils::{e500, HttpError},
+};
+use anyhow::Context;
+use axum::{extract::State, response::Redirect, Form};
+use axum_messages::Messages;
+use serde::Deserialize;
+use uuid::Uuid;
+
+#[tracing::instrument(skip_all)]
+pub(in crate::routes::admin) async fn create_sequence(
+    State(app_state): State<AppState>,
+    messages: Messages,
+    Form(form): Form<CreateFormData>,
+) -> Result<Redirect, HttpError<anyhow::Error>> {
+    let name = form.name.trim();
+    if name.is_empty() {
+        messages.error("The sequence name must not be empty.");
+        return Ok(Redirect::to("/admin/sequences"));
+    }
+
+    // Sequences start inactive, so that nobody enters one without steps.
+    sqlx::query!(
+        r#"
+        INSERT INTO drip_sequences (drip_sequence_id, name, active)
+        VALUES ($1, $2, false)
+        "#,
+        Uuid::new_v4(),
+        name,
+    )
+    .execute(&app_state.db_pool)
+    .await
+    .context("Failed to create drip sequence")
+    .map_err(e500)?;
+
+    messages.info("The sequence has been created. Add its steps, then activate it.");
+
+    Ok(Redirect::to("/admin/sequences"))
+}
+
+#[tracing::instrument(skip_all, fields(drip_sequence_id=%form.drip_sequence_id))]
+pub(in crate::routes::admin) async fn add_step(
+    State(app_state): State<AppState>,
+    messages: Messages,
+    Form(form): Form<StepFormData>,
+) -> Result<Redirect, HttpError<anyhow::Error>> {
+    if form.delay_days < 0 {
+        messages.error("The delay must not be negative.");
+        return Ok(Redirect::to("/admin/sequences"));
+    }
+    if [&form.title, &form.html_content, &form.text_content]
+        .iter()
+        .any(|field| field.trim().is_empty())
+    {
+        messages.error("The title and both contents of a step must not be empty.");
+        return Ok(Redirect::to("/admin/sequences"));
+    }
+
+    let mut transaction = app_state
+        .db_pool
+        .begin()
+        .await
+        .context("Failed to begin transaction")
+        .map_err(e500)?;
+
+    // Locks the sequence, so that concurrent additions get distinct positions.
+    let sequence_exists = sqlx::query!(
+        r#"
+        SELECT drip_sequence_id
+        FROM drip_sequences
+        WHERE drip_sequence_id = $1
+        FOR UPDATE
+        "#,
+        form.drip_sequence_id,
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to fetch drip sequence")
+    .map_err(e500)?
+    .is_some();
+    if !sequence_exists {
+        messages.error("The sequence does not exist.");
+        return Ok(Redirect::to("/admin/sequences"));
+    }
+
+    let last_step = sqlx::query!(
+        r#"
+        SELECT position, delay_days
+        FROM drip_steps
+        WHERE drip_sequence_id = $1
+        ORDER BY position DESC
+        LIMIT 1
+        "#,
+        form.drip_sequence_id,
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to fetch the last drip step")
+    .map_err(e500)?;
+
+    // Steps are sent in order, so a step cannot come before the previous one.
+    if let Some(last_step) = &last_step {
+        if form.delay_days < last_step.delay_days {
+            messages.error(format!(
+                "The step must come on day {} or later, after the previous step.",
+                last_step.delay_days
+            ));
+            return Ok(Redirect::to("/admin/sequences"));
+        }
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO drip_steps (
+            drip_sequence_id,
+            position,
+            delay_days,
+            title,
+            text_content,
+            html_content
+        )
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        form.drip_sequence_id,
+        last_step.map_or(0, |step| step.position + 1),
+        form.delay_days,
+        form.title,
+        form.text_content,
+        form.html_content,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to add drip step")
+    .map_err(e500)?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit transaction")
+        .map_err(e500)?;
+
+    messages.info("The step has been added.");
+
+    Ok(Redirect::to("/admin/sequences"))
+}
+
+/// Inactive sequences are not entered by new subscribers. Subscribers
+/// already in one keep receiving its steps.
+#[tracing::instrument(skip_all, fields(drip_sequence_id=%form.drip_sequence_id))]
+pub(in crate::routes::admin) async fn set_sequence_active(
+    State(app_state): State<AppState>,
+    messages: Messages,
+    Form(form): Form<ActiveFormData>,
+) -> Result<Redirect, HttpError<anyhow::Error>> {
+    sqlx::query!(
+        r#"
+        UPDATE drip_sequences
+        SET active = $1
+        WHERE drip_sequence_id = $2
+        "#,
+        form.active,
+        form.drip_sequence_id,
+    )
+    .execute(&app_state.db_pool)
+    .await
+    .context("Failed to update drip sequence")
+    .map_err(e500)?;
+
+    messages.info(if form.active {
+        "The sequence has been activated."
+    } else {
+        "The sequence has been deactivated."
+    });
+
+    Ok(Redirect::to("/admin/sequences"))
+}
+
+#[derive(Deserialize)]
+pub(in crate::routes::admin) struct CreateFormData {
+    name: String,
+}
+
+#[derive(Deserialize)]
+pub(in crate::routes::admin) struct StepFormData {
+    drip_sequence_id: Uuid,
+    delay_days: i32,
+    title: String,
+    html_content: String,
+    text_content: String,
+}
+
+#[derive(Deserialize)]
+pub(in crate::routes::admin) struct ActiveFormData {
+    drip_sequence_id: Uuid,
+    active: bool,
+}
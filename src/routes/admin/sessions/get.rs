@@ -0,0 +1,95 @@
+use crate::{
+    app_state::AppState,
+    authentication::{
+        extract::SessionUserId,
+        sessions::{list_sessions, UserSession},
+    },
+    session_state::TypedSession,
+    utils::{e500, HttpError},
+};
+use askama_axum::Template;
+use axum::extract::State;
+use axum_messages::Messages;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+#[tracing::instrument(name = "Get sessions page", skip_all, fields(user_id=%user_id))]
+pub(in crate::routes::admin) async fn sessions_page(
+    State(app_state): State<AppState>,
+    SessionUserId(user_id): SessionUserId,
+    session: TypedSession,
+    messages: Messages,
+) -> Result<SessionsPage<'static>, HttpError<anyhow::Error>> {
+    let flashes = messages.map(|m| m.message).collect();
+
+    let sessions = list_sessions(
+        &app_state.db_pool,
+        &app_state.session_store,
+        user_id,
+        session.id().as_ref(),
+    )
+    .await
+    .map_err(e500)?
+    .into_iter()
+    .map(SessionRow::from)
+    .collect();
+
+    Ok(SessionsPage {
+        page_title: "Active Sessions",
+        ip_address_label: "IP address",
+        user_agent_label: "User agent",
+        created_at_label: "Logged in at",
+        last_seen_at_label: "Last seen at",
+        current_session_label: "This session",
+        unknown_label: "unknown",
+        log_out_button: "Log out",
+        log_out_others_button: "Log out other sessions",
+        back_link: "Back",
+        sessions,
+        flashes,
+    })
+}
+
+#[derive(Template)]
+#[template(path = "web/sessions.html")]
+pub(in crate::routes::admin) struct SessionsPage<'a> {
+    page_title: &'a str,
+    ip_address_label: &'a str,
+    user_agent_label: &'a str,
+    created_at_label: &'a str,
+    last_seen_at_label: &'a str,
+    current_session_label: &'a str,
+    unknown_label: &'a str,
+    log_out_button: &'a str,
+    log_out_others_button: &'a str,
+    back_link: &'a str,
+    sessions: Vec<SessionRow>,
+    flashes: Vec<String>,
+}
+
+struct SessionRow {
+    user_session_id: String,
+    ip_address: Option<String>,
+    user_agent: Option<String>,
+    created_at: String,
+    last_seen_at: String,
+    is_current: bool,
+}
+
+impl From<UserSession> for SessionRow {
+    fn from(session: UserSession) -> Self {
+        Self {
+            user_session_id: session.user_session_id.to_string(),
+            ip_address: session.ip_address,
+            user_agent: session.user_agent,
+            created_at: format_timestamp(session.created_at),
+            last_seen_at: format_timestamp(session.last_seen_at),
+            is_current: session.is_current,
+        }
+    }
+}
+
+fn format_timestamp(timestamp: OffsetDateTime) -> String {
+    timestamp
+        .format(&Rfc3339)
+        .unwrap_or_else(|_| timestamp.to_string())
+}
@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub(super) use get::sessions_page;
+pub(super) use post::{log_out_other_sessions, log_out_session};
@@ -0,0 +1,64 @@
+use crate::{
+    app_state::AppState,
+    authentication::{
+        extract::SessionUserId,
+        sessions::{revoke_other_sessions, revoke_session},
+    },
+    session_state::TypedSession,
+    utils::{e500, HttpError},
+};
+use axum::{extract::State, response::Redirect, Form};
+use axum_messages::Messages;
+use serde::Deserialize;
+use uuid::Uuid;
+
+#[tracing::instrument(skip_all, fields(user_id=%user_id))]
+pub(in crate::routes::admin) async fn log_out_session(
+    State(app_state): State<AppState>,
+    SessionUserId(user_id): SessionUserId,
+    messages: Messages,
+    Form(form): Form<FormData>,
+) -> Result<Redirect, HttpError<anyhow::Error>> {
+    let revoked = revoke_session(
+        &app_state.db_pool,
+        &app_state.session_store,
+        user_id,
+        form.user_session_id,
+    )
+    .await
+    .map_err(e500)?;
+
+    if revoked {
+        messages.info("The session has been logged out.");
+    } else {
+        messages.error("The session does not exist or has already expired.");
+    }
+
+    Ok(Redirect::to("/admin/sessions"))
+}
+
+#[tracing::instrument(skip_all, fields(user_id=%user_id))]
+pub(in crate::routes::admin) async fn log_out_other_sessions(
+    State(app_state): State<AppState>,
+    SessionUserId(user_id): SessionUserId,
+    session: TypedSession,
+    messages: Messages,
+) -> Result<Redirect, HttpError<anyhow::Error>> {
+    revoke_other_sessions(
+        &app_state.db_pool,
+        &app_state.session_store,
+        user_id,
+        session.id().as_ref(),
+    )
+    .await
+    .map_err(e500)?;
+
+    messages.info("All other sessions have been logged out.");
+
+    Ok(Redirect::to("/admin/sessions"))
+}
+
+#[derive(Deserialize)]
+pub(in crate::routes::admin) struct FormData {
+    user_session_id: Uuid,
+}
@@ -0,0 +1,52 @@
+use crate::{
+    app_state::AppState,
+    migrations::{schema_version, supported_schema_version},
+    utils::{e500, HttpError},
+};
+use anyhow::{Context, Error};
+use askama::Template;
+use axum::extract::State;
+
+#[tracing::instrument(name = "Get status page", skip_all)]
+pub(super) async fn status_page(
+    State(app_state): State<AppState>,
+) -> Result<StatusPage<'static>, HttpError<Error>> {
+    let mut connection = app_state
+        .db_pool
+        .acquire()
+        .await
+        .context("Failed to connect to the database")
+        .map_err(e500)?;
+    let schema_version = schema_version(&mut connection).await.map_err(e500)?;
+    let supported_schema_version = supported_schema_version();
+
+    Ok(StatusPage {
+        page_title: "System Status",
+        application_version_label: "Application version",
+        schema_version_label: "Database schema version",
+        supported_schema_version_label: "Latest supported schema version",
+        pending_migrations: "The database has pending migrations.",
+        not_migrated: "none",
+        back_link: "Back",
+        application_version: env!("CARGO_PKG_VERSION"),
+        schema_version,
+        supported_schema_version,
+        has_pending_migrations: schema_version < supported_schema_version,
+    })
+}
+
+#[derive(Template)]
+#[template(path = "web/status.html")]
+pub struct StatusPage<'a> {
+    page_title: &'a str,
+    application_version_label: &'a str,
+    schema_version_label: &'a str,
+    supported_schema_version_label: &'a str,
+    pending_migrations: &'a str,
+    not_migrated: &'a str,
+    back_link: &'a str,
+    application_version: &'a str,
+    schema_version: Option<i64>,
+    supported_schema_version: Option<i64>,
+    has_pending_migrations: bool,
+}
@@ -0,0 +1,107 @@
+use crate::{
+    app_state::AppState,
+    suppression_list::{list_suppressions, Suppression},
+    utils::{e500, HttpError},
+};
+use askama_axum::Template;
+use axum::extract::State;
+use axum_messages::Messages;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+#[tracing::instrument(name = "Get suppressions page", skip_all)]
+pub(in crate::routes::admin) async fn suppressions_page(
+    State(app_state): State<AppState>,
+    messages: Messages,
+) -> Result<SuppressionsPage<'static>, HttpError<anyhow::Error>> {
+    let flashes = messages.map(|m| m.message).collect();
+
+    let suppressions = list_suppressions(&app_state.db_pool)
+        .await
+        .map_err(e500)?
+        .into_iter()
+        .map(SuppressionRow::from)
+        .collect();
+
+    Ok(SuppressionsPage {
+        page_title: "Suppression List",
+        description: "Nothing is sent to suppressed addresses and domains. \
+            Skipped sends are counted below.",
+        entry_label: "Email or domain",
+        kind_label: "Kind",
+        reason_label: "Reason",
+        source_label: "Source",
+        created_at_label: "Added at",
+        skipped_sends_label: "Skipped sends",
+        last_skipped_at_label: "Last skipped at",
+        never_label: "never",
+        remove_button: "Remove",
+        new_entry_label: "Suppress an address or domain",
+        add_button: "Suppress",
+        import_label: "Import from CSV",
+        import_description: "The first row names the columns: entry and, optionally, reason.",
+        csv_label: "CSV",
+        default_reason_label: "Reason for rows without one",
+        import_button: "Import",
+        back_link: "Back",
+        suppressions,
+        flashes,
+    })
+}
+
+#[derive(Template)]
+#[template(path = "web/suppressions.html")]
+pub(in crate::routes::admin) struct SuppressionsPage<'a> {
+    page_title: &'a str,
+    description: &'a str,
+    entry_label: &'a str,
+    kind_label: &'a str,
+    reason_label: &'a str,
+    source_label: &'a str,
+    created_at_label: &'a str,
+    skipped_sends_label: &'a str,
+    last_skipped_at_label: &'a str,
+    never_label: &'a str,
+    remove_button: &'a str,
+    new_entry_label: &'a str,
+    add_button: &'a str,
+    import_label: &'a str,
+    import_description: &'a str,
+    csv_label: &'a str,
+    default_reason_label: &'a str,
+    import_button: &'a str,
+    back_link: &'a str,
+    suppressions: Vec<SuppressionRow>,
+    flashes: Vec<String>,
+}
+
+struct SuppressionRow {
+    suppression_id: String,
+    kind: String,
+    value: String,
+    reason: String,
+    source: String,
+    created_at: String,
+    skipped_sends: i32,
+    last_skipped_at: Option<String>,
+}
+
+impl From<Suppression> for SuppressionRow {
+    fn from(suppression: Suppression) -> Self {
+        Self {
+            suppression_id: suppression.suppression_id.to_string(),
+            kind: suppression.kind,
+            value: suppression.value,
+            reason: suppression.reason,
+            source: suppression.source,
+            created_at: format_timestamp(suppression.created_at),
+            skipped_sends: suppression.skipped_sends,
+            last_skipped_at: suppression.last_skipped_at.map(format_timestamp),
+        }
+    }
+}
+
+fn format_timestamp(timestamp: OffsetDateTime) -> String {
+    timestamp
+        .format(&Rfc3339)
+        .unwrap_or_else(|_| timestamp.to_string())
+}
@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub(super) use get::suppressions_page;
+pub(super) use post::{add_suppression_entry, import_suppressions, remove_suppression_entry};
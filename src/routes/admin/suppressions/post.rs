@@ -0,0 +1,134 @@
+use crate::{
+    app_state::AppState,
+    suppression_list::{
+        add_suppression, parse_csv, remove_suppression, SuppressionEntry, SuppressionSource,
+    },
+    utils::{e500, HttpError},
+};
+use anyhow::Context;
+use axum::{extract::State, response::Redirect, Form};
+use axum_messages::Messages;
+use serde::Deserialize;
+use uuid::Uuid;
+
+#[tracing::instrument(skip_all)]
+pub(in crate::routes::admin) async fn add_suppression_entry(
+    State(app_state): State<AppState>,
+    messages: Messages,
+    Form(form): Form<AddFormData>,
+) -> Result<Redirect, HttpError<anyhow::Error>> {
+    let reason = form.reason.trim();
+    if reason.is_empty() {
+        messages.error("The reason must not be empty.");
+        return Ok(Redirect::to("/admin/suppressions"));
+    }
+
+    let entry = match SuppressionEntry::parse(&form.entry) {
+        Ok(entry) => entry,
+        Err(e) => {
+            messages.error(e);
+            return Ok(Redirect::to("/admin/suppressions"));
+        }
+    };
+
+    let added = add_suppression(&app_state.db_pool, &entry, reason, SuppressionSource::Admin)
+        .await
+        .map_err(e500)?;
+
+    if added {
+        messages.info(format!("{} has been suppressed.", entry.value()));
+    } else {
+        messages.error(format!("{} is suppressed already.", entry.value()));
+    }
+
+    Ok(Redirect::to("/admin/suppressions"))
+}
+
+/// Imports all rows or none of them, so that a fixed CSV can be imported again.
+#[tracing::instrument(skip_all)]
+pub(in crate::routes::admin) async fn import_suppressions(
+    State(app_state): State<AppState>,
+    messages: Messages,
+    Form(form): Form<ImportFormData>,
+) -> Result<Redirect, HttpError<anyhow::Error>> {
+    let default_reason = form.default_reason.trim();
+    if default_reason.is_empty() {
+        messages.error("The reason for rows without one must not be empty.");
+        return Ok(Redirect::to("/admin/suppressions"));
+    }
+
+    let entries = match parse_csv(&form.csv, default_reason) {
+        Ok(entries) => entries,
+        Err(e) => {
+            messages.error(format!("Nothing has been imported. {e}"));
+            return Ok(Redirect::to("/admin/suppressions"));
+        }
+    };
+
+    let mut transaction = app_state
+        .db_pool
+        .begin()
+        .await
+        .context("Failed to begin transaction")
+        .map_err(e500)?;
+
+    let mut added = 0;
+    for (entry, reason) in &entries {
+        if add_suppression(
+            &mut *transaction,
+            entry,
+            reason,
+            SuppressionSource::CsvImport,
+        )
+        .await
+        .map_err(e500)?
+        {
+            added += 1;
+        }
+    }
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit transaction")
+        .map_err(e500)?;
+
+    messages.info(format!(
+        "{added} entries have been imported, {} were suppressed already.",
+        entries.len() - added
+    ));
+
+    Ok(Redirect::to("/admin/suppressions"))
+}
+
+#[tracing::instrument(skip_all, fields(suppression_id=%form.suppression_id))]
+pub(in crate::routes::admin) async fn remove_suppression_entry(
+    State(app_state): State<AppState>,
+    messages: Messages,
+    Form(form): Form<RemoveFormData>,
+) -> Result<Redirect, HttpError<anyhow::Error>> {
+    remove_suppression(&app_state.db_pool, form.suppression_id)
+        .await
+        .map_err(e500)?;
+
+    messages.info("The suppression has been removed.");
+
+    Ok(Redirect::to("/admin/suppressions"))
+}
+
+#[derive(Deserialize)]
+pub(in crate::routes::admin) struct AddFormData {
+    entry: String,
+    reason: String,
+}
+
+#[derive(Deserialize)]
+pub(in crate::routes::admin) struct ImportFormData {
+    csv: String,
+    default_reason: String,
+}
+
+#[derive(Deserialize)]
+pub(in crate::routes::admin) struct RemoveFormData {
+    suppression_id: Uuid,
+}
@@ -0,0 +1,97 @@
+use crate::{
+    app_state::AppState,
+    authentication::{
+        api_tokens::{list_api_tokens, ApiScope, ApiToken},
+        extract::SessionUserId,
+    },
+    utils::{e500, HttpError},
+};
+use askama_axum::Template;
+use axum::extract::State;
+use axum_messages::Messages;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+#[tracing::instrument(name = "Get API tokens page", skip_all, fields(user_id=%user_id))]
+pub(in crate::routes::admin) async fn tokens_page(
+    State(app_state): State<AppState>,
+    SessionUserId(user_id): SessionUserId,
+    messages: Messages,
+) -> Result<TokensPage<'static>, HttpError<anyhow::Error>> {
+    let flashes = messages.map(|m| m.message).collect();
+
+    let tokens = list_api_tokens(&app_state.db_pool, user_id)
+        .await
+        .map_err(e500)?
+        .into_iter()
+        .map(TokenRow::from)
+        .collect();
+
+    Ok(TokensPage {
+        page_title: "API Tokens",
+        name_label: "Name",
+        token_label: "Token",
+        scopes_label: "Scopes",
+        created_at_label: "Created at",
+        last_used_at_label: "Last used at",
+        never_label: "never",
+        revoke_button: "Revoke",
+        new_token_label: "New token",
+        create_button: "Create token",
+        back_link: "Back",
+        available_scopes: ApiScope::ALL.iter().map(ApiScope::as_str).collect(),
+        tokens,
+        flashes,
+    })
+}
+
+#[derive(Template)]
+#[template(path = "web/tokens.html")]
+pub(in crate::routes::admin) struct TokensPage<'a> {
+    page_title: &'a str,
+    name_label: &'a str,
+    token_label: &'a str,
+    scopes_label: &'a str,
+    created_at_label: &'a str,
+    last_used_at_label: &'a str,
+    never_label: &'a str,
+    revoke_button: &'a str,
+    new_token_label: &'a str,
+    create_button: &'a str,
+    back_link: &'a str,
+    available_scopes: Vec<&'a str>,
+    tokens: Vec<TokenRow>,
+    flashes: Vec<String>,
+}
+
+struct TokenRow {
+    api_token_id: String,
+    name: String,
+    token_prefix: String,
+    scopes: String,
+    created_at: String,
+    last_used_at: Option<String>,
+}
+
+impl From<ApiToken> for TokenRow {
+    fn from(token: ApiToken) -> Self {
+        Self {
+            api_token_id: token.api_token_id.to_string(),
+            name: token.name,
+            token_prefix: token.token_prefix,
+            scopes: token
+                .scopes
+                .iter()
+                .map(ApiScope::as_str)
+                .collect::<Vec<_>>()
+                .join(", "),
+            created_at: format_timestamp(token.created_at),
+            last_used_at: token.last_used_at.map(format_timestamp),
+        }
+    }
+}
+
+fn format_timestamp(timestamp: OffsetDateTime) -> String {
+    timestamp
+        .format(&Rfc3339)
+        .unwrap_or_else(|_| timestamp.to_string())
+}
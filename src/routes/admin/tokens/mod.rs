@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub(super) use get::tokens_page;
+pub(super) use post::{create_token, revoke_token};
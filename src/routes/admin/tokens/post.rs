@@ -0,0 +1,96 @@
+use crate::{
+    app_state::AppState,
+    authentication::{
+        api_tokens::{create_api_token, revoke_api_token, ApiScope},
+        extract::SessionUserId,
+    },
+    utils::{e500, HttpError},
+};
+use axum::{extract::State, response::Redirect, Form};
+use axum_messages::Messages;
+use secrecy::ExposeSecret;
+use serde::Deserialize;
+use uuid::Uuid;
+
+#[tracing::instrument(skip_all, fields(user_id=%user_id))]
+pub(in crate::routes::admin) async fn create_token(
+    State(app_state): State<AppState>,
+    SessionUserId(user_id): SessionUserId,
+    messages: Messages,
+    Form(form): Form<CreateFormData>,
+) -> Result<Redirect, HttpError<anyhow::Error>> {
+    let name = form.name.trim();
+    if name.is_empty() {
+        messages.error("The token name must not be empty.");
+        return Ok(Redirect::to("/admin/tokens"));
+    }
+
+    let scopes = form.scopes();
+    if scopes.is_empty() {
+        messages.error("Select at least one scope for the token.");
+        return Ok(Redirect::to("/admin/tokens"));
+    }
+
+    let token = create_api_token(&app_state.db_pool, user_id, name, &scopes)
+        .await
+        .map_err(e500)?;
+
+    messages.info(format!(
+        "Your new token is {} - copy it now, it will not be shown again.",
+        token.expose_secret()
+    ));
+
+    Ok(Redirect::to("/admin/tokens"))
+}
+
+#[tracing::instrument(skip_all, fields(user_id=%user_id))]
+pub(in crate::routes::admin) async fn revoke_token(
+    State(app_state): State<AppState>,
+    SessionUserId(user_id): SessionUserId,
+    messages: Messages,
+    Form(form): Form<RevokeFormData>,
+) -> Result<Redirect, HttpError<anyhow::Error>> {
+    let revoked = revoke_api_token(&app_state.db_pool, user_id, form.api_token_id)
+        .await
+        .map_err(e500)?;
+
+    if revoked {
+        messages.info("The token has been revoked.");
+    } else {
+        messages.error("The token does not exist or has already been revoked.");
+    }
+
+    Ok(Redirect::to("/admin/tokens"))
+}
+
+#[derive(Deserialize)]
+pub(in crate::routes::admin) struct CreateFormData {
+    name: String,
+    #[serde(rename = "issues:read", default)]
+    issues_read: bool,
+    #[serde(rename = "issues:write", default)]
+    issues_write: bool,
+    #[serde(rename = "subscribers:read", default)]
+    subscribers_read: bool,
+    #[serde(rename = "subscribers:write", default)]
+    subscribers_write: bool,
+}
+
+impl CreateFormData {
+    fn scopes(&self) -> Vec<ApiScope> {
+        [
+            (self.issues_read, ApiScope::IssuesRead),
+            (self.issues_write, ApiScope::IssuesWrite),
+            (self.subscribers_read, ApiScope::SubscribersRead),
+            (self.subscribers_write, ApiScope::SubscribersWrite),
+        ]
+        .into_iter()
+        .filter_map(|(selected, scope)| selected.then_some(scope))
+        .collect()
+    }
+}
+
+#[derive(Deserialize)]
+pub(in crate::routes::admin) struct RevokeFormData {
+    api_token_id: Uuid,
+}
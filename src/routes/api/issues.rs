@@ -0,0 +1,307 @@
+use super::{
+    problem::{ApiError, Problem},
+    require_scope,
+};
+use crate::{
+    app_state::AppState,
+    audit::{record_audit_event, AuditContext, AuditEventType},
+    authentication::{
+        api_tokens::ApiScope,
+        extract::{GrantedScopes, SessionUserId},
+    },
+    delivery_queue::{delivery_stats, DeliveryStats},
+    idempotency::{save_response, try_processing, IdempotencyKey, NextAction},
+    routes::admin::{enqueue_delivery_tasks, insert_newsletter_issue},
+};
+use anyhow::Context;
+use axum::{
+    extract::{
+        rejection::{JsonRejection, PathRejection},
+        Path, State,
+    },
+    http::{header::LOCATION, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+#[utoipa::path(
+    get,
+    path = "/issues",
+    tag = "issues",
+    responses(
+        (status = 200, description = "Published issues, newest first", body = IssueList),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Missing scope", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer_token" = ["issues:read"])),
+)]
+#[tracing::instrument(name = "List issues", skip_all)]
+pub(super) async fn list_issues(
+    State(app_state): State<AppState>,
+    scopes: GrantedScopes,
+) -> Result<Json<IssueList>, ApiError> {
+    require_scope(&scopes, ApiScope::IssuesRead)?;
+
+    let issues = sqlx::query_as!(
+        IssueSummary,
+        r#"
+        SELECT
+            newsletter_issue_id AS issue_id,
+            title,
+            published_at
+        FROM newsletter_issues
+        ORDER BY published_at DESC
+        "#,
+    )
+    .fetch_all(&app_state.db_pool)
+    .await
+    .context("Failed to retrieve newsletter issues")?;
+
+    Ok(Json(IssueList { issues }))
+}
+
+/// Publishes a new issue. Requires an `Idempotency-Key` header, so that
+/// clients can safely retry the request without sending the issue twice.
+#[utoipa::path(
+    post,
+    path = "/issues",
+    tag = "issues",
+    params(
+        ("Idempotency-Key" = String, Header, description = "Unique key of the request, shorter than 50 characters"),
+    ),
+    request_body = NewIssue,
+    responses(
+        (status = 201, description = "The issue has been published", body = Issue),
+        (status = 400, description = "Invalid request", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Missing scope", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer_token" = ["issues:write"])),
+)]
+#[tracing::instrument(name = "Create issue", skip_all, fields(user_id=%user_id))]
+pub(super) async fn create_issue(
+    State(app_state): State<AppState>,
+    SessionUserId(user_id): SessionUserId,
+    scopes: GrantedScopes,
+    audit_context: AuditContext,
+    headers: HeaderMap,
+    payload: Result<Json<NewIssue>, JsonRejection>,
+) -> Result<Response, ApiError> {
+    require_scope(&scopes, ApiScope::IssuesWrite)?;
+
+    let idempotency_key = idempotency_key(&headers)?;
+    let Json(new_issue) = payload?;
+    new_issue.validate()?;
+
+    let mut transaction =
+        match try_processing(&app_state.db_pool, &idempotency_key, user_id).await? {
+            NextAction::StartProcessing(transaction) => transaction,
+            NextAction::ReturnSavedResponse(saved_response) => return Ok(saved_response),
+        };
+
+    let issue_id = insert_newsletter_issue(
+        &mut transaction,
+        &new_issue.title,
+        &new_issue.text_content,
+        &new_issue.html_content,
+        new_issue.track_opens && app_state.tracking_enabled,
+        new_issue.track_clicks && app_state.tracking_enabled,
+    )
+    .await
+    .context("Failed to store newsletter issue details")?;
+
+    enqueue_delivery_tasks(&mut transaction, issue_id)
+        .await
+        .context("Failed to enqueue delivery tasks")?;
+
+    record_audit_event(
+        &mut *transaction,
+        &audit_context,
+        Some(user_id),
+        AuditEventType::NewsletterPublished,
+        json!({ "newsletter_issue_id": issue_id, "title": &new_issue.title }),
+    )
+    .await?;
+
+    let issue = fetch_issue(&mut *transaction, issue_id)
+        .await?
+        .context("Stored newsletter issue not found")?;
+
+    let response = (
+        StatusCode::CREATED,
+        [(LOCATION, format!("/api/v1/issues/{issue_id}"))],
+        Json(issue),
+    )
+        .into_response();
+    let response = save_response(transaction, &idempotency_key, user_id, response).await?;
+
+    Ok(response)
+}
+
+#[utoipa::path(
+    get,
+    path = "/issues/{issue_id}",
+    tag = "issues",
+    params(("issue_id" = Uuid, Path, description = "Id of the issue")),
+    responses(
+        (status = 200, description = "The issue", body = Issue),
+        (status = 400, description = "Invalid request", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Missing scope", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Issue not found", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer_token" = ["issues:read"])),
+)]
+#[tracing::instrument(name = "Get issue", skip_all)]
+pub(super) async fn get_issue(
+    State(app_state): State<AppState>,
+    scopes: GrantedScopes,
+    issue_id: Result<Path<Uuid>, PathRejection>,
+) -> Result<Json<Issue>, ApiError> {
+    require_scope(&scopes, ApiScope::IssuesRead)?;
+    let Path(issue_id) = issue_id?;
+
+    fetch_issue(&app_state.db_pool, issue_id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| issue_not_found(issue_id))
+}
+
+#[utoipa::path(
+    get,
+    path = "/issues/{issue_id}/stats",
+    tag = "issues",
+    params(("issue_id" = Uuid, Path, description = "Id of the issue")),
+    responses(
+        (status = 200, description = "Delivery stats of the issue", body = DeliveryStats),
+        (status = 400, description = "Invalid request", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Missing scope", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Issue not found", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer_token" = ["issues:read"])),
+)]
+#[tracing::instrument(name = "Get issue delivery stats", skip_all)]
+pub(super) async fn get_issue_stats(
+    State(app_state): State<AppState>,
+    scopes: GrantedScopes,
+    issue_id: Result<Path<Uuid>, PathRejection>,
+) -> Result<Json<DeliveryStats>, ApiError> {
+    require_scope(&scopes, ApiScope::IssuesRead)?;
+    let Path(issue_id) = issue_id?;
+
+    let stats = delivery_stats(&app_state.db_pool, issue_id)
+        .await?
+        .ok_or_else(|| issue_not_found(issue_id))?;
+
+    Ok(Json(stats))
+}
+
+fn idempotency_key(headers: &HeaderMap) -> Result<IdempotencyKey, ApiError> {
+    let value = headers
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .ok_or_else(|| {
+            ApiError::BadRequest(format!("The `{IDEMPOTENCY_KEY_HEADER}` header is missing"))
+        })?
+        .to_str()
+        .map_err(|_| {
+            ApiError::BadRequest(format!(
+                "The `{IDEMPOTENCY_KEY_HEADER}` header must be valid ASCII"
+            ))
+        })?;
+
+    value
+        .to_string()
+        .try_into()
+        .map_err(|e: anyhow::Error| ApiError::BadRequest(e.to_string()))
+}
+
+fn issue_not_found(issue_id: Uuid) -> ApiError {
+    ApiError::NotFound(format!("Issue `{issue_id}` does not exist"))
+}
+
+#[tracing::instrument(skip(executor))]
+async fn fetch_issue<'e>(
+    executor: impl sqlx::PgExecutor<'e>,
+    issue_id: Uuid,
+) -> Result<Option<Issue>, anyhow::Error> {
+    sqlx::query_as!(
+        Issue,
+        r#"
+        SELECT
+            newsletter_issue_id AS issue_id,
+            title,
+            text_content,
+            html_content,
+            track_opens,
+            track_clicks,
+            published_at
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        "#,
+        issue_id,
+    )
+    .fetch_optional(executor)
+    .await
+    .context("Failed to retrieve newsletter issue")
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(super) struct NewIssue {
+    title: String,
+    text_content: String,
+    html_content: String,
+    /// Ignored when tracking is disabled globally.
+    #[serde(default)]
+    track_opens: bool,
+    /// Ignored when tracking is disabled globally.
+    #[serde(default)]
+    track_clicks: bool,
+}
+
+impl NewIssue {
+    fn validate(&self) -> Result<(), ApiError> {
+        for (field, value) in [
+            ("title", &self.title),
+            ("text_content", &self.text_content),
+            ("html_content", &self.html_content),
+        ] {
+            if value.trim().is_empty() {
+                return Err(ApiError::BadRequest(format!(
+                    "The `{field}` field must not be empty"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub(super) struct IssueList {
+    issues: Vec<IssueSummary>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(super) struct IssueSummary {
+    issue_id: Uuid,
+    title: String,
+    published_at: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(super) struct Issue {
+    issue_id: Uuid,
+    title: String,
+    text_content: String,
+    html_content: String,
+    track_opens: bool,
+    track_clicks: bool,
+    published_at: String,
+}
@@ -0,0 +1,50 @@
+use super::problem::ApiError;
+use crate::{
+    app_state::AppState,
+    authentication::{
+        api_tokens::ApiScope,
+        extract::{GrantedScopes, SessionUserId},
+    },
+    routes::admin::get_username,
+};
+use anyhow::Context;
+use axum::{extract::State, Json};
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Describes the owner of the presented token, so that clients can check
+/// their credentials before doing anything else.
+#[utoipa::path(
+    get,
+    path = "/me",
+    tag = "tokens",
+    responses(
+        (status = 200, description = "Owner and scopes of the token", body = CurrentUser),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+    security(("bearer_token" = [])),
+)]
+#[tracing::instrument(name = "Get current API user", skip_all, fields(user_id=%user_id))]
+pub(super) async fn current_user(
+    State(app_state): State<AppState>,
+    SessionUserId(user_id): SessionUserId,
+    GrantedScopes(scopes): GrantedScopes,
+) -> Result<Json<CurrentUser>, ApiError> {
+    let username = get_username(&app_state.db_pool, user_id)
+        .await
+        .context("Failed to retrieve the username of the token owner")?;
+
+    Ok(Json(CurrentUser {
+        user_id,
+        username,
+        scopes: scopes.iter().map(ApiScope::as_str).collect(),
+    }))
+}
+
+#[derive(Serialize, ToSchema)]
+pub(super) struct CurrentUser {
+    user_id: Uuid,
+    username: String,
+    scopes: Vec<&'static str>,
+}
@@ -0,0 +1,92 @@
+use crate::{
+    app_state::AppState,
+    authentication::{api_tokens::ApiScope, extract::GrantedScopes, middleware::BearerTokenLayer},
+};
+use axum::{routing::get, Json, Router};
+use problem::ApiError;
+use sqlx::PgPool;
+use utoipa::{
+    openapi::{
+        security::{Http, HttpAuthScheme, SecurityScheme},
+        OpenApi as OpenApiDocument,
+    },
+    Modify, OpenApi,
+};
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+mod issues;
+mod me;
+mod problem;
+mod subscribers;
+
+pub const OPENAPI_PATH: &str = "/api/openapi.json";
+const BEARER_TOKEN: &str = "bearer_token";
+
+pub fn router(db_pool: PgPool) -> Router<AppState> {
+    let (api, _) = api_router().split_for_parts();
+
+    api.layer(BearerTokenLayer::new(db_pool))
+        .route(OPENAPI_PATH, get(openapi_document))
+}
+
+/// The OpenAPI document of `/api/v1`. Routes and their documentation are
+/// registered together in `api_router`, so that one cannot change without
+/// the other.
+pub fn openapi() -> OpenApiDocument {
+    let (_, document) = api_router().split_for_parts();
+    document
+}
+
+fn api_router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::with_openapi(ApiDoc::openapi()).nest(
+        "/api/v1",
+        OpenApiRouter::new()
+            .routes(routes!(me::current_user))
+            .routes(routes!(issues::list_issues, issues::create_issue))
+            .routes(routes!(issues::get_issue))
+            .routes(routes!(issues::get_issue_stats))
+            .routes(routes!(
+                subscribers::list_subscribers,
+                subscribers::add_subscriber
+            ))
+            .routes(routes!(subscribers::remove_subscriber))
+            .routes(routes!(subscribers::change_subscriber_email)),
+    )
+}
+
+#[tracing::instrument(name = "Get OpenAPI document")]
+async fn openapi_document() -> Json<OpenApiDocument> {
+    Json(openapi())
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "zero2prod", description = "Newsletter administration API"),
+    modifiers(&BearerTokenSecurity),
+    tags(
+        (name = "issues", description = "Newsletter issues and their delivery"),
+        (name = "subscribers", description = "Newsletter subscribers"),
+        (name = "tokens", description = "API token introspection"),
+    )
+)]
+struct ApiDoc;
+
+struct BearerTokenSecurity;
+
+impl Modify for BearerTokenSecurity {
+    fn modify(&self, openapi: &mut OpenApiDocument) {
+        openapi
+            .components
+            .get_or_insert_with(Default::default)
+            .add_security_scheme(
+                BEARER_TOKEN,
+                SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+            );
+    }
+}
+
+fn require_scope(scopes: &GrantedScopes, scope: ApiScope) -> Result<(), ApiError> {
+    scopes
+        .require(scope)
+        .map_err(|_| ApiError::MissingScope(scope))
+}
@@ -0,0 +1,327 @@
+use super::{
+    problem::{ApiError, Problem},
+    require_scope,
+};
+use crate::{
+    app_state::AppState,
+    audit::{record_audit_event, AuditContext, AuditEventType},
+    authentication::{
+        api_tokens::ApiScope,
+        extract::{GrantedScopes, SessionUserId},
+    },
+    domain::{
+        NewSubscriber, SubscriberEmail, SubscriberName, SubscriptionStatus, SubscriptionToken,
+    },
+    routes::{
+        subscriptions::{
+            delete_subscriber, get_subscription, insert_subscriber, send_confirmation_email,
+            store_token,
+        },
+        subscriptions_email::{request_email_change, EmailChangeError},
+    },
+};
+use anyhow::Context;
+use axum::{
+    extract::{
+        rejection::{JsonRejection, PathRejection},
+        Path, State,
+    },
+    http::{header::LOCATION, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[utoipa::path(
+    get,
+    path = "/subscribers",
+    tag = "subscribers",
+    responses(
+        (status = 200, description = "All subscribers, oldest first", body = SubscriberList),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Missing scope", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer_token" = ["subscribers:read"])),
+)]
+#[tracing::instrument(name = "List subscribers", skip_all)]
+pub(super) async fn list_subscribers(
+    State(app_state): State<AppState>,
+    scopes: GrantedScopes,
+) -> Result<Json<SubscriberList>, ApiError> {
+    require_scope(&scopes, ApiScope::SubscribersRead)?;
+
+    let subscribers = sqlx::query!(
+        r#"
+        SELECT id, email, name, status, subscribed_at
+        FROM subscriptions
+        ORDER BY subscribed_at
+        "#,
+    )
+    .fetch_all(&app_state.db_pool)
+    .await
+    .context("Failed to retrieve subscribers")?
+    .into_iter()
+    .map(|row| Subscriber {
+        subscriber_id: row.id,
+        email: row.email,
+        name: row.name,
+        status: row.status,
+        subscribed_at: format_timestamp(row.subscribed_at),
+    })
+    .collect();
+
+    Ok(Json(SubscriberList { subscribers }))
+}
+
+/// Adds a subscriber on their behalf. They still have to confirm
+/// the subscription with the link from the welcome email.
+#[utoipa::path(
+    post,
+    path = "/subscribers",
+    tag = "subscribers",
+    request_body = NewSubscriberBody,
+    responses(
+        (status = 201, description = "The subscriber has been added", body = Subscriber),
+        (status = 400, description = "Invalid request", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Missing scope", body = Problem, content_type = "application/problem+json"),
+        (status = 409, description = "Subscriber already exists", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer_token" = ["subscribers:write"])),
+)]
+#[tracing::instrument(name = "Add subscriber", skip_all, fields(user_id=%user_id))]
+pub(super) async fn add_subscriber(
+    State(app_state): State<AppState>,
+    SessionUserId(user_id): SessionUserId,
+    scopes: GrantedScopes,
+    audit_context: AuditContext,
+    payload: Result<Json<NewSubscriberBody>, JsonRejection>,
+) -> Result<Response, ApiError> {
+    require_scope(&scopes, ApiScope::SubscribersWrite)?;
+
+    let Json(body) = payload?;
+    let mut new_subscriber: NewSubscriber = body.try_into().map_err(ApiError::BadRequest)?;
+    new_subscriber.email = new_subscriber.email.with_rules(&app_state.local_part_rules);
+
+    let mut transaction = app_state
+        .db_pool
+        .begin()
+        .await
+        .context("Failed to begin transaction")?;
+
+    if get_subscription(&mut transaction, &new_subscriber.email)
+        .await?
+        .is_some()
+    {
+        return Err(ApiError::Conflict(format!(
+            "Subscriber `{}` already exists",
+            new_subscriber.email
+        )));
+    }
+
+    let subscriber_id = insert_subscriber(&mut transaction, &new_subscriber).await?;
+    let subscription_token = SubscriptionToken::generate();
+    store_token(
+        &mut transaction,
+        subscriber_id,
+        &subscription_token,
+        &app_state.token_secret,
+    )
+    .await?;
+
+    record_audit_event(
+        &mut *transaction,
+        &audit_context,
+        Some(user_id),
+        AuditEventType::SubscriberAdded,
+        json!({ "subscriber_id": subscriber_id, "email": new_subscriber.email.as_ref() }),
+    )
+    .await?;
+
+    let subscriber = Subscriber {
+        subscriber_id,
+        email: new_subscriber.email.to_string(),
+        name: new_subscriber.name.as_ref().to_string(),
+        status: SubscriptionStatus::PendingConfirmation.as_ref().to_string(),
+        subscribed_at: format_timestamp(OffsetDateTime::now_utc()),
+    };
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit transaction")?;
+
+    send_confirmation_email(
+        &app_state.db_pool,
+        &app_state.email_client,
+        new_subscriber,
+        &app_state.base_url,
+        &subscription_token,
+    )
+    .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        [(LOCATION, format!("/api/v1/subscribers/{subscriber_id}"))],
+        Json(subscriber),
+    )
+        .into_response())
+}
+
+#[utoipa::path(
+    delete,
+    path = "/subscribers/{subscriber_id}",
+    tag = "subscribers",
+    params(("subscriber_id" = Uuid, Path, description = "Id of the subscriber")),
+    responses(
+        (status = 204, description = "The subscriber has been removed"),
+        (status = 400, description = "Invalid request", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Missing scope", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Subscriber not found", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer_token" = ["subscribers:write"])),
+)]
+#[tracing::instrument(name = "Remove subscriber", skip_all, fields(user_id=%user_id))]
+pub(super) async fn remove_subscriber(
+    State(app_state): State<AppState>,
+    SessionUserId(user_id): SessionUserId,
+    scopes: GrantedScopes,
+    audit_context: AuditContext,
+    subscriber_id: Result<Path<Uuid>, PathRejection>,
+) -> Result<StatusCode, ApiError> {
+    require_scope(&scopes, ApiScope::SubscribersWrite)?;
+    let Path(subscriber_id) = subscriber_id?;
+
+    let mut transaction = app_state
+        .db_pool
+        .begin()
+        .await
+        .context("Failed to begin transaction")?;
+
+    let Some(email) = delete_subscriber(&mut transaction, subscriber_id).await? else {
+        return Err(ApiError::NotFound(format!(
+            "Subscriber `{subscriber_id}` does not exist"
+        )));
+    };
+
+    record_audit_event(
+        &mut *transaction,
+        &audit_context,
+        Some(user_id),
+        AuditEventType::SubscriberRemoved,
+        json!({ "subscriber_id": subscriber_id, "email": email }),
+    )
+    .await?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit transaction")?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Sends a link confirming the new address to it. The address of the
+/// subscriber changes only once the link is followed.
+#[utoipa::path(
+    post,
+    path = "/subscribers/{subscriber_id}/email",
+    tag = "subscribers",
+    params(("subscriber_id" = Uuid, Path, description = "Id of the subscriber")),
+    request_body = EmailChangeBody,
+    responses(
+        (status = 202, description = "The confirmation link has been sent to the new address"),
+        (status = 400, description = "Invalid request", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Missing scope", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Subscriber not found", body = Problem, content_type = "application/problem+json"),
+        (status = 409, description = "The new address is already subscribed", body = Problem, content_type = "application/problem+json"),
+        (status = 429, description = "Too many email changes requested", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer_token" = ["subscribers:write"])),
+)]
+#[tracing::instrument(name = "Change subscriber email", skip_all, fields(user_id=%user_id))]
+pub(super) async fn change_subscriber_email(
+    State(app_state): State<AppState>,
+    SessionUserId(user_id): SessionUserId,
+    scopes: GrantedScopes,
+    audit_context: AuditContext,
+    subscriber_id: Result<Path<Uuid>, PathRejection>,
+    payload: Result<Json<EmailChangeBody>, JsonRejection>,
+) -> Result<StatusCode, ApiError> {
+    require_scope(&scopes, ApiScope::SubscribersWrite)?;
+    let Path(subscriber_id) = subscriber_id?;
+    let Json(body) = payload?;
+
+    let new_email = request_email_change(
+        &app_state,
+        audit_context.ip_address.as_deref(),
+        subscriber_id,
+        body.email,
+    )
+    .await
+    .map_err(|e| match e {
+        EmailChangeError::ValidationError(_) => ApiError::BadRequest(e.to_string()),
+        EmailChangeError::UnknownSubscriber(_) => ApiError::NotFound(e.to_string()),
+        EmailChangeError::AddressTaken(_) => ApiError::Conflict(e.to_string()),
+        EmailChangeError::Rejected(_) => ApiError::TooManyRequests(e.to_string()),
+        EmailChangeError::UnexpectedError(e) => ApiError::UnexpectedError(e),
+    })?;
+
+    record_audit_event(
+        &app_state.db_pool,
+        &audit_context,
+        Some(user_id),
+        AuditEventType::SubscriberEmailChangeRequested,
+        json!({ "subscriber_id": subscriber_id, "new_email": new_email.as_ref() }),
+    )
+    .await?;
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+fn format_timestamp(timestamp: OffsetDateTime) -> String {
+    timestamp
+        .format(&Rfc3339)
+        .unwrap_or_else(|_| timestamp.to_string())
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(super) struct NewSubscriberBody {
+    email: String,
+    name: String,
+}
+
+impl TryFrom<NewSubscriberBody> for NewSubscriber {
+    type Error = String;
+
+    fn try_from(value: NewSubscriberBody) -> Result<Self, Self::Error> {
+        let email = SubscriberEmail::parse(value.email)?;
+        let name = SubscriberName::parse(value.name)?;
+        Ok(NewSubscriber { email, name })
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(super) struct EmailChangeBody {
+    email: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(super) struct SubscriberList {
+    subscribers: Vec<Subscriber>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(super) struct Subscriber {
+    subscriber_id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: String,
+}
@@ -1,10 +1,172 @@
 use crate::app_state::AppState;
-use axum::{http::StatusCode, routing::get, Router};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+use tower_sessions_redis_store::fred::{error::RedisError, interfaces::ClientLike};
 
 pub fn router() -> Router<AppState> {
-    Router::new().route("/health_check", get(health_check))
+    Router::new()
+        .route("/health_check", get(health_check))
+        .route("/health/live", get(liveness))
+        .route("/health/ready", get(readiness))
 }
 
+/// Kept for monitors set up before the liveness and readiness checks.
 async fn health_check() -> StatusCode {
     StatusCode::OK
 }
+
+/// Tells whether the process is able to serve requests at all, without
+/// looking at its dependencies, whose outages restarting would not fix.
+async fn liveness() -> Json<Health> {
+    Json(Health {
+        status: Status::Up,
+        checks: BTreeMap::new(),
+    })
+}
+
+/// Tells whether the dependencies needed to serve requests are up. Responds
+/// with 503 Service Unavailable if any of them is not.
+///
+/// Meant for uptime monitors and for checking a deployment before sending it
+/// traffic, not for the platform's health check, which would restart the app
+/// whenever a dependency is down. The email provider is checked at most once
+/// per `email_provider_cache_seconds`, however often this is polled.
+#[tracing::instrument(name = "Check readiness", skip_all)]
+async fn readiness(State(app_state): State<AppState>) -> Response {
+    let timeout = app_state.health_check_timeout;
+
+    let database = check("database", timeout, async {
+        sqlx::query!("SELECT 1 AS one")
+            .execute(&app_state.db_pool)
+            .await
+            .map(|_| ())
+    });
+    let redis = check("redis", timeout, async {
+        let pong: Result<(), RedisError> = app_state.redis_pool.ping().await;
+        pong
+    });
+    let email_provider = async {
+        if app_state.check_email_provider {
+            Some(
+                app_state
+                    .email_provider_health
+                    .get_or_check(check(
+                        "email_provider",
+                        timeout,
+                        app_state.email_client.check_server(),
+                    ))
+                    .await,
+            )
+        } else {
+            None
+        }
+    };
+
+    let (database, redis, email_provider) = tokio::join!(database, redis, email_provider);
+    let checks: BTreeMap<_, _> = [Some(database), Some(redis), email_provider]
+        .into_iter()
+        .flatten()
+        .collect();
+
+    let (status_code, status) = if checks.values().all(|check| check.status == Status::Up) {
+        (StatusCode::OK, Status::Up)
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, Status::Down)
+    };
+
+    (status_code, Json(Health { status, checks })).into_response()
+}
+
+async fn check<E: Display>(
+    dependency: &'static str,
+    timeout: Duration,
+    check: impl Future<Output = Result<(), E>>,
+) -> DependencyCheck {
+    let started_at = Instant::now();
+    let result = tokio::time::timeout(timeout, check).await;
+    let latency_ms = started_at.elapsed().as_secs_f64() * 1000.0;
+
+    // Errors are logged rather than returned, as the endpoint is public.
+    let status = match result {
+        Ok(Ok(())) => Status::Up,
+        Ok(Err(e)) => {
+            tracing::warn!(dependency, error.message = %e, "Dependency check failed");
+            Status::Down
+        }
+        Err(_) => {
+            tracing::warn!(dependency, "Dependency check timed out");
+            Status::Down
+        }
+    };
+
+    (dependency, DependencyHealth { status, latency_ms })
+}
+
+/// Name of a dependency and how it is doing.
+type DependencyCheck = (&'static str, DependencyHealth);
+
+/// Outcome of a dependency check, reused until it is `ttl` old.
+#[derive(Clone)]
+pub struct CachedHealth {
+    ttl: Duration,
+    last: Arc<Mutex<Option<(Instant, DependencyCheck)>>>,
+}
+
+impl CachedHealth {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            last: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Runs the check unless a fresh enough outcome is at hand. Concurrent
+    /// callers wait for the same check rather than running their own.
+    async fn get_or_check(&self, check: impl Future<Output = DependencyCheck>) -> DependencyCheck {
+        let mut last = self.last.lock().await;
+
+        if let Some((checked_at, health)) = last.as_ref() {
+            if checked_at.elapsed() < self.ttl {
+                return health.clone();
+            }
+        }
+
+        let health = check.await;
+        *last = Some((Instant::now(), health.clone()));
+        health
+    }
+}
+
+#[derive(Serialize)]
+struct Health {
+    status: Status,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    checks: BTreeMap<&'static str, DependencyHealth>,
+}
+
+#[derive(Clone, Serialize)]
+struct DependencyHealth {
+    status: Status,
+    latency_ms: f64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Up,
+    Down,
+}
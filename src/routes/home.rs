@@ -1,16 +1,25 @@
-use crate::app_state::AppState;
+use crate::{app_state::AppState, security_headers::CspNonce};
 use askama_axum::Template;
-use axum::{routing::get, Router};
+use axum::{extract::State, routing::get, Router};
 
 pub fn router() -> Router<AppState> {
     Router::new().route("/", get(home))
 }
 
-#[tracing::instrument(name = "Render home page")]
-async fn home() -> HomeTemplate<'static> {
+#[tracing::instrument(name = "Render home page", skip_all)]
+async fn home(State(app_state): State<AppState>, csp_nonce: CspNonce) -> HomeTemplate<'static> {
+    let protection = &app_state.subscribe_protection;
+    let pow_challenge = protection.pow_challenge();
+
     HomeTemplate {
         page_title: "zero2prod",
         username: None,
+        form_token: protection.form_token(),
+        pow_difficulty_bits: pow_challenge
+            .as_ref()
+            .map_or(0, |challenge| challenge.difficulty_bits),
+        pow_challenge: pow_challenge.map(|challenge| challenge.challenge),
+        csp_nonce,
     }
 }
 
@@ -19,4 +28,8 @@ async fn home() -> HomeTemplate<'static> {
 struct HomeTemplate<'a> {
     page_title: &'a str,
     username: Option<String>,
+    form_token: String,
+    pow_challenge: Option<String>,
+    pow_difficulty_bits: u32,
+    csp_nonce: CspNonce,
 }
@@ -1,8 +1,14 @@
 use crate::{
     app_state::AppState,
-    authentication::password::{validate_credentials, AuthError, Credentials},
+    audit::{record_audit_event, AuditContext, AuditEventType},
+    authentication::{
+        password::{validate_credentials, AuthError, Credentials},
+        sessions::register_session,
+    },
+    client_info::ClientInfo,
     session_state::TypedSession,
 };
+use anyhow::anyhow;
 use axum::{
     extract::State,
     http::StatusCode,
@@ -12,21 +18,26 @@ use axum::{
 use axum_messages::Messages;
 use secrecy::Secret;
 use serde::Deserialize;
+use serde_json::json;
 
 #[tracing::instrument(
-    skip(app_state, session, messages, form),
+    skip(app_state, session, client_info, audit_context, messages, form),
     fields(username = tracing::field::Empty, user_id = tracing::field::Empty)
 )]
 pub(super) async fn login(
     State(app_state): State<AppState>,
     session: TypedSession,
+    client_info: ClientInfo,
+    audit_context: AuditContext,
     messages: Messages,
     Form(form): Form<FormData>,
 ) -> Result<Redirect, LoginErrorResponse> {
     tracing::Span::current().record("username", &tracing::field::display(&form.username));
+    let audit_payload = json!({ "username": &form.username });
 
     let user_id = match validate_credentials(
         &app_state.db_pool,
+        &app_state.password_hashing,
         Credentials {
             username: form.username,
             password: form.password,
@@ -37,6 +48,21 @@ pub(super) async fn login(
         Ok(user_id) => user_id,
         Err(e) => match e {
             AuthError::InvalidCredentials(_) => {
+                if let Err(audit_error) = record_audit_event(
+                    &app_state.db_pool,
+                    &audit_context,
+                    None,
+                    AuditEventType::LoginFailed,
+                    audit_payload,
+                )
+                .await
+                {
+                    return Err(LoginErrorResponse::new_unexpected_with_redirect(
+                        audit_error,
+                        messages,
+                    ));
+                }
+
                 return Err(LoginErrorResponse::new_auth_with_redirect(
                     e.into(),
                     messages,
@@ -56,10 +82,40 @@ pub(super) async fn login(
         ));
     }
 
-    session
-        .insert_user_id(user_id)
-        .await
-        .map_err(|e| LoginErrorResponse::new_unexpected_with_redirect(e, messages))?;
+    if let Err(e) = session.insert_user_id(user_id).await {
+        return Err(LoginErrorResponse::new_unexpected_with_redirect(
+            e, messages,
+        ));
+    }
+
+    if let Err(e) = session.save().await {
+        return Err(LoginErrorResponse::new_unexpected_with_redirect(
+            e, messages,
+        ));
+    }
+
+    let Some(session_id) = session.id() else {
+        return Err(LoginErrorResponse::new_unexpected_with_redirect(
+            anyhow!("Session id not found after saving the session"),
+            messages,
+        ));
+    };
+
+    if let Err(e) = register_session(&app_state.db_pool, user_id, &session_id, &client_info).await {
+        return Err(LoginErrorResponse::new_unexpected_with_redirect(
+            e, messages,
+        ));
+    }
+
+    record_audit_event(
+        &app_state.db_pool,
+        &audit_context,
+        Some(user_id),
+        AuditEventType::LoginSucceeded,
+        audit_payload,
+    )
+    .await
+    .map_err(|e| LoginErrorResponse::new_unexpected_with_redirect(e, messages))?;
 
     Ok(Redirect::to("/admin/dashboard"))
 }
@@ -1,6 +1,11 @@
 pub mod admin;
+pub mod api;
 pub mod health_check;
 pub mod home;
 pub mod login;
 pub mod subscriptions;
 pub mod subscriptions_confirm;
+pub mod subscriptions_email;
+pub mod subscriptions_preferences;
+pub mod tracking;
+pub mod webhooks;
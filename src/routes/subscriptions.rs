@@ -1,9 +1,12 @@
 use crate::{
     app_state::AppState,
+    client_info::ClientInfo,
     domain::{
         NewSubscriber, SubscriberEmail, SubscriberName, SubscriptionStatus, SubscriptionToken,
     },
     email_client::EmailClient,
+    subscribe_protection::{FormFields, ProtectionError, Rejection},
+    suppression_list::check_suppression,
 };
 use anyhow::Context;
 use askama::Template;
@@ -14,9 +17,9 @@ use axum::{
     routing::post,
     Form, Router,
 };
-use secrecy::ExposeSecret;
+use secrecy::{ExposeSecret, Secret};
 use serde::Deserialize;
-use sqlx::{Executor, FromRow, Postgres, Transaction};
+use sqlx::{Executor, FromRow, PgPool, Postgres, Transaction};
 use std::fmt::Debug;
 use time::OffsetDateTime;
 use uuid::Uuid;
@@ -27,7 +30,7 @@ pub fn router() -> Router<AppState> {
 
 #[tracing::instrument(
     name = "Add new subscriber",
-    skip(app_state, form),
+    skip(app_state, client_info, form),
     fields(
         subscriber_email = %form.email,
         subscriber_name = %form.name
@@ -35,9 +38,38 @@ pub fn router() -> Router<AppState> {
 )]
 async fn subscribe(
     State(app_state): State<AppState>,
+    client_info: ClientInfo,
     Form(form): Form<FormData>,
 ) -> Result<(), SubscribeError> {
-    let new_subscriber: NewSubscriber = form.try_into().map_err(SubscribeError::ValidationError)?;
+    let protection = &app_state.subscribe_protection;
+
+    let protection_fields = form.protection_fields();
+    match protection.check_form(&protection_fields) {
+        Ok(()) => {}
+        // Bots are not told that they have been caught.
+        Err(Rejection::Honeypot) => return Ok(()),
+        Err(rejection) => return Err(rejection.into()),
+    }
+    protection
+        .consume_form(&app_state.db_pool, &protection_fields)
+        .await?;
+
+    let mut new_subscriber: NewSubscriber =
+        form.try_into().map_err(SubscribeError::ValidationError)?;
+    new_subscriber.email = app_state
+        .email_verifier
+        .verify(&new_subscriber.email)
+        .await
+        .map_err(|e| SubscribeError::ValidationError(e.to_string()))?
+        .with_rules(&app_state.local_part_rules);
+
+    protection
+        .check_rate_limits(
+            &app_state.db_pool,
+            client_info.ip_address.as_deref(),
+            &new_subscriber.email,
+        )
+        .await?;
 
     let mut transaction = app_state
         .db_pool
@@ -46,18 +78,34 @@ async fn subscribe(
         .context("Failed to begin transaction")?;
 
     let subscriber_id = match get_subscription(&mut transaction, &new_subscriber.email).await? {
+        // People who have unsubscribed may subscribe again, confirming anew.
+        // Confirming also proves that a bounced address works again.
         Some(Subscription {
-            status: SubscriptionStatus::PendingConfirmation,
+            status:
+                SubscriptionStatus::PendingConfirmation
+                | SubscriptionStatus::Unsubscribed
+                | SubscriptionStatus::Bounced,
             id,
             ..
-        }) => id,
+        }) => {
+            protection
+                .check_confirmation_email_cap(&mut transaction, id)
+                .await?;
+            id
+        }
         Some(_) => return Err(SubscribeError::SubscriptionAlreadyConfirmed),
         None => insert_subscriber(&mut transaction, &new_subscriber).await?,
     };
 
     let subscription_token = SubscriptionToken::generate();
 
-    store_token(&mut transaction, subscriber_id, &subscription_token).await?;
+    store_token(
+        &mut transaction,
+        subscriber_id,
+        &subscription_token,
+        &app_state.token_secret,
+    )
+    .await?;
 
     transaction
         .commit()
@@ -65,6 +113,7 @@ async fn subscribe(
         .context("Failed to commit transaction")?;
 
     send_confirmation_email(
+        &app_state.db_pool,
         &app_state.email_client,
         new_subscriber,
         &app_state.base_url,
@@ -79,7 +128,7 @@ async fn subscribe(
     name = "Get subscriber details from the database",
     skip(transaction, email)
 )]
-async fn get_subscription(
+pub(crate) async fn get_subscription(
     transaction: &mut Transaction<'_, Postgres>,
     email: &SubscriberEmail,
 ) -> Result<Option<Subscription>, anyhow::Error> {
@@ -87,9 +136,9 @@ async fn get_subscription(
         r#"
         SELECT id, status
         FROM subscriptions
-        WHERE email = $1
+        WHERE canonical_email = $1
         "#,
-        email.as_ref()
+        email.canonical()
     );
 
     let subscription = match transaction
@@ -110,18 +159,19 @@ async fn get_subscription(
     name = "Save new subscriber details in the database",
     skip(transaction, new_subscriber)
 )]
-async fn insert_subscriber(
+pub(crate) async fn insert_subscriber(
     transaction: &mut Transaction<'_, Postgres>,
     new_subscriber: &NewSubscriber,
 ) -> Result<Uuid, anyhow::Error> {
     let subscriber_id = Uuid::new_v4();
     let query = sqlx::query!(
         r#"
-        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
-        VALUES ($1, $2, $3, $4, $5)
+        INSERT INTO subscriptions (id, email, canonical_email, name, subscribed_at, status)
+        VALUES ($1, $2, $3, $4, $5, $6)
         "#,
         subscriber_id,
         new_subscriber.email.as_ref(),
+        new_subscriber.email.canonical(),
         new_subscriber.name.as_ref(),
         OffsetDateTime::now_utc(),
         SubscriptionStatus::PendingConfirmation.as_ref(),
@@ -135,21 +185,69 @@ async fn insert_subscriber(
     Ok(subscriber_id)
 }
 
+/// Deletes a subscriber together with their tokens and pending deliveries.
+/// Returns the email of the deleted subscriber, or `None` if there is no such subscriber.
+#[tracing::instrument(skip(transaction))]
+pub(crate) async fn delete_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<Option<String>, anyhow::Error> {
+    transaction
+        .execute(sqlx::query!(
+            r#"
+            DELETE FROM subscription_tokens
+            WHERE subscriber_id = $1
+            "#,
+            subscriber_id,
+        ))
+        .await
+        .context("Failed to delete subscription tokens")?;
+
+    let Some(row) = sqlx::query!(
+        r#"
+        DELETE FROM subscriptions
+        WHERE id = $1
+        RETURNING email, canonical_email
+        "#,
+        subscriber_id,
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .context("Failed to delete subscriber")?
+    else {
+        return Ok(None);
+    };
+
+    transaction
+        .execute(sqlx::query!(
+            r#"
+            DELETE FROM issue_delivery_queue
+            WHERE canonical_email = $1
+            "#,
+            row.canonical_email,
+        ))
+        .await
+        .context("Failed to delete pending deliveries")?;
+
+    Ok(Some(row.email))
+}
+
 #[tracing::instrument(
     name = "Store subscription token in the database",
-    skip(transaction, subscription_token)
+    skip(transaction, subscription_token, secret)
 )]
-async fn store_token(
+pub(crate) async fn store_token(
     transaction: &mut Transaction<'_, Postgres>,
     subscriber_id: Uuid,
     subscription_token: &SubscriptionToken,
+    secret: &Secret<String>,
 ) -> Result<(), anyhow::Error> {
     let query = sqlx::query!(
         r#"
-        INSERT INTO subscription_tokens (subscription_token, subscriber_id)
+        INSERT INTO subscription_tokens (subscription_token_hash, subscriber_id)
         VALUES ($1, $2)
         "#,
-        subscription_token.expose_secret(),
+        subscription_token.hash(secret),
         subscriber_id
     );
 
@@ -163,14 +261,21 @@ async fn store_token(
 
 #[tracing::instrument(
     name = "Send confirmation email to a new subscriber",
-    skip(email_client, new_subscriber, base_url, subscription_token)
+    skip(db_pool, email_client, new_subscriber, base_url, subscription_token)
 )]
-async fn send_confirmation_email(
+pub(crate) async fn send_confirmation_email(
+    db_pool: &PgPool,
     email_client: &EmailClient,
     new_subscriber: NewSubscriber,
     base_url: &Uri,
     subscription_token: &SubscriptionToken,
 ) -> Result<(), anyhow::Error> {
+    let email = &new_subscriber.email;
+    if let Some(reason) = check_suppression(db_pool, email.as_ref(), email.canonical()).await? {
+        tracing::info!(reason = %reason, "Recipient is on the suppression list. Skipping.");
+        return Ok(());
+    }
+
     let link = format!(
         "{base_url}subscriptions/confirm?subscription_token={}",
         subscription_token.expose_secret()
@@ -198,6 +303,22 @@ async fn send_confirmation_email(
 struct FormData {
     name: String,
     email: String,
+    /// Honeypot, hidden from humans.
+    website: Option<String>,
+    form_token: Option<String>,
+    pow_challenge: Option<String>,
+    pow_solution: Option<String>,
+}
+
+impl FormData {
+    fn protection_fields(&self) -> FormFields<'_> {
+        FormFields {
+            honeypot: self.website.as_deref(),
+            form_token: self.form_token.as_deref(),
+            pow_challenge: self.pow_challenge.as_deref(),
+            pow_solution: self.pow_solution.as_deref(),
+        }
+    }
 }
 
 impl TryFrom<FormData> for NewSubscriber {
@@ -211,10 +332,10 @@ impl TryFrom<FormData> for NewSubscriber {
 }
 
 #[derive(FromRow)]
-struct Subscription {
-    id: Uuid,
+pub(crate) struct Subscription {
+    pub(crate) id: Uuid,
     #[sqlx(try_from = "String")]
-    status: SubscriptionStatus,
+    pub(crate) status: SubscriptionStatus,
 }
 
 #[derive(Template)]
@@ -236,9 +357,20 @@ enum SubscribeError {
     #[error("Subscription has been confirmed already")]
     SubscriptionAlreadyConfirmed,
     #[error(transparent)]
+    Rejected(#[from] Rejection),
+    #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
 
+impl From<ProtectionError> for SubscribeError {
+    fn from(error: ProtectionError) -> Self {
+        match error {
+            ProtectionError::Rejected(rejection) => Self::Rejected(rejection),
+            ProtectionError::UnexpectedError(e) => Self::UnexpectedError(e),
+        }
+    }
+}
+
 impl IntoResponse for SubscribeError {
     fn into_response(self) -> Response {
         tracing::error!("{:#?}", self);
@@ -248,6 +380,12 @@ impl IntoResponse for SubscribeError {
             Self::SubscriptionAlreadyConfirmed => {
                 (StatusCode::UNPROCESSABLE_ENTITY, self.to_string()).into_response()
             }
+            Self::Rejected(
+                Rejection::IpRateLimited
+                | Rejection::EmailRateLimited
+                | Rejection::ConfirmationEmailCap,
+            ) => (StatusCode::TOO_MANY_REQUESTS, self.to_string()).into_response(),
+            Self::Rejected(_) => (StatusCode::BAD_REQUEST, self.to_string()).into_response(),
             Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
         }
     }
@@ -1,6 +1,7 @@
 use crate::{
     app_state::AppState,
     domain::{SubscriptionStatus, SubscriptionToken},
+    drip_sequences::enroll_subscriber,
 };
 use anyhow::Context;
 use axum::{
@@ -10,9 +11,9 @@ use axum::{
     routing::get,
     Router,
 };
-use secrecy::ExposeSecret;
+use secrecy::Secret;
 use serde::Deserialize;
-use sqlx::{Executor, Postgres, Row, Transaction};
+use sqlx::{Executor, Postgres, Transaction};
 use uuid::Uuid;
 
 pub fn router() -> Router<AppState> {
@@ -33,14 +34,20 @@ async fn confirm(
     let subscription_token = SubscriptionToken::parse(parameters.subscription_token)
         .map_err(SubscriptionConfirmationError::InvalidTokenFormat)?;
 
-    let subscriber_id =
-        match get_subscriber_id_from_token(&mut transaction, &subscription_token).await? {
-            Some(id) => id,
-            None => return Err(SubscriptionConfirmationError::UnauthorizedToken),
-        };
+    let subscriber_id = match get_subscriber_id_from_token(
+        &mut transaction,
+        &subscription_token,
+        &app_state.token_secret,
+    )
+    .await?
+    {
+        Some(id) => id,
+        None => return Err(SubscriptionConfirmationError::UnauthorizedToken),
+    };
 
     confirm_subscriber(&mut transaction, subscriber_id).await?;
     delete_confirmation_tokens(&mut transaction, subscriber_id).await?;
+    enroll_subscriber(&mut *transaction, subscriber_id).await?;
 
     transaction
         .commit()
@@ -57,32 +64,28 @@ struct Parameters {
 
 #[tracing::instrument(
     name = "Get subscriber_id from token",
-    skip(transaction, subscription_token)
+    skip(transaction, subscription_token, secret)
 )]
 async fn get_subscriber_id_from_token(
     transaction: &mut Transaction<'_, Postgres>,
     subscription_token: &SubscriptionToken,
+    secret: &Secret<String>,
 ) -> Result<Option<Uuid>, anyhow::Error> {
-    let query = sqlx::query!(
+    let row = sqlx::query!(
         r#"
-        SELECT subscriber_id FROM subscription_tokens
-        WHERE subscription_token = $1
+        SELECT subscriber_id, subscription_token_hash AS "subscription_token_hash!"
+        FROM subscription_tokens
+        WHERE subscription_token_hash = $1
         "#,
-        subscription_token.expose_secret(),
-    );
-
-    let subscriber_id = match transaction
-        .fetch_optional(query)
-        .await
-        .context("Failed fetch subscriber id")?
-    {
-        Some(row) => row
-            .try_get("subscriber_id")
-            .context("Failed to instantiate subscriber_id")?,
-        _ => None,
-    };
-
-    Ok(subscriber_id)
+        subscription_token.hash(secret),
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .context("Failed fetch subscriber id")?;
+
+    Ok(row
+        .filter(|row| subscription_token.verify(secret, &row.subscription_token_hash))
+        .map(|row| row.subscriber_id))
 }
 
 #[tracing::instrument(
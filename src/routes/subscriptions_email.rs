@@ -0,0 +1,535 @@
+use crate::{
+    app_state::AppState,
+    domain::{SubscriberEmail, SubscriptionToken},
+    email_client::EmailClient,
+    routes::subscriptions::get_subscription,
+    subscribe_protection::{ProtectionError, Rejection},
+    suppression_list::check_suppression,
+};
+use anyhow::Context;
+use askama::Template;
+use axum::{
+    extract::{Query, State},
+    http::{StatusCode, Uri},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/subscriptions/email/confirm", get(confirm_email_change))
+}
+
+/// Starts moving a subscription to a new address. The address is swapped
+/// only once the link sent to the new address is followed, within a day; a
+/// later request replaces an earlier one that has not been confirmed.
+/// Requests are rate limited like subscription attempts.
+#[tracing::instrument(name = "Request email change", skip(app_state, new_email))]
+pub(crate) async fn request_email_change(
+    app_state: &AppState,
+    ip_address: Option<&str>,
+    subscriber_id: Uuid,
+    new_email: String,
+) -> Result<SubscriberEmail, EmailChangeError> {
+    let new_email = SubscriberEmail::parse(new_email).map_err(EmailChangeError::ValidationError)?;
+    let new_email = app_state
+        .email_verifier
+        .verify(&new_email)
+        .await
+        .map_err(|e| EmailChangeError::ValidationError(e.to_string()))?
+        .with_rules(&app_state.local_part_rules);
+
+    let protection = &app_state.subscribe_protection;
+    protection
+        .check_rate_limits(&app_state.db_pool, ip_address, &new_email)
+        .await?;
+
+    let mut transaction = app_state
+        .db_pool
+        .begin()
+        .await
+        .context("Failed to begin transaction")?;
+
+    let subscriber_exists = sqlx::query!(
+        r#"
+        SELECT id
+        FROM subscriptions
+        WHERE id = $1
+        FOR UPDATE
+        "#,
+        subscriber_id,
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to fetch subscriber")?
+    .is_some();
+    if !subscriber_exists {
+        return Err(EmailChangeError::UnknownSubscriber(subscriber_id));
+    }
+    protection
+        .check_email_change_cap(&mut transaction, subscriber_id)
+        .await?;
+
+    match get_subscription(&mut transaction, &new_email).await? {
+        Some(subscription) if subscription.id == subscriber_id => {
+            return Err(EmailChangeError::ValidationError(format!(
+                "`{new_email}` is already the address of this subscription"
+            )))
+        }
+        Some(_) => return Err(EmailChangeError::AddressTaken(new_email.to_string())),
+        None => {}
+    }
+
+    let subscription_token = SubscriptionToken::generate();
+    store_email_change(
+        &mut transaction,
+        subscriber_id,
+        &new_email,
+        &subscription_token,
+        &app_state.token_secret,
+    )
+    .await?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit transaction")?;
+
+    send_email_change_confirmation(
+        &app_state.db_pool,
+        &app_state.email_client,
+        &new_email,
+        &app_state.base_url,
+        &subscription_token,
+    )
+    .await?;
+
+    Ok(new_email)
+}
+
+/// Earlier requests are kept, to be counted by the email change cap, but only
+/// the latest one can be confirmed.
+#[tracing::instrument(
+    name = "Store email change in the database",
+    skip(transaction, new_email, subscription_token, secret)
+)]
+async fn store_email_change(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    new_email: &SubscriberEmail,
+    subscription_token: &SubscriptionToken,
+    secret: &Secret<String>,
+) -> Result<(), anyhow::Error> {
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO email_changes (
+            subscription_token_hash,
+            subscriber_id,
+            new_email,
+            new_canonical_email
+        )
+        VALUES ($1, $2, $3, $4)
+        "#,
+        subscription_token.hash(secret),
+        subscriber_id,
+        new_email.as_ref(),
+        new_email.canonical(),
+    );
+
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to store email change")?;
+
+    Ok(())
+}
+
+#[tracing::instrument(
+    name = "Send email change confirmation",
+    skip(db_pool, email_client, new_email, base_url, subscription_token)
+)]
+async fn send_email_change_confirmation(
+    db_pool: &PgPool,
+    email_client: &EmailClient,
+    new_email: &SubscriberEmail,
+    base_url: &Uri,
+    subscription_token: &SubscriptionToken,
+) -> Result<(), anyhow::Error> {
+    if let Some(reason) =
+        check_suppression(db_pool, new_email.as_ref(), new_email.canonical()).await?
+    {
+        tracing::info!(reason = %reason, "Recipient is on the suppression list. Skipping.");
+        return Ok(());
+    }
+
+    let link = format!(
+        "{base_url}subscriptions/email/confirm?subscription_token={}",
+        subscription_token.expose_secret()
+    );
+
+    let html_body = ConfirmationHtmlTemplate {
+        confirmation_link: &link,
+    }
+    .render()
+    .context("Failed to render html template")?;
+
+    let plain_body = ConfirmationPlainTextTemplate {
+        confirmation_link: &link,
+    }
+    .render()
+    .context("Failed to render plain text template")?;
+
+    email_client
+        .send_email(
+            new_email,
+            "Confirm your new address",
+            &html_body,
+            &plain_body,
+        )
+        .await
+        .context("Failed to execute request")
+}
+
+#[tracing::instrument(name = "Confirm email change", skip(app_state, parameters))]
+async fn confirm_email_change(
+    State(app_state): State<AppState>,
+    Query(parameters): Query<Parameters>,
+) -> Result<(), EmailChangeConfirmationError> {
+    let subscription_token = SubscriptionToken::parse(parameters.subscription_token)
+        .map_err(EmailChangeConfirmationError::InvalidTokenFormat)?;
+
+    let mut transaction = app_state
+        .db_pool
+        .begin()
+        .await
+        .context("Failed to begin transaction")?;
+
+    let Some(email_change) = get_email_change(
+        &mut transaction,
+        &subscription_token,
+        &app_state.token_secret,
+    )
+    .await?
+    else {
+        return Err(EmailChangeConfirmationError::UnauthorizedToken);
+    };
+
+    let old_email = sqlx::query!(
+        r#"
+        SELECT email, canonical_email
+        FROM subscriptions
+        WHERE id = $1
+        FOR UPDATE
+        "#,
+        email_change.subscriber_id,
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .context("Failed to fetch subscriber")?;
+
+    // Someone may have subscribed with the new address in the meantime.
+    let address_taken = sqlx::query!(
+        r#"
+        SELECT id
+        FROM subscriptions
+        WHERE canonical_email = $1 AND id <> $2
+        "#,
+        email_change.new_canonical_email,
+        email_change.subscriber_id,
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to check whether the new address is taken")?
+    .is_some();
+    if address_taken {
+        delete_email_changes(&mut transaction, email_change.subscriber_id).await?;
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit transaction")?;
+        return Err(EmailChangeConfirmationError::AddressTaken);
+    }
+
+    swap_address(
+        &mut transaction,
+        email_change.subscriber_id,
+        &old_email.canonical_email,
+        &email_change,
+    )
+    .await?;
+    delete_email_changes(&mut transaction, email_change.subscriber_id).await?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit transaction")?;
+
+    // The change has been made, failing to tell the old address about it
+    // must not make the confirmation look failed.
+    if let Err(e) = notify_old_address(
+        &app_state.db_pool,
+        &app_state.email_client,
+        old_email.email,
+        &old_email.canonical_email,
+        &email_change,
+    )
+    .await
+    {
+        tracing::error!(
+            error_cause_chain = ?e,
+            error.message = %e,
+            "Failed to notify the old address about the email change"
+        );
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct Parameters {
+    subscription_token: String,
+}
+
+struct EmailChange {
+    subscriber_id: Uuid,
+    new_email: String,
+    new_canonical_email: String,
+}
+
+/// The email change a token has been sent for, unless it has expired or has
+/// been replaced by a later request.
+#[tracing::instrument(
+    name = "Get email change from token",
+    skip(transaction, subscription_token, secret)
+)]
+async fn get_email_change(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscription_token: &SubscriptionToken,
+    secret: &Secret<String>,
+) -> Result<Option<EmailChange>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT subscription_token_hash, subscriber_id, new_email, new_canonical_email
+        FROM email_changes c
+        WHERE
+            subscription_token_hash = $1 AND
+            requested_at > now() - interval '1 day' AND
+            NOT EXISTS (
+                SELECT 1
+                FROM email_changes later
+                WHERE
+                    later.subscriber_id = c.subscriber_id AND
+                    later.requested_at > c.requested_at
+            )
+        "#,
+        subscription_token.hash(secret),
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .context("Failed to fetch email change")?;
+
+    Ok(row
+        .filter(|row| subscription_token.verify(secret, &row.subscription_token_hash))
+        .map(|row| EmailChange {
+            subscriber_id: row.subscriber_id,
+            new_email: row.new_email,
+            new_canonical_email: row.new_canonical_email,
+        }))
+}
+
+/// Moves the subscription, and the issues still queued for it, to the new
+/// address. The subscription keeps its id, so its history stays attached.
+#[tracing::instrument(name = "Swap subscriber address", skip(transaction, email_change))]
+async fn swap_address(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    old_canonical_email: &str,
+    email_change: &EmailChange,
+) -> Result<(), anyhow::Error> {
+    transaction
+        .execute(sqlx::query!(
+            r#"
+            UPDATE subscriptions
+            SET email = $1, canonical_email = $2
+            WHERE id = $3
+            "#,
+            email_change.new_email,
+            email_change.new_canonical_email,
+            subscriber_id,
+        ))
+        .await
+        .context("Failed to update subscriber address")?;
+
+    transaction
+        .execute(sqlx::query!(
+            r#"
+            UPDATE issue_delivery_queue
+            SET canonical_email = $1
+            WHERE canonical_email = $2
+            "#,
+            email_change.new_canonical_email,
+            old_canonical_email,
+        ))
+        .await
+        .context("Failed to move pending deliveries")?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "Delete email changes", skip(transaction))]
+async fn delete_email_changes(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    let query = sqlx::query!(
+        r#"
+        DELETE FROM email_changes
+        WHERE subscriber_id = $1
+        "#,
+        subscriber_id,
+    );
+
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to delete email changes")?;
+
+    Ok(())
+}
+
+/// Deletes email changes which can no longer be confirmed. Run periodically
+/// by the background worker.
+#[tracing::instrument(skip_all)]
+pub async fn delete_expired_email_changes(db_pool: &PgPool) -> Result<u64, anyhow::Error> {
+    let deleted = sqlx::query!(
+        r#"
+        DELETE FROM email_changes
+        WHERE requested_at < now() - interval '1 day'
+        "#,
+    )
+    .execute(db_pool)
+    .await
+    .context("Failed to delete expired email changes")?
+    .rows_affected();
+
+    Ok(deleted)
+}
+
+#[tracing::instrument(
+    name = "Notify the old address about an email change",
+    skip(db_pool, email_client, old_email, old_canonical_email, email_change)
+)]
+async fn notify_old_address(
+    db_pool: &PgPool,
+    email_client: &EmailClient,
+    old_email: String,
+    old_canonical_email: &str,
+    email_change: &EmailChange,
+) -> Result<(), anyhow::Error> {
+    if let Some(reason) = check_suppression(db_pool, &old_email, old_canonical_email).await? {
+        tracing::info!(reason = %reason, "Recipient is on the suppression list. Skipping.");
+        return Ok(());
+    }
+
+    let old_email = SubscriberEmail::parse(old_email).map_err(anyhow::Error::msg)?;
+
+    let html_body = ChangedHtmlTemplate {
+        new_email: &email_change.new_email,
+    }
+    .render()
+    .context("Failed to render html template")?;
+
+    let plain_body = ChangedPlainTextTemplate {
+        new_email: &email_change.new_email,
+    }
+    .render()
+    .context("Failed to render plain text template")?;
+
+    email_client
+        .send_email(
+            &old_email,
+            "Your subscription has moved",
+            &html_body,
+            &plain_body,
+        )
+        .await
+        .context("Failed to execute request")
+}
+
+#[derive(Template)]
+#[template(path = "email/email_change.html")]
+struct ConfirmationHtmlTemplate<'a> {
+    confirmation_link: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/email_change.txt")]
+struct ConfirmationPlainTextTemplate<'a> {
+    confirmation_link: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/email_changed.html")]
+struct ChangedHtmlTemplate<'a> {
+    new_email: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/email_changed.txt")]
+struct ChangedPlainTextTemplate<'a> {
+    new_email: &'a str,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum EmailChangeError {
+    #[error("{0}")]
+    ValidationError(String),
+    #[error("Subscriber `{0}` does not exist")]
+    UnknownSubscriber(Uuid),
+    #[error("`{0}` is already subscribed")]
+    AddressTaken(String),
+    #[error(transparent)]
+    Rejected(#[from] Rejection),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl From<ProtectionError> for EmailChangeError {
+    fn from(error: ProtectionError) -> Self {
+        match error {
+            ProtectionError::Rejected(rejection) => Self::Rejected(rejection),
+            ProtectionError::UnexpectedError(e) => Self::UnexpectedError(e),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+enum EmailChangeConfirmationError {
+    #[error("{0}")]
+    InvalidTokenFormat(String),
+    #[error("Token is not authorized")]
+    UnauthorizedToken,
+    #[error("The new address has been subscribed in the meantime")]
+    AddressTaken,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl IntoResponse for EmailChangeConfirmationError {
+    fn into_response(self) -> Response {
+        tracing::error!("{:#?}", self);
+
+        match self {
+            Self::InvalidTokenFormat(_) => {
+                (StatusCode::BAD_REQUEST, self.to_string()).into_response()
+            }
+            Self::UnauthorizedToken => (StatusCode::UNAUTHORIZED, self.to_string()).into_response(),
+            Self::AddressTaken => (StatusCode::CONFLICT, self.to_string()).into_response(),
+            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    }
+}
@@ -0,0 +1,387 @@
+use crate::{
+    app_state::AppState,
+    client_info::ClientInfo,
+    domain::{
+        ContentFormat, DigestFrequency, PreferencesToken, SubscriberName, SubscriptionStatus,
+    },
+    drip_sequences::stop_enrollments,
+    routes::subscriptions_email::{request_email_change, EmailChangeError},
+};
+use anyhow::Context;
+use askama_axum::Template;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
+    Form, Router,
+};
+use axum_messages::Messages;
+use serde::Deserialize;
+use sqlx::PgPool;
+use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
+use uuid::Uuid;
+
+/// Periods, in days, for which a subscriber may pause delivery.
+const PAUSE_DAYS: [i64; 3] = [7, 30, 90];
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/subscriptions/preferences",
+            get(preferences_form).post(update_preferences),
+        )
+        .route("/subscriptions/preferences/email", post(change_email))
+        .route("/subscriptions/unsubscribe", post(unsubscribe))
+}
+
+#[tracing::instrument(name = "Get subscription preferences", skip_all)]
+async fn preferences_form(
+    State(app_state): State<AppState>,
+    messages: Messages,
+    Query(parameters): Query<Parameters>,
+) -> Result<PreferencesTemplate<'static>, PreferencesError> {
+    let subscriber_id = verify_token(&parameters.token, &app_state)?;
+    let preferences = get_preferences(&app_state.db_pool, subscriber_id)
+        .await?
+        .ok_or(PreferencesError::InvalidToken)?;
+    let paused_until = preferences
+        .paused_until
+        .filter(|until| *until > OffsetDateTime::now_utc());
+
+    Ok(PreferencesTemplate {
+        page_title: "Subscription preferences",
+        flashes: messages.map(|m| m.message).collect(),
+        token: parameters.token,
+        subscribed: preferences.status == SubscriptionStatus::Confirmed,
+        name: preferences.name,
+        email: preferences.email,
+        content_formats: [ContentFormat::Html, ContentFormat::PlainText]
+            .into_iter()
+            .map(|format| Choice {
+                value: format.as_ref().to_string(),
+                label: content_format_label(format).to_string(),
+                selected: format == preferences.content_format,
+            })
+            .collect(),
+        digest_frequencies: [
+            DigestFrequency::Immediate,
+            DigestFrequency::Daily,
+            DigestFrequency::Weekly,
+        ]
+        .into_iter()
+        .map(|frequency| Choice {
+            value: frequency.as_ref().to_string(),
+            label: digest_frequency_label(frequency).to_string(),
+            selected: frequency == preferences.digest_frequency,
+        })
+        .collect(),
+        pause_choices: pause_choices(paused_until),
+    })
+}
+
+#[tracing::instrument(name = "Update subscription preferences", skip_all)]
+async fn update_preferences(
+    State(app_state): State<AppState>,
+    messages: Messages,
+    Form(form): Form<FormData>,
+) -> Result<Redirect, PreferencesError> {
+    let subscriber_id = verify_token(&form.token, &app_state)?;
+    let preferences_page = format!("/subscriptions/preferences?token={}", form.token);
+    let preferences = get_preferences(&app_state.db_pool, subscriber_id)
+        .await?
+        .ok_or(PreferencesError::InvalidToken)?;
+
+    let update = match form.try_into_update(preferences.paused_until) {
+        Ok(update) => update,
+        Err(e) => {
+            messages.error(e);
+            return Ok(Redirect::to(&preferences_page));
+        }
+    };
+
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET name = $1, content_format = $2, digest_frequency = $3, paused_until = $4
+        WHERE id = $5
+        "#,
+        update.name.as_ref(),
+        update.content_format.as_ref(),
+        update.digest_frequency.as_ref(),
+        update.paused_until,
+        subscriber_id,
+    )
+    .execute(&app_state.db_pool)
+    .await
+    .context("Failed to update subscription preferences")?;
+
+    messages.info("Your preferences have been updated.");
+
+    Ok(Redirect::to(&preferences_page))
+}
+
+#[tracing::instrument(name = "Change email from the preferences page", skip_all)]
+async fn change_email(
+    State(app_state): State<AppState>,
+    client_info: ClientInfo,
+    messages: Messages,
+    Form(form): Form<ChangeEmailFormData>,
+) -> Result<Redirect, PreferencesError> {
+    let subscriber_id = verify_token(&form.token, &app_state)?;
+    let preferences_page = format!("/subscriptions/preferences?token={}", form.token);
+
+    match request_email_change(
+        &app_state,
+        client_info.ip_address.as_deref(),
+        subscriber_id,
+        form.email,
+    )
+    .await
+    {
+        Ok(new_email) => messages.info(format!(
+            "We have sent a confirmation link to {new_email}. \
+            Your address will change once you follow it."
+        )),
+        Err(EmailChangeError::UnknownSubscriber(_)) => return Err(PreferencesError::InvalidToken),
+        Err(EmailChangeError::UnexpectedError(e)) => return Err(e.into()),
+        Err(e) => messages.error(e.to_string()),
+    };
+
+    Ok(Redirect::to(&preferences_page))
+}
+
+#[tracing::instrument(name = "Unsubscribe", skip_all)]
+async fn unsubscribe(
+    State(app_state): State<AppState>,
+    messages: Messages,
+    Form(form): Form<UnsubscribeFormData>,
+) -> Result<Redirect, PreferencesError> {
+    let subscriber_id = verify_token(&form.token, &app_state)?;
+
+    let mut transaction = app_state
+        .db_pool
+        .begin()
+        .await
+        .context("Failed to begin transaction")?;
+
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET status = $1
+        WHERE id = $2 AND status = $3
+        "#,
+        SubscriptionStatus::Unsubscribed.as_ref(),
+        subscriber_id,
+        SubscriptionStatus::Confirmed.as_ref(),
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to unsubscribe")?;
+    stop_enrollments(&mut *transaction, subscriber_id).await?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit transaction")?;
+
+    messages.info("You have been unsubscribed.");
+
+    Ok(Redirect::to(&format!(
+        "/subscriptions/preferences?token={}",
+        form.token
+    )))
+}
+
+fn verify_token(token: &str, app_state: &AppState) -> Result<Uuid, PreferencesError> {
+    PreferencesToken::verify(token, &app_state.token_secret).ok_or(PreferencesError::InvalidToken)
+}
+
+#[tracing::instrument(name = "Get preferences from the database", skip(db_pool))]
+async fn get_preferences(
+    db_pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<Preferences>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT name, email, status, content_format, digest_frequency, paused_until
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id,
+    )
+    .fetch_optional(db_pool)
+    .await
+    .context("Failed to fetch subscription preferences")?;
+
+    row.map(|row| {
+        Ok(Preferences {
+            name: row.name,
+            email: row.email,
+            status: row.status.try_into().map_err(anyhow::Error::msg)?,
+            content_format: row.content_format.try_into().map_err(anyhow::Error::msg)?,
+            digest_frequency: row
+                .digest_frequency
+                .try_into()
+                .map_err(anyhow::Error::msg)?,
+            paused_until: row.paused_until,
+        })
+    })
+    .transpose()
+}
+
+fn pause_choices(paused_until: Option<OffsetDateTime>) -> Vec<Choice> {
+    let mut choices = match paused_until {
+        Some(until) => vec![
+            Choice {
+                value: "keep".to_string(),
+                label: format!("Paused until {}", format_timestamp(until)),
+                selected: true,
+            },
+            Choice {
+                value: "0".to_string(),
+                label: "Resume delivery".to_string(),
+                selected: false,
+            },
+        ],
+        None => vec![Choice {
+            value: "0".to_string(),
+            label: "Not paused".to_string(),
+            selected: true,
+        }],
+    };
+
+    choices.extend(PAUSE_DAYS.iter().map(|days| Choice {
+        value: days.to_string(),
+        label: format!("Pause for {days} days"),
+        selected: false,
+    }));
+
+    choices
+}
+
+fn content_format_label(format: ContentFormat) -> &'static str {
+    match format {
+        ContentFormat::Html => "HTML",
+        ContentFormat::PlainText => "Plain text only",
+    }
+}
+
+fn digest_frequency_label(frequency: DigestFrequency) -> &'static str {
+    match frequency {
+        DigestFrequency::Immediate => "Every issue as it is published",
+        DigestFrequency::Daily => "Daily digest",
+        DigestFrequency::Weekly => "Weekly digest",
+    }
+}
+
+fn format_timestamp(timestamp: OffsetDateTime) -> String {
+    timestamp
+        .format(&Rfc3339)
+        .unwrap_or_else(|_| timestamp.to_string())
+}
+
+#[derive(Deserialize)]
+struct Parameters {
+    token: String,
+}
+
+#[derive(Deserialize)]
+struct FormData {
+    token: String,
+    name: String,
+    content_format: String,
+    digest_frequency: String,
+    pause: String,
+}
+
+impl FormData {
+    fn try_into_update(
+        self,
+        paused_until: Option<OffsetDateTime>,
+    ) -> Result<PreferencesUpdate, String> {
+        let paused_until = match self.pause.as_str() {
+            "keep" => paused_until,
+            "0" => None,
+            days => match days.parse() {
+                Ok(days) if PAUSE_DAYS.contains(&days) => {
+                    Some(OffsetDateTime::now_utc() + Duration::days(days))
+                }
+                _ => return Err(format!("`{days}` is not a valid pause period")),
+            },
+        };
+
+        Ok(PreferencesUpdate {
+            name: SubscriberName::parse(self.name)?,
+            content_format: self.content_format.try_into()?,
+            digest_frequency: self.digest_frequency.try_into()?,
+            paused_until,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct ChangeEmailFormData {
+    token: String,
+    email: String,
+}
+
+#[derive(Deserialize)]
+struct UnsubscribeFormData {
+    token: String,
+}
+
+struct Preferences {
+    name: String,
+    email: String,
+    status: SubscriptionStatus,
+    content_format: ContentFormat,
+    digest_frequency: DigestFrequency,
+    paused_until: Option<OffsetDateTime>,
+}
+
+struct PreferencesUpdate {
+    name: SubscriberName,
+    content_format: ContentFormat,
+    digest_frequency: DigestFrequency,
+    paused_until: Option<OffsetDateTime>,
+}
+
+struct Choice {
+    value: String,
+    label: String,
+    selected: bool,
+}
+
+#[derive(Template)]
+#[template(path = "web/preferences.html")]
+struct PreferencesTemplate<'a> {
+    page_title: &'a str,
+    flashes: Vec<String>,
+    token: String,
+    subscribed: bool,
+    name: String,
+    email: String,
+    content_formats: Vec<Choice>,
+    digest_frequencies: Vec<Choice>,
+    pause_choices: Vec<Choice>,
+}
+
+#[derive(Debug, thiserror::Error)]
+enum PreferencesError {
+    #[error("This preferences link is not valid")]
+    InvalidToken,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl IntoResponse for PreferencesError {
+    fn into_response(self) -> Response {
+        tracing::error!("{:#?}", self);
+
+        match self {
+            Self::InvalidToken => (StatusCode::UNAUTHORIZED, self.to_string()).into_response(),
+            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    }
+}
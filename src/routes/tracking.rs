@@ -0,0 +1,138 @@
+use crate::{
+    app_state::AppState,
+    domain::{issue_links, unescape_link, TrackedEvent, TrackingToken},
+};
+use anyhow::Context;
+use axum::{
+    extract::{Path, State},
+    http::{
+        header::{CACHE_CONTROL, CONTENT_TYPE, LOCATION},
+        StatusCode,
+    },
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Transparent 1x1 GIF.
+const PIXEL: [u8; 43] = [
+    0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x01, 0x00, 0x01, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xff, 0xff, 0xff, 0x21, 0xf9, 0x04, 0x01, 0x00, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x01, 0x00, 0x00, 0x02, 0x02, 0x44, 0x01, 0x00, 0x3b,
+];
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/o/:token", get(track_open))
+        .route("/r/:token", get(track_click))
+}
+
+/// Serves the pixel whatever the token, so that emails never show a broken
+/// image. Only valid tokens are recorded.
+#[tracing::instrument(name = "Track issue open", skip_all)]
+async fn track_open(State(app_state): State<AppState>, Path(token): Path<String>) -> Response {
+    if let Some((issue_id, subscriber_id, TrackedEvent::Open)) =
+        TrackingToken::verify(&token, &app_state.token_secret)
+    {
+        if app_state.tracking_enabled {
+            record(&app_state.db_pool, issue_id, subscriber_id, None).await;
+        }
+    }
+
+    (
+        [(CONTENT_TYPE, "image/gif"), (CACHE_CONTROL, "no-store")],
+        PIXEL,
+    )
+        .into_response()
+}
+
+/// Redirects to a link of an issue. Failing to record the click must not keep
+/// the subscriber from getting where they wanted to go.
+#[tracing::instrument(name = "Track link click", skip_all)]
+async fn track_click(
+    State(app_state): State<AppState>,
+    Path(token): Path<String>,
+) -> Result<Response, TrackingError> {
+    let Some((issue_id, subscriber_id, TrackedEvent::Click(index))) =
+        TrackingToken::verify(&token, &app_state.token_secret)
+    else {
+        return Err(TrackingError::UnknownLink);
+    };
+
+    let html_content = sqlx::query!(
+        "SELECT html_content FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        issue_id,
+    )
+    .fetch_optional(&app_state.db_pool)
+    .await
+    .context("Failed to fetch newsletter issue")?
+    .ok_or(TrackingError::UnknownLink)?
+    .html_content;
+
+    let link = issue_links(&html_content)
+        .into_iter()
+        .nth(index)
+        .map(|link| unescape_link(&link))
+        .ok_or(TrackingError::UnknownLink)?;
+
+    if app_state.tracking_enabled {
+        record(&app_state.db_pool, issue_id, subscriber_id, Some(&link)).await;
+    }
+
+    Ok((StatusCode::FOUND, [(LOCATION, link)]).into_response())
+}
+
+/// Records an open, or a click when given a link. Events of subscribers who
+/// have since been removed are dropped.
+#[tracing::instrument(skip(db_pool, link))]
+async fn record(db_pool: &PgPool, issue_id: Uuid, subscriber_id: Uuid, link: Option<&str>) {
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO issue_tracking_events (
+            newsletter_issue_id,
+            subscriber_id,
+            event_type,
+            link_url,
+            occurred_at
+        )
+        SELECT $1, id, $3, $4, now()
+        FROM subscriptions
+        WHERE id = $2
+        "#,
+        issue_id,
+        subscriber_id,
+        if link.is_some() { "click" } else { "open" },
+        link,
+    )
+    .execute(db_pool)
+    .await;
+
+    if let Err(e) = result {
+        tracing::error!(
+            error_cause_chain = ?e,
+            error.message = %e,
+            "Failed to record tracking event"
+        );
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+enum TrackingError {
+    #[error("This link is not valid")]
+    UnknownLink,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl IntoResponse for TrackingError {
+    fn into_response(self) -> Response {
+        tracing::error!("{:#?}", self);
+
+        match self {
+            Self::UnknownLink => (StatusCode::NOT_FOUND, self.to_string()).into_response(),
+            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    }
+}
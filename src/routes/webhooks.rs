@@ -0,0 +1,252 @@
+use crate::{
+    app_state::AppState,
+    domain::{SubscriberEmail, SubscriptionStatus},
+    email_events::{apply_thresholds, record_event, suppress, EmailEvent, EmailEventType},
+};
+use anyhow::Context;
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+    Router,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::Deserialize;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/webhooks/postmark", post(postmark))
+}
+
+/// Receives Postmark's bounce, spam complaint and subscription change
+/// webhooks. Other record types are acknowledged, so that they are not
+/// retried, and ignored.
+#[tracing::instrument(name = "Receive Postmark webhook", skip_all)]
+async fn postmark(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<StatusCode, WebhookError> {
+    let authenticated = credentials(&headers).is_some_and(|(username, secret)| {
+        app_state
+            .webhook_credentials
+            .verify(username.as_deref(), &secret)
+    });
+    if !authenticated {
+        return Err(WebhookError::Unauthorized);
+    }
+
+    let payload: PostmarkPayload =
+        serde_json::from_slice(&body).map_err(|e| WebhookError::InvalidPayload(e.to_string()))?;
+    let canonical_email = |email: &str| {
+        SubscriberEmail::parse_with(email.to_string(), &app_state.local_part_rules)
+            .ok()
+            .map(|email| email.canonical().to_string())
+    };
+
+    let (event, suppression) = match payload {
+        PostmarkPayload::Bounce(bounce) => {
+            let Some(event_type) = bounce_event_type(&bounce.bounce_type) else {
+                tracing::info!("Ignoring `{}` bounce", bounce.bounce_type);
+                return Ok(StatusCode::OK);
+            };
+            (bounce.into_event(event_type, canonical_email), None)
+        }
+        PostmarkPayload::SpamComplaint(complaint) => (
+            complaint.into_event(EmailEventType::SpamComplaint, canonical_email),
+            None,
+        ),
+        PostmarkPayload::SubscriptionChange(change) => {
+            let suppression = change.suppression();
+            (change.into_event(canonical_email), suppression)
+        }
+        PostmarkPayload::Other => {
+            tracing::info!("Ignoring unsupported record type");
+            return Ok(StatusCode::OK);
+        }
+    };
+
+    let mut transaction = app_state
+        .db_pool
+        .begin()
+        .await
+        .context("Failed to begin transaction")?;
+
+    if let Some(subscriber_id) = record_event(&mut transaction, &event).await? {
+        match suppression {
+            Some(status) => suppress(&mut transaction, subscriber_id, status).await?,
+            None => {
+                apply_thresholds(
+                    &mut transaction,
+                    subscriber_id,
+                    &app_state.suppression_thresholds,
+                )
+                .await?
+            }
+        }
+    }
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit transaction")?;
+
+    Ok(StatusCode::OK)
+}
+
+/// Extracts the username and secret from basic auth, or only the secret from
+/// the `X-Webhook-Secret` header.
+fn credentials(headers: &HeaderMap) -> Option<(Option<String>, String)> {
+    if let Some(secret) = headers.get("X-Webhook-Secret") {
+        return secret
+            .to_str()
+            .ok()
+            .map(|secret| (None, secret.to_string()));
+    }
+
+    let encoded = headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Basic ")?;
+    let decoded = String::from_utf8(STANDARD.decode(encoded).ok()?).ok()?;
+    let (username, secret) = decoded.split_once(':')?;
+
+    Some((Some(username.to_string()), secret.to_string()))
+}
+
+/// Only bounces telling that an address cannot receive email count as ones.
+/// Auto-responders, challenges and the like are ignored.
+fn bounce_event_type(bounce_type: &str) -> Option<EmailEventType> {
+    match bounce_type {
+        "HardBounce" | "BadEmailAddress" | "ManuallyDeactivated" => {
+            Some(EmailEventType::HardBounce)
+        }
+        "SoftBounce" | "Transient" | "DnsError" => Some(EmailEventType::SoftBounce),
+        _ => None,
+    }
+}
+
+fn parse_timestamp(timestamp: &str) -> OffsetDateTime {
+    OffsetDateTime::parse(timestamp, &Rfc3339).unwrap_or_else(|e| {
+        tracing::warn!(error.message = %e, "Invalid `{timestamp}` timestamp. Using current time.");
+        OffsetDateTime::now_utc()
+    })
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "RecordType")]
+enum PostmarkPayload {
+    Bounce(BouncePayload),
+    SpamComplaint(BouncePayload),
+    SubscriptionChange(SubscriptionChangePayload),
+    #[serde(other)]
+    Other,
+}
+
+/// Postmark reports spam complaints in the same form as bounces.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct BouncePayload {
+    #[serde(rename = "ID")]
+    id: i64,
+    #[serde(rename = "Type")]
+    bounce_type: String,
+    #[serde(rename = "MessageID")]
+    message_id: Option<String>,
+    email: String,
+    bounced_at: String,
+    description: Option<String>,
+}
+
+impl BouncePayload {
+    fn into_event(
+        self,
+        event_type: EmailEventType,
+        canonical_email: impl Fn(&str) -> Option<String>,
+    ) -> EmailEvent {
+        EmailEvent {
+            provider_event_id: Some(format!("postmark:bounce:{}", self.id)),
+            provider_message_id: self.message_id,
+            event_type,
+            canonical_email: canonical_email(&self.email),
+            email: self.email,
+            details: self.description,
+            occurred_at: parse_timestamp(&self.bounced_at),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SubscriptionChangePayload {
+    #[serde(rename = "MessageID")]
+    message_id: Option<String>,
+    recipient: String,
+    changed_at: String,
+    suppress_sending: bool,
+    suppression_reason: Option<String>,
+}
+
+impl SubscriptionChangePayload {
+    /// Addresses Postmark suppresses are suppressed here right away. Lifted
+    /// suppressions are only recorded, the subscriber has to subscribe again.
+    fn suppression(&self) -> Option<SubscriptionStatus> {
+        if !self.suppress_sending {
+            return None;
+        }
+
+        Some(match self.suppression_reason.as_deref() {
+            Some("HardBounce") => SubscriptionStatus::Bounced,
+            Some("SpamComplaint") => SubscriptionStatus::Complained,
+            _ => SubscriptionStatus::Unsubscribed,
+        })
+    }
+
+    fn into_event(self, canonical_email: impl Fn(&str) -> Option<String>) -> EmailEvent {
+        let details = match (self.suppress_sending, self.suppression_reason) {
+            (true, Some(reason)) => format!("Sending suppressed: {reason}"),
+            (true, None) => "Sending suppressed".to_string(),
+            (false, _) => "Sending reactivated".to_string(),
+        };
+
+        EmailEvent {
+            provider_event_id: None,
+            provider_message_id: self.message_id,
+            event_type: EmailEventType::SubscriptionChange,
+            canonical_email: canonical_email(&self.recipient),
+            email: self.recipient,
+            details: Some(details),
+            occurred_at: parse_timestamp(&self.changed_at),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+enum WebhookError {
+    #[error("Invalid webhook credentials")]
+    Unauthorized,
+    #[error("Invalid webhook payload: {0}")]
+    InvalidPayload(String),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl IntoResponse for WebhookError {
+    fn into_response(self) -> Response {
+        tracing::error!("{:#?}", self);
+
+        match self {
+            Self::Unauthorized => (
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, r#"Basic realm="webhooks""#)],
+                self.to_string(),
+            )
+                .into_response(),
+            Self::InvalidPayload(_) => (StatusCode::BAD_REQUEST, self.to_string()).into_response(),
+            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    }
+}
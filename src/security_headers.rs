@@ -0,0 +1,187 @@
+use anyhow::Context as _;
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{
+        header::{
+            CONTENT_SECURITY_POLICY, REFERRER_POLICY, STRICT_TRANSPORT_SECURITY,
+            X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS,
+        },
+        request::Parts,
+        HeaderValue, Request, Response, StatusCode,
+    },
+};
+use rand::RngCore;
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tower::{Layer, Service};
+
+const NONCE_PLACEHOLDER: &str = "{nonce}";
+
+/// Headers added to every response. The content security policy may contain
+/// a `{nonce}` placeholder, replaced with a fresh [`CspNonce`] on every request.
+#[derive(Clone, Debug)]
+pub struct SecurityHeaders {
+    content_security_policy: String,
+    frame_options: HeaderValue,
+    referrer_policy: HeaderValue,
+    strict_transport_security: Option<HeaderValue>,
+}
+
+impl SecurityHeaders {
+    pub fn new(
+        content_security_policy: &str,
+        frame_options: &str,
+        referrer_policy: &str,
+        hsts_max_age_seconds: Option<u64>,
+    ) -> Result<Self, anyhow::Error> {
+        // Validate the policy upfront, so that it cannot fail per request.
+        HeaderValue::from_str(&content_security_policy.replace(NONCE_PLACEHOLDER, ""))
+            .context("Invalid content security policy")?;
+
+        let strict_transport_security = hsts_max_age_seconds
+            .map(|max_age| HeaderValue::from_str(&format!("max-age={max_age}; includeSubDomains")))
+            .transpose()
+            .context("Invalid HSTS max age")?;
+
+        Ok(Self {
+            content_security_policy: content_security_policy.to_string(),
+            frame_options: HeaderValue::from_str(frame_options)
+                .context("Invalid X-Frame-Options value")?,
+            referrer_policy: HeaderValue::from_str(referrer_policy)
+                .context("Invalid Referrer-Policy value")?,
+            strict_transport_security,
+        })
+    }
+
+    fn content_security_policy(&self, nonce: &CspNonce) -> Option<HeaderValue> {
+        let policy = self
+            .content_security_policy
+            .replace(NONCE_PLACEHOLDER, nonce.as_ref());
+
+        HeaderValue::from_str(&policy).ok()
+    }
+}
+
+/// Per-request nonce allowing an inline `<script nonce="...">` under the
+/// content security policy. Templates with inline scripts take it as a field.
+#[derive(Clone, Debug)]
+pub struct CspNonce(Arc<str>);
+
+impl CspNonce {
+    fn generate() -> Self {
+        let mut bytes = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        Self(hex::encode(bytes).into())
+    }
+}
+
+impl AsRef<str> for CspNonce {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for CspNonce {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for CspNonce
+where
+    S: Send + Sync,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts.extensions.get::<CspNonce>().cloned().ok_or_else(|| {
+            tracing::error!("CSP nonce not found, is the `SecurityHeadersLayer` missing?");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SecurityHeadersLayer {
+    headers: Arc<SecurityHeaders>,
+}
+
+impl SecurityHeadersLayer {
+    pub fn new(headers: SecurityHeaders) -> Self {
+        Self {
+            headers: Arc::new(headers),
+        }
+    }
+}
+
+impl<S> Layer<S> for SecurityHeadersLayer {
+    type Service = SecurityHeadersService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        SecurityHeadersService {
+            inner,
+            headers: self.headers.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SecurityHeadersService<S> {
+    inner: S,
+    headers: Arc<SecurityHeaders>,
+}
+
+impl<ReqBody, ResBody, S> Service<Request<ReqBody>> for SecurityHeadersService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<ReqBody>) -> Self::Future {
+        let nonce = CspNonce::generate();
+        req.extensions_mut().insert(nonce.clone());
+
+        let headers = self.headers.clone();
+        let future = self.inner.call(req);
+
+        Box::pin(async move {
+            let mut res = future.await?;
+            let res_headers = res.headers_mut();
+
+            if let Some(policy) = headers.content_security_policy(&nonce) {
+                res_headers.entry(CONTENT_SECURITY_POLICY).or_insert(policy);
+            }
+            res_headers
+                .entry(X_FRAME_OPTIONS)
+                .or_insert_with(|| headers.frame_options.clone());
+            res_headers
+                .entry(REFERRER_POLICY)
+                .or_insert_with(|| headers.referrer_policy.clone());
+            res_headers
+                .entry(X_CONTENT_TYPE_OPTIONS)
+                .or_insert(HeaderValue::from_static("nosniff"));
+            if let Some(hsts) = &headers.strict_transport_security {
+                res_headers
+                    .entry(STRICT_TRANSPORT_SECURITY)
+                    .or_insert_with(|| hsts.clone());
+            }
+
+            Ok(res)
+        })
+    }
+}
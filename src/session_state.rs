@@ -4,7 +4,7 @@ use axum::{
     extract::FromRequestParts,
     http::{request::Parts, StatusCode},
 };
-use tower_sessions::Session;
+use tower_sessions::{session::Id, Session};
 use uuid::Uuid;
 
 pub struct TypedSession(Session);
@@ -16,6 +16,10 @@ impl TypedSession {
         Self(session)
     }
 
+    pub fn id(&self) -> Option<Id> {
+        self.0.id()
+    }
+
     pub async fn cycle_id(&self) -> Result<(), Error> {
         self.0
             .cycle_id()
@@ -27,6 +31,10 @@ impl TypedSession {
         self.0.flush().await.context("Failed to flush session")
     }
 
+    pub async fn save(&self) -> Result<(), Error> {
+        self.0.save().await.context("Failed to save session")
+    }
+
     pub async fn insert_user_id(&self, user_id: Uuid) -> Result<(), Error> {
         self.0
             .insert(Self::USER_ID_KEY, user_id)
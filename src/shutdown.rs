@@ -0,0 +1,28 @@
+use tokio::signal;
+use tokio_util::sync::CancellationToken;
+
+/// Cancels `shutdown` on SIGINT or, on Unix, on SIGTERM, which is what
+/// container runtimes send before killing the process.
+pub async fn cancel_on_signal(shutdown: CancellationToken) {
+    let interrupt = async {
+        signal::ctrl_c().await.expect("Failed to listen for SIGINT");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => tracing::info!("Received SIGINT"),
+        _ = terminate => tracing::info!("Received SIGTERM"),
+    }
+
+    tracing::info!("Shutting down");
+    shutdown.cancel();
+}
@@ -1,19 +1,35 @@
 use crate::{
     app_state::AppState,
-    configuration::{ApplicationSettings, DatabaseSettings, Settings},
+    authentication::{password::PasswordHashing, password_policy::PasswordPolicy},
+    client_info::TrustedProxies,
+    configuration::{ApplicationSettings, DatabaseSettings, HealthCheckSettings, Settings},
+    domain::LocalPartRules,
     email_client::EmailClient,
+    email_events::{SuppressionThresholds, WebhookCredentials},
+    email_verification::EmailVerifier,
     request_id::RequestUuid,
-    routes::{admin, health_check, home, login, subscriptions, subscriptions_confirm},
+    routes::{
+        admin, api, health_check, home, login, subscriptions, subscriptions_confirm,
+        subscriptions_email, subscriptions_preferences, tracking, webhooks,
+    },
+    security_headers::{SecurityHeaders, SecurityHeadersLayer},
+    subscribe_protection::SubscribeProtection,
     telemetry::request_span,
 };
 use anyhow::anyhow;
-use axum::{http::Uri, serve::Serve, Router};
+use axum::{
+    extract::{connect_info::IntoMakeServiceWithConnectInfo, ConnectInfo},
+    http::Uri,
+    middleware::AddExtension,
+    serve::Serve,
+    Extension, Router,
+};
 use axum_messages::MessagesManagerLayer;
 use secrecy::{ExposeSecret, Secret};
 use sqlx::{postgres::PgPoolOptions, PgPool};
-use std::{net::SocketAddr, str::FromStr};
-use time::Duration;
+use std::{future::IntoFuture, net::SocketAddr, str::FromStr, time::Duration};
 use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
 use tower::ServiceBuilder;
 use tower_http::{
     trace::{DefaultOnRequest, DefaultOnResponse, TraceLayer},
@@ -30,10 +46,17 @@ use tower_sessions_redis_store::{
 };
 use tracing::Level;
 
+type Server = Serve<
+    IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+    AddExtension<Router, ConnectInfo<SocketAddr>>,
+>;
+
 pub struct Application {
     local_addr: SocketAddr,
-    server: Serve<Router, Router>,
+    server: Server,
+    redis_pool: RedisPool,
     redis_conn: ConnectHandle,
+    shutdown_timeout: Duration,
 }
 
 impl Application {
@@ -59,7 +82,38 @@ impl Application {
             timeout,
         );
 
+        let email_verifier = config
+            .email_verification
+            .email_verifier()
+            .expect("Failed to set up email verification");
+
+        let password_hashing = config
+            .password_hashing
+            .password_hashing()
+            .expect("Failed to set up password hashing");
+
+        let password_policy = config
+            .password_policy
+            .password_policy()
+            .expect("Failed to set up password policy");
+
+        let security_headers = config
+            .security_headers
+            .security_headers(config.environment)
+            .expect("Failed to set up security headers");
+
+        let subscribe_protection = config
+            .subscribe_protection
+            .subscribe_protection(&config.application.hmac_secret);
+
+        let webhook_credentials = config
+            .postmark_webhook
+            .credentials(config.environment)
+            .expect("Failed to set up Postmark webhook credentials");
+
         let (redis_pool, redis_conn) = get_redis_connection_pool(&config.application).await;
+        let shutdown_timeout = config.application.shutdown_timeout();
+        let trusted_proxies = TrustedProxies(config.application.trusted_proxies);
 
         let local_addr = listener
             .local_addr()
@@ -69,16 +123,29 @@ impl Application {
             listener,
             db_pool,
             email_client,
+            email_verifier,
+            config.local_part_rules,
             config.application.base_url,
             config.application.hmac_secret,
-            redis_pool,
+            redis_pool.clone(),
+            password_hashing,
+            password_policy,
+            security_headers,
+            subscribe_protection,
+            webhook_credentials,
+            config.postmark_webhook.thresholds(),
+            config.tracking.enabled,
+            config.health_check,
+            trusted_proxies,
         )
         .await;
 
         Self {
             local_addr,
             server,
+            redis_pool,
             redis_conn,
+            shutdown_timeout,
         }
     }
 
@@ -86,9 +153,27 @@ impl Application {
         self.local_addr
     }
 
-    pub async fn run_until_stopped(self) -> Result<(), anyhow::Error> {
+    /// Serves requests until `shutdown` is cancelled, then stops accepting
+    /// connections and lets open ones finish within the shutdown timeout.
+    pub async fn run_until_stopped(self, shutdown: CancellationToken) -> Result<(), anyhow::Error> {
         tracing::info!("Listening on {}", self.local_addr);
-        self.server.await?;
+
+        let server = self
+            .server
+            .with_graceful_shutdown(shutdown.clone().cancelled_owned())
+            .into_future();
+        let shutdown_timeout = self.shutdown_timeout;
+        let deadline = async {
+            shutdown.cancelled().await;
+            tokio::time::sleep(shutdown_timeout).await;
+        };
+
+        tokio::select! {
+            result = server => result?,
+            _ = deadline => tracing::warn!("Connections did not close in time. Dropping them."),
+        }
+
+        self.redis_pool.quit().await?;
         self.redis_conn.await?.map_err(|e| anyhow!(e))
     }
 }
@@ -110,35 +195,70 @@ async fn get_redis_connection_pool(config: &ApplicationSettings) -> (RedisPool,
     (pool, conn)
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn run(
     listener: TcpListener,
     db_pool: PgPool,
     email_client: EmailClient,
+    email_verifier: EmailVerifier,
+    local_part_rules: LocalPartRules,
     base_url: String,
     hmac_secret: Secret<String>,
     redis_pool: RedisPool,
-) -> Serve<Router, Router> {
+    password_hashing: PasswordHashing,
+    password_policy: PasswordPolicy,
+    security_headers: SecurityHeaders,
+    subscribe_protection: SubscribeProtection,
+    webhook_credentials: WebhookCredentials,
+    suppression_thresholds: SuppressionThresholds,
+    tracking_enabled: bool,
+    health_check: HealthCheckSettings,
+    trusted_proxies: TrustedProxies,
+) -> Server {
     let key = Key::from(hmac_secret.expose_secret().as_bytes());
+    let session_store = RedisStore::new(redis_pool.clone());
 
     let app_state = AppState {
         db_pool,
         email_client,
+        email_verifier,
+        local_part_rules,
         base_url: Uri::from_str(&base_url).expect("Failed to parse base url"),
         hmac_secret: key.clone(),
+        token_secret: hmac_secret,
+        session_store: session_store.clone(),
+        redis_pool,
+        password_hashing,
+        password_policy,
+        subscribe_protection,
+        webhook_credentials,
+        suppression_thresholds,
+        tracking_enabled,
+        health_check_timeout: health_check.timeout(),
+        check_email_provider: health_check.check_email_provider,
+        email_provider_health: health_check::CachedHealth::new(
+            health_check.email_provider_cache_ttl(),
+        ),
     };
 
     let app = Router::new()
         .merge(health_check::router())
         .merge(subscriptions::router())
         .merge(subscriptions_confirm::router())
+        .merge(subscriptions_email::router())
+        .merge(subscriptions_preferences::router())
         .merge(home::router())
         .merge(login::router())
-        .merge(admin::router())
+        .merge(tracking::router())
+        .merge(webhooks::router())
+        .merge(admin::router(app_state.db_pool.clone()))
+        .merge(api::router(app_state.db_pool.clone()))
         .with_state(app_state)
         .layer(MessagesManagerLayer)
+        .layer(Extension(trusted_proxies))
         .layer(
-            SessionManagerLayer::new(RedisStore::new(redis_pool))
-                .with_expiry(Expiry::OnInactivity(Duration::minutes(10)))
+            SessionManagerLayer::new(session_store)
+                .with_expiry(Expiry::OnInactivity(time::Duration::minutes(10)))
                 .with_private(key),
         )
         .layer(
@@ -151,7 +271,11 @@ async fn run(
                         .on_response(DefaultOnResponse::new().level(Level::INFO)),
                 )
                 .propagate_x_request_id(),
-        );
+        )
+        .layer(SecurityHeadersLayer::new(security_headers));
 
-    axum::serve(listener, app)
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
 }
@@ -0,0 +1,656 @@
+use crate::domain::SubscriberEmail;
+use anyhow::Context;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Forms older than that are rejected, so that a single rendered form cannot
+/// be replayed forever.
+const FORM_TOKEN_LIFETIME: Duration = Duration::from_secs(24 * 60 * 60);
+const CHALLENGE_LIFETIME: Duration = Duration::from_secs(60 * 60);
+
+/// Reasons for rejecting a subscription attempt.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum Rejection {
+    #[error("The honeypot field has been filled in")]
+    Honeypot,
+    #[error("The subscription form is missing, expired, forged or already submitted")]
+    InvalidForm,
+    #[error("The subscription form has been submitted too quickly")]
+    TooFast,
+    #[error("The proof of work is missing, invalid or already used")]
+    ProofOfWork,
+    #[error("Too many subscription attempts from this address, try again later")]
+    IpRateLimited,
+    #[error("Too many subscription attempts for this email, try again later")]
+    EmailRateLimited,
+    #[error("Too many confirmation emails have been sent to this email today")]
+    ConfirmationEmailCap,
+}
+
+impl Rejection {
+    pub const ALL: [Rejection; 7] = [
+        Rejection::Honeypot,
+        Rejection::InvalidForm,
+        Rejection::TooFast,
+        Rejection::ProofOfWork,
+        Rejection::IpRateLimited,
+        Rejection::EmailRateLimited,
+        Rejection::ConfirmationEmailCap,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Rejection::Honeypot => "honeypot",
+            Rejection::InvalidForm => "invalid_form",
+            Rejection::TooFast => "too_fast",
+            Rejection::ProofOfWork => "proof_of_work",
+            Rejection::IpRateLimited => "ip_rate_limited",
+            Rejection::EmailRateLimited => "email_rate_limited",
+            Rejection::ConfirmationEmailCap => "confirmation_email_cap",
+        }
+    }
+
+    fn index(&self) -> usize {
+        Rejection::ALL
+            .iter()
+            .position(|rejection| rejection == self)
+            .expect("All rejections are listed in `Rejection::ALL`")
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProtectionError {
+    #[error(transparent)]
+    Rejected(#[from] Rejection),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+/// Counts of rejected subscription attempts since the application started.
+#[derive(Debug, Default)]
+pub struct RejectionMetrics {
+    counters: [AtomicU64; Rejection::ALL.len()],
+}
+
+impl RejectionMetrics {
+    fn record(&self, rejection: Rejection) {
+        self.counters[rejection.index()].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn count(&self, rejection: Rejection) -> u64 {
+        self.counters[rejection.index()].load(Ordering::Relaxed)
+    }
+}
+
+/// A client-side puzzle making every subscription attempt cost some CPU time.
+/// Solutions are found by the subscribe form: a `solution` such that the
+/// SHA-256 of `challenge` followed by `solution` starts with
+/// `difficulty_bits` zero bits. Implementations decide how challenges are
+/// issued and tracked.
+pub trait ProofOfWork: Send + Sync {
+    fn challenge(&self) -> PowChallenge;
+
+    fn verify(&self, challenge: &str, solution: &str) -> bool;
+}
+
+#[derive(Clone, Debug)]
+pub struct PowChallenge {
+    pub challenge: String,
+    pub difficulty_bits: u32,
+}
+
+/// Stateless proof of work with signed, expiring challenges. Challenges which
+/// have been used are tracked by `SubscribeProtection::consume_form`.
+pub struct HashcashProofOfWork {
+    difficulty_bits: u32,
+    secret: Secret<String>,
+}
+
+impl HashcashProofOfWork {
+    pub fn new(difficulty_bits: u32, secret: Secret<String>) -> Self {
+        Self {
+            difficulty_bits,
+            secret,
+        }
+    }
+}
+
+impl ProofOfWork for HashcashProofOfWork {
+    fn challenge(&self) -> PowChallenge {
+        PowChallenge {
+            challenge: sign(&self.secret, &format!("{}.{}", random_hex(), now_millis())),
+            difficulty_bits: self.difficulty_bits,
+        }
+    }
+
+    fn verify(&self, challenge: &str, solution: &str) -> bool {
+        let Some(payload) = verify_signature(&self.secret, challenge) else {
+            return false;
+        };
+        let Some(issued_at) = payload
+            .rsplit_once('.')
+            .and_then(|(_, issued_at)| issued_at.parse().ok())
+        else {
+            return false;
+        };
+        if elapsed_since(issued_at) > CHALLENGE_LIFETIME {
+            return false;
+        }
+
+        let digest = Sha256::new()
+            .chain_update(challenge)
+            .chain_update(solution)
+            .finalize();
+
+        leading_zero_bits(&digest) >= self.difficulty_bits
+    }
+}
+
+fn leading_zero_bits(bytes: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in bytes {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+/// Checks run on `POST /subscriptions` before a confirmation email is sent.
+#[derive(Clone)]
+pub struct SubscribeProtection {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    secret: Secret<String>,
+    attempts_per_ip_per_hour: i64,
+    attempts_per_email_per_hour: i64,
+    confirmation_emails_per_day: i64,
+    minimum_fill_time: Duration,
+    proof_of_work: Option<Box<dyn ProofOfWork>>,
+    metrics: RejectionMetrics,
+}
+
+/// The anti-bot fields of the subscribe form.
+#[derive(Debug, Default)]
+pub struct FormFields<'a> {
+    /// Hidden from humans, so only filled in by bots.
+    pub honeypot: Option<&'a str>,
+    pub form_token: Option<&'a str>,
+    pub pow_challenge: Option<&'a str>,
+    pub pow_solution: Option<&'a str>,
+}
+
+impl SubscribeProtection {
+    pub fn new(
+        secret: Secret<String>,
+        attempts_per_ip_per_hour: i64,
+        attempts_per_email_per_hour: i64,
+        confirmation_emails_per_day: i64,
+        minimum_fill_time: Duration,
+        proof_of_work: Option<Box<dyn ProofOfWork>>,
+    ) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                secret,
+                attempts_per_ip_per_hour,
+                attempts_per_email_per_hour,
+                confirmation_emails_per_day,
+                minimum_fill_time,
+                proof_of_work,
+                metrics: RejectionMetrics::default(),
+            }),
+        }
+    }
+
+    pub fn metrics(&self) -> &RejectionMetrics {
+        &self.inner.metrics
+    }
+
+    /// Signed render time of the subscribe form, checked by `check_form`.
+    /// Made unique with a random prefix, so that it can only be used once.
+    pub fn form_token(&self) -> String {
+        sign(
+            &self.inner.secret,
+            &format!("{}.{}", random_hex(), now_millis()),
+        )
+    }
+
+    pub fn pow_challenge(&self) -> Option<PowChallenge> {
+        self.inner
+            .proof_of_work
+            .as_ref()
+            .map(|proof_of_work| proof_of_work.challenge())
+    }
+
+    /// Checks that the form has been filled in by a human: the honeypot is
+    /// empty, the form has been open long enough, and the proof of work,
+    /// if enabled, is solved.
+    pub fn check_form(&self, fields: &FormFields<'_>) -> Result<(), Rejection> {
+        if fields.honeypot.is_some_and(|value| !value.is_empty()) {
+            return Err(self.reject(Rejection::Honeypot));
+        }
+
+        if !self.inner.minimum_fill_time.is_zero() {
+            let rendered_at = fields
+                .form_token
+                .and_then(|token| verify_signature(&self.inner.secret, token))
+                .and_then(|payload| payload.rsplit_once('.'))
+                .and_then(|(_, rendered_at)| rendered_at.parse().ok())
+                .ok_or_else(|| self.reject(Rejection::InvalidForm))?;
+
+            let elapsed = elapsed_since(rendered_at);
+            if elapsed > FORM_TOKEN_LIFETIME {
+                return Err(self.reject(Rejection::InvalidForm));
+            }
+            if elapsed < self.inner.minimum_fill_time {
+                return Err(self.reject(Rejection::TooFast));
+            }
+        }
+
+        if let Some(proof_of_work) = &self.inner.proof_of_work {
+            let solved = match (fields.pow_challenge, fields.pow_solution) {
+                (Some(challenge), Some(solution)) => proof_of_work.verify(challenge, solution),
+                _ => false,
+            };
+            if !solved {
+                return Err(self.reject(Rejection::ProofOfWork));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Marks the form token and the proof of work challenge as used, so that
+    /// a form filled in once cannot be submitted again. Run after `check_form`
+    /// has accepted the form.
+    #[tracing::instrument(skip_all)]
+    pub async fn consume_form(
+        &self,
+        db_pool: &PgPool,
+        fields: &FormFields<'_>,
+    ) -> Result<(), ProtectionError> {
+        if !self.inner.minimum_fill_time.is_zero() {
+            if let Some(form_token) = fields.form_token {
+                if !claim_token(db_pool, form_token, FORM_TOKEN_LIFETIME).await? {
+                    return Err(self.reject(Rejection::InvalidForm).into());
+                }
+            }
+        }
+
+        if self.inner.proof_of_work.is_some() {
+            if let Some(challenge) = fields.pow_challenge {
+                if !claim_token(db_pool, challenge, CHALLENGE_LIFETIME).await? {
+                    return Err(self.reject(Rejection::ProofOfWork).into());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Records the attempt and checks it against the hourly per-IP and
+    /// per-email limits. Attempts are stored in Postgres, so the limits hold
+    /// across application instances.
+    #[tracing::instrument(skip(self, db_pool, email))]
+    pub async fn check_rate_limits(
+        &self,
+        db_pool: &PgPool,
+        ip_address: Option<&str>,
+        email: &SubscriberEmail,
+    ) -> Result<(), ProtectionError> {
+        let mut transaction = db_pool
+            .begin()
+            .await
+            .context("Failed to begin transaction")?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO subscribe_attempts (attempted_at, ip_address, email)
+            VALUES (now(), $1, $2)
+            "#,
+            ip_address,
+            email.canonical(),
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to record subscribe attempt")?;
+
+        let attempts = sqlx::query!(
+            r#"
+            SELECT
+                COUNT(*) FILTER (WHERE ip_address = $1) AS "per_ip!",
+                COUNT(*) FILTER (WHERE email = $2) AS "per_email!"
+            FROM subscribe_attempts
+            WHERE attempted_at > now() - interval '1 hour'
+            "#,
+            ip_address,
+            email.canonical(),
+        )
+        .fetch_one(&mut *transaction)
+        .await
+        .context("Failed to count subscribe attempts")?;
+
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit transaction")?;
+
+        if attempts.per_ip > self.inner.attempts_per_ip_per_hour {
+            return Err(self.reject(Rejection::IpRateLimited).into());
+        }
+        if attempts.per_email > self.inner.attempts_per_email_per_hour {
+            return Err(self.reject(Rejection::EmailRateLimited).into());
+        }
+
+        Ok(())
+    }
+
+    /// Checks how many confirmation emails a pending subscriber has been sent
+    /// in the last day. Every email carries a fresh token, so tokens are counted.
+    #[tracing::instrument(skip(self, transaction))]
+    pub async fn check_confirmation_email_cap(
+        &self,
+        transaction: &mut Transaction<'_, Postgres>,
+        subscriber_id: Uuid,
+    ) -> Result<(), ProtectionError> {
+        let sent = sqlx::query!(
+            r#"
+            SELECT COUNT(*) AS "count!"
+            FROM subscription_tokens
+            WHERE subscriber_id = $1 AND created_at > now() - interval '1 day'
+            "#,
+            subscriber_id,
+        )
+        .fetch_one(&mut **transaction)
+        .await
+        .context("Failed to count confirmation emails")?
+        .count;
+
+        if sent >= self.inner.confirmation_emails_per_day {
+            return Err(self.reject(Rejection::ConfirmationEmailCap).into());
+        }
+
+        Ok(())
+    }
+
+    /// Checks how many email changes a subscriber has requested in the last
+    /// day, each of which sent a confirmation email to the new address.
+    #[tracing::instrument(skip(self, transaction))]
+    pub async fn check_email_change_cap(
+        &self,
+        transaction: &mut Transaction<'_, Postgres>,
+        subscriber_id: Uuid,
+    ) -> Result<(), ProtectionError> {
+        let sent = sqlx::query!(
+            r#"
+            SELECT COUNT(*) AS "count!"
+            FROM email_changes
+            WHERE subscriber_id = $1 AND requested_at > now() - interval '1 day'
+            "#,
+            subscriber_id,
+        )
+        .fetch_one(&mut **transaction)
+        .await
+        .context("Failed to count email change confirmations")?
+        .count;
+
+        if sent >= self.inner.confirmation_emails_per_day {
+            return Err(self.reject(Rejection::ConfirmationEmailCap).into());
+        }
+
+        Ok(())
+    }
+
+    fn reject(&self, rejection: Rejection) -> Rejection {
+        tracing::warn!(reason = rejection.as_str(), "Rejected subscription attempt");
+        self.inner.metrics.record(rejection);
+        rejection
+    }
+}
+
+/// Records a token as used until it expires. Returns whether it was unused.
+async fn claim_token(
+    db_pool: &PgPool,
+    token: &str,
+    lifetime: Duration,
+) -> Result<bool, anyhow::Error> {
+    let claimed = sqlx::query!(
+        r#"
+        INSERT INTO used_form_tokens (token, expires_at)
+        VALUES ($1, now() + make_interval(secs => $2))
+        ON CONFLICT DO NOTHING
+        "#,
+        token,
+        lifetime.as_secs_f64(),
+    )
+    .execute(db_pool)
+    .await
+    .context("Failed to record used form token")?
+    .rows_affected();
+
+    Ok(claimed > 0)
+}
+
+/// Deletes used form tokens which have expired, and so are rejected anyway.
+/// Run periodically by the background worker.
+#[tracing::instrument(skip_all)]
+pub async fn delete_expired_form_tokens(db_pool: &PgPool) -> Result<u64, anyhow::Error> {
+    let deleted = sqlx::query!("DELETE FROM used_form_tokens WHERE expires_at < now()")
+        .execute(db_pool)
+        .await
+        .context("Failed to delete expired form tokens")?
+        .rows_affected();
+
+    Ok(deleted)
+}
+
+/// Deletes attempts too old to count towards any limit. Run periodically by
+/// the background worker, rather than on every attempt.
+#[tracing::instrument(skip_all)]
+pub async fn delete_expired_attempts(db_pool: &PgPool) -> Result<u64, anyhow::Error> {
+    let deleted = sqlx::query!(
+        r#"
+        DELETE FROM subscribe_attempts
+        WHERE attempted_at < now() - interval '1 day'
+        "#,
+    )
+    .execute(db_pool)
+    .await
+    .context("Failed to delete old subscribe attempts")?
+    .rows_affected();
+
+    Ok(deleted)
+}
+
+fn random_hex() -> String {
+    let mut random = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut random);
+    hex::encode(random)
+}
+
+fn now_millis() -> i128 {
+    OffsetDateTime::now_utc().unix_timestamp_nanos() / 1_000_000
+}
+
+fn elapsed_since(millis: i128) -> Duration {
+    let elapsed = (now_millis() - millis).max(0);
+    Duration::from_millis(u64::try_from(elapsed).unwrap_or(u64::MAX))
+}
+
+/// Appends an HMAC of the payload, so that it can be handed out to clients.
+fn sign(secret: &Secret<String>, payload: &str) -> String {
+    let signature = mac(secret, payload).finalize().into_bytes();
+    format!("{payload}.{}", hex::encode(signature))
+}
+
+/// Returns the payload of a value produced by `sign`, if the signature holds.
+fn verify_signature<'a>(secret: &Secret<String>, signed: &'a str) -> Option<&'a str> {
+    let (payload, signature) = signed.rsplit_once('.')?;
+    let signature = hex::decode(signature).ok()?;
+
+    mac(secret, payload)
+        .verify_slice(&signature)
+        .is_ok()
+        .then_some(payload)
+}
+
+fn mac(secret: &Secret<String>, payload: &str) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret.expose_secret().as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        leading_zero_bits, FormFields, HashcashProofOfWork, ProofOfWork, Rejection,
+        SubscribeProtection,
+    };
+    use claims::{assert_err_eq, assert_ok};
+    use secrecy::Secret;
+    use std::time::Duration;
+
+    fn secret() -> Secret<String> {
+        Secret::new("secret".to_string())
+    }
+
+    fn protection(
+        minimum_fill_time: Duration,
+        proof_of_work: Option<Box<dyn ProofOfWork>>,
+    ) -> SubscribeProtection {
+        SubscribeProtection::new(secret(), 10, 3, 3, minimum_fill_time, proof_of_work)
+    }
+
+    fn solve(challenge: &str, difficulty_bits: u32) -> String {
+        use sha2::{Digest, Sha256};
+
+        (0u64..)
+            .map(|nonce| nonce.to_string())
+            .find(|nonce| {
+                let digest = Sha256::new()
+                    .chain_update(challenge)
+                    .chain_update(nonce)
+                    .finalize();
+                leading_zero_bits(&digest) >= difficulty_bits
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn leading_zero_bits_are_counted_across_bytes() {
+        assert_eq!(leading_zero_bits(&[0x00, 0x00, 0x80]), 16);
+        assert_eq!(leading_zero_bits(&[0x00, 0x0f, 0x00]), 12);
+        assert_eq!(leading_zero_bits(&[0xff]), 0);
+    }
+
+    #[test]
+    fn filled_in_honeypot_is_rejected_and_counted() {
+        // given
+        let protection = protection(Duration::ZERO, None);
+        let fields = FormFields {
+            honeypot: Some("https://spam.example.com"),
+            ..Default::default()
+        };
+
+        // when
+        let result = protection.check_form(&fields);
+
+        // then
+        assert_err_eq!(result, Rejection::Honeypot);
+        assert_eq!(protection.metrics().count(Rejection::Honeypot), 1);
+    }
+
+    #[test]
+    fn form_submitted_too_quickly_is_rejected() {
+        // given
+        let protection = protection(Duration::from_secs(60), None);
+        let form_token = protection.form_token();
+        let fields = FormFields {
+            form_token: Some(&form_token),
+            ..Default::default()
+        };
+
+        // when
+        let result = protection.check_form(&fields);
+
+        // then
+        assert_err_eq!(result, Rejection::TooFast);
+    }
+
+    #[test]
+    fn forged_form_token_is_rejected() {
+        // given
+        let protection = protection(Duration::from_millis(1), None);
+        let fields = FormFields {
+            form_token: Some("0.deadbeef"),
+            ..Default::default()
+        };
+
+        // when
+        let result = protection.check_form(&fields);
+
+        // then
+        assert_err_eq!(result, Rejection::InvalidForm);
+    }
+
+    #[test]
+    fn solved_proof_of_work_is_accepted() {
+        // given
+        let proof_of_work = HashcashProofOfWork::new(8, secret());
+        let challenge = proof_of_work.challenge();
+        let solution = solve(&challenge.challenge, challenge.difficulty_bits);
+        let protection = protection(Duration::ZERO, Some(Box::new(proof_of_work)));
+        let fields = FormFields {
+            pow_challenge: Some(&challenge.challenge),
+            pow_solution: Some(&solution),
+            ..Default::default()
+        };
+
+        // when
+        let result = protection.check_form(&fields);
+
+        // then
+        assert_ok!(result);
+    }
+
+    #[test]
+    fn missing_or_forged_proof_of_work_is_rejected() {
+        // given
+        let protection = protection(
+            Duration::ZERO,
+            Some(Box::new(HashcashProofOfWork::new(8, secret()))),
+        );
+        let forged = HashcashProofOfWork::new(0, Secret::new("other".to_string())).challenge();
+
+        // when
+        let missing = protection.check_form(&FormFields::default());
+        let forged = protection.check_form(&FormFields {
+            pow_challenge: Some(&forged.challenge),
+            pow_solution: Some("0"),
+            ..Default::default()
+        });
+
+        // then
+        assert_err_eq!(missing, Rejection::ProofOfWork);
+        assert_err_eq!(forged, Rejection::ProofOfWork);
+        assert_eq!(protection.metrics().count(Rejection::ProofOfWork), 2);
+    }
+}
@@ -0,0 +1,140 @@
+use crate::domain::{
+    LocalPartRules, NewSubscriber, SubscriberEmail, SubscriberName, SubscriptionStatus,
+};
+use anyhow::Context;
+use serde::Serialize;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// Parses CSV with `email` and `name` columns, the header row included.
+pub fn parse_csv(
+    csv: &str,
+    local_part_rules: &LocalPartRules,
+) -> Result<Vec<NewSubscriber>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(csv.as_bytes());
+
+    let headers = reader
+        .headers()
+        .map_err(|e| format!("Invalid CSV: {e}"))?
+        .clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|header| header == name)
+            .ok_or_else(|| format!("The CSV has no `{name}` column"))
+    };
+    let email_column = column("email")?;
+    let name_column = column("name")?;
+
+    reader
+        .records()
+        .map(|record| {
+            let record = record.map_err(|e| format!("Invalid CSV: {e}"))?;
+            let line = record.position().map_or(0, |position| position.line());
+            let field = |column| record.get(column).unwrap_or_default().to_string();
+            let email = SubscriberEmail::parse_with(field(email_column), local_part_rules)
+                .map_err(|e| format!("Line {line}: {e}"))?;
+            let name = SubscriberName::parse(field(name_column))
+                .map_err(|e| format!("Line {line}: {e}"))?;
+            Ok(NewSubscriber { email, name })
+        })
+        .collect()
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportSummary {
+    pub imported: u64,
+    pub skipped: u64,
+}
+
+/// Adds subscribers who have confirmed their subscription elsewhere, e.g.
+/// with a previous newsletter provider, so they get no confirmation email.
+/// Addresses which are already subscribed, in any status, are skipped.
+#[tracing::instrument(skip_all, fields(subscribers = subscribers.len()))]
+pub async fn import_subscribers(
+    db_pool: &PgPool,
+    subscribers: &[NewSubscriber],
+) -> Result<ImportSummary, anyhow::Error> {
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to begin transaction")?;
+    let mut summary = ImportSummary::default();
+
+    for subscriber in subscribers {
+        let inserted = sqlx::query!(
+            r#"
+            INSERT INTO subscriptions (id, email, canonical_email, name, subscribed_at, status)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT DO NOTHING
+            "#,
+            Uuid::new_v4(),
+            subscriber.email.as_ref(),
+            subscriber.email.canonical(),
+            subscriber.name.as_ref(),
+            OffsetDateTime::now_utc(),
+            SubscriptionStatus::Confirmed.as_ref(),
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to insert imported subscriber")?
+        .rows_affected();
+
+        if inserted > 0 {
+            summary.imported += 1;
+        } else {
+            summary.skipped += 1;
+        }
+    }
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit transaction")?;
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_csv;
+    use crate::domain::LocalPartRules;
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn csv_rows_are_parsed_with_the_local_part_rules() {
+        let csv = "name,email\nImie Nazwisko,Imie.Nazwisko@Example.com\n";
+
+        let subscribers = assert_ok!(parse_csv(csv, &LocalPartRules::default()));
+
+        assert_eq!(subscribers.len(), 1);
+        assert_eq!(subscribers[0].name.as_ref(), "Imie Nazwisko");
+        assert_eq!(
+            subscribers[0].email.canonical(),
+            "imie.nazwisko@example.com"
+        );
+    }
+
+    #[test]
+    fn csv_errors_point_at_the_line() {
+        let csv = "email,name\nimie@example.com,Imie\nnot an email,Nazwisko\n";
+
+        let error = assert_err!(parse_csv(csv, &LocalPartRules::default()));
+
+        assert!(error.starts_with("Line 3:"), "{error}");
+    }
+
+    #[test]
+    fn csv_without_name_column_is_rejected() {
+        let error = assert_err!(parse_csv(
+            "email\nimie@example.com\n",
+            &LocalPartRules::default()
+        ));
+
+        assert!(error.contains("`name`"), "{error}");
+    }
+}
@@ -0,0 +1,323 @@
+use crate::domain::SubscriberEmail;
+use anyhow::Context;
+use sqlx::{PgExecutor, PgPool};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// A single address, or every address of a domain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SuppressionKind {
+    Email,
+    Domain,
+}
+
+impl AsRef<str> for SuppressionKind {
+    fn as_ref(&self) -> &'static str {
+        match self {
+            SuppressionKind::Email => "email",
+            SuppressionKind::Domain => "domain",
+        }
+    }
+}
+
+/// How an entry got on the list.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SuppressionSource {
+    Admin,
+    CsvImport,
+}
+
+impl AsRef<str> for SuppressionSource {
+    fn as_ref(&self) -> &'static str {
+        match self {
+            SuppressionSource::Admin => "admin",
+            SuppressionSource::CsvImport => "csv_import",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct SuppressionEntry {
+    kind: SuppressionKind,
+    value: String,
+}
+
+impl SuppressionEntry {
+    /// Parses an email address, or a domain given either as is or as
+    /// `@domain`. Both are stored lowercase, domains punycode-encoded.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+
+        match s.strip_prefix('@') {
+            None if s.contains('@') => {
+                let email = SubscriberEmail::parse(s.to_string())?.normalise()?;
+                Ok(Self {
+                    kind: SuppressionKind::Email,
+                    value: email.as_ref().to_lowercase(),
+                })
+            }
+            domain => {
+                let domain = domain.unwrap_or(s);
+                let value = idna::domain_to_ascii(domain)
+                    .ok()
+                    .filter(|value| {
+                        value.contains('.')
+                            && value
+                                .chars()
+                                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+                    })
+                    .ok_or_else(|| format!("`{s}` is neither an email address nor a domain"))?;
+                Ok(Self {
+                    kind: SuppressionKind::Domain,
+                    value,
+                })
+            }
+        }
+    }
+
+    pub fn kind(&self) -> SuppressionKind {
+        self.kind
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+/// Parses CSV with `entry` and `reason` columns, the header row included.
+/// Rows without a reason get the default one.
+pub fn parse_csv(
+    csv: &str,
+    default_reason: &str,
+) -> Result<Vec<(SuppressionEntry, String)>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(csv.as_bytes());
+
+    let headers = reader
+        .headers()
+        .map_err(|e| format!("Invalid CSV: {e}"))?
+        .clone();
+    let column = |name: &str| headers.iter().position(|header| header == name);
+    let entry_column = column("entry").ok_or("The CSV has no `entry` column")?;
+    let reason_column = column("reason");
+
+    reader
+        .records()
+        .map(|record| {
+            let record = record.map_err(|e| format!("Invalid CSV: {e}"))?;
+            let line = record.position().map_or(0, |position| position.line());
+            let entry = SuppressionEntry::parse(record.get(entry_column).unwrap_or_default())
+                .map_err(|e| format!("Line {line}: {e}"))?;
+            let reason = reason_column
+                .and_then(|column| record.get(column))
+                .filter(|reason| !reason.is_empty())
+                .unwrap_or(default_reason);
+            Ok((entry, reason.to_string()))
+        })
+        .collect()
+}
+
+pub struct Suppression {
+    pub suppression_id: Uuid,
+    pub kind: String,
+    pub value: String,
+    pub reason: String,
+    pub source: String,
+    pub created_at: OffsetDateTime,
+    pub skipped_sends: i32,
+    pub last_skipped_at: Option<OffsetDateTime>,
+}
+
+#[tracing::instrument(skip(db_pool))]
+pub async fn list_suppressions(db_pool: &PgPool) -> Result<Vec<Suppression>, anyhow::Error> {
+    sqlx::query_as!(
+        Suppression,
+        r#"
+        SELECT
+            suppression_id,
+            kind,
+            value,
+            reason,
+            source,
+            created_at,
+            skipped_sends,
+            last_skipped_at
+        FROM suppressions
+        ORDER BY kind, value
+        "#,
+    )
+    .fetch_all(db_pool)
+    .await
+    .context("Failed to retrieve suppressions")
+}
+
+/// Adds an entry, returning `false` if it is on the list already.
+#[tracing::instrument(skip(executor, reason))]
+pub async fn add_suppression<'e>(
+    executor: impl PgExecutor<'e>,
+    entry: &SuppressionEntry,
+    reason: &str,
+    source: SuppressionSource,
+) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO suppressions (suppression_id, kind, value, reason, source, created_at)
+        VALUES ($1, $2, $3, $4, $5, now())
+        ON CONFLICT (kind, value) DO NOTHING
+        "#,
+        Uuid::new_v4(),
+        entry.kind.as_ref(),
+        entry.value,
+        reason,
+        source.as_ref(),
+    )
+    .execute(executor)
+    .await
+    .context("Failed to add suppression")?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+#[tracing::instrument(skip(db_pool))]
+pub async fn remove_suppression(
+    db_pool: &PgPool,
+    suppression_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        "DELETE FROM suppressions WHERE suppression_id = $1",
+        suppression_id
+    )
+    .execute(db_pool)
+    .await
+    .context("Failed to remove suppression")?;
+
+    Ok(())
+}
+
+/// Returns the reason an address is suppressed for, if it is, counting the
+/// send that is going to be skipped. Every send path checks it before
+/// handing an email over to the email client.
+#[tracing::instrument(skip(executor, email, canonical_email))]
+pub(crate) async fn check_suppression<'e>(
+    executor: impl PgExecutor<'e>,
+    email: &str,
+    canonical_email: &str,
+) -> Result<Option<String>, anyhow::Error> {
+    let reasons = sqlx::query!(
+        r#"
+        UPDATE suppressions
+        SET skipped_sends = skipped_sends + 1, last_skipped_at = now()
+        WHERE (kind = 'email' AND value IN (lower($1), lower($2)))
+            OR (kind = 'domain' AND value = split_part(lower($1), '@', 2))
+        RETURNING reason
+        "#,
+        email,
+        canonical_email,
+    )
+    .fetch_all(executor)
+    .await
+    .context("Failed to check the suppression list")?;
+
+    Ok(reasons.into_iter().next().map(|row| row.reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_csv, SuppressionEntry, SuppressionKind};
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn email_entries_are_lowercased() {
+        // given
+        let s = " Imie.Nazwisko@Example.COM ";
+
+        // when
+        let result = SuppressionEntry::parse(s);
+
+        // then
+        let entry = assert_ok!(result);
+        assert_eq!(entry.kind(), SuppressionKind::Email);
+        assert_eq!(entry.value(), "imie.nazwisko@example.com");
+    }
+
+    #[test]
+    fn domains_are_accepted_with_or_without_at_sign() {
+        for s in ["example.com", "@Example.com"] {
+            // when
+            let result = SuppressionEntry::parse(s);
+
+            // then
+            let entry = assert_ok!(result);
+            assert_eq!(entry.kind(), SuppressionKind::Domain);
+            assert_eq!(entry.value(), "example.com");
+        }
+    }
+
+    #[test]
+    fn internationalised_domains_are_punycode_encoded() {
+        // given
+        let s = "żółw.pl";
+
+        // when
+        let result = SuppressionEntry::parse(s);
+
+        // then
+        let entry = assert_ok!(result);
+        assert!(entry.value().starts_with("xn--"));
+        assert!(entry.value().ends_with(".pl"));
+    }
+
+    #[test]
+    fn invalid_entries_are_rejected() {
+        for s in ["", "localhost", "imie@", "@", "a@b@example.com"] {
+            // when
+            let result = SuppressionEntry::parse(s);
+
+            // then
+            assert_err!(result, "{s}");
+        }
+    }
+
+    #[test]
+    fn csv_rows_without_reason_get_the_default_one() {
+        // given
+        let csv = "entry,reason\nimie@example.com,Asked by phone\nexample.org,\n";
+
+        // when
+        let result = parse_csv(csv, "Imported");
+
+        // then
+        let entries = assert_ok!(result);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].1, "Asked by phone");
+        assert_eq!(entries[1].0.kind(), SuppressionKind::Domain);
+        assert_eq!(entries[1].1, "Imported");
+    }
+
+    #[test]
+    fn csv_errors_point_at_the_line() {
+        // given
+        let csv = "entry,reason\nimie@example.com,\nnot an entry,\n";
+
+        // when
+        let result = parse_csv(csv, "Imported");
+
+        // then
+        let error = assert_err!(result);
+        assert!(error.starts_with("Line 3:"), "{error}");
+    }
+
+    #[test]
+    fn csv_without_entry_column_is_rejected() {
+        // given
+        let csv = "email,reason\nimie@example.com,\n";
+
+        // when
+        let result = parse_csv(csv, "Imported");
+
+        // then
+        assert_err!(result);
+    }
+}
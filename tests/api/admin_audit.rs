@@ -0,0 +1,152 @@
+use crate::helpers::{assert_redirect_to, TestApp};
+use serde_json::json;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn login_is_required_to_access_audit_log() {
+    // given
+    let app = TestApp::spawn().await;
+
+    // when
+    let response = app.get_audit("").await;
+
+    // then
+    assert_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn logins_are_audited_with_request_details() {
+    // given
+    let app = TestApp::spawn().await;
+    let response = app
+        .log_in(&app.test_user.username, &Uuid::new_v4().to_string())
+        .await;
+    assert_redirect_to(&response, "/login");
+
+    // when
+    let response = app
+        .log_in(&app.test_user.username, &app.test_user.password)
+        .await;
+    assert_redirect_to(&response, "/admin/dashboard");
+
+    // then
+    let html_page = app.get_audit_html("").await;
+    assert!(html_page.contains("<td>login_failed</td>"));
+    assert!(html_page.contains("<td>login_succeeded</td>"));
+
+    let saved = sqlx::query!(
+        r#"
+        SELECT actor_user_id, request_id, ip_address, payload
+        FROM audit_events
+        WHERE event_type = 'login_succeeded'
+        "#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("Failed to fetch audit event");
+    assert_eq!(saved.actor_user_id, Some(app.test_user.user_id));
+    assert!(saved.request_id.is_some());
+    assert_eq!(saved.ip_address.as_deref(), Some("127.0.0.1"));
+    assert_eq!(saved.payload, json!({ "username": app.test_user.username }));
+}
+
+#[tokio::test]
+async fn audit_log_can_be_filtered_by_event_type() {
+    // given
+    let app = TestApp::spawn().await;
+    app.log_in(&app.test_user.username, &Uuid::new_v4().to_string())
+        .await;
+    app.log_in(&app.test_user.username, &app.test_user.password)
+        .await;
+
+    // when
+    let html_page = app.get_audit_html("event_type=login_failed").await;
+
+    // then
+    assert!(html_page.contains("<td>login_failed</td>"));
+    assert!(!html_page.contains("<td>login_succeeded</td>"));
+}
+
+#[tokio::test]
+async fn audit_log_can_be_exported_as_csv() {
+    // given
+    let app = TestApp::spawn().await;
+    app.log_in(&app.test_user.username, &app.test_user.password)
+        .await;
+    let new_password = Uuid::new_v4().to_string();
+    let response = app
+        .post_change_password(&json!({
+            "current_password": &app.test_user.password,
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+    assert_redirect_to(&response, "/admin/password");
+
+    // when
+    let response = app.get_audit_export("event_type=password_changed").await;
+
+    // then
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.headers()["content-type"],
+        "text/csv; charset=utf-8"
+    );
+
+    let csv = response.text().await.unwrap();
+    let lines: Vec<_> = csv.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("audit_event_id,occurred_at,event_type"));
+    assert!(lines[1].contains(",password_changed,"));
+    assert!(lines[1].contains(&app.test_user.username));
+}
+
+#[tokio::test]
+async fn exported_cells_are_not_evaluated_as_formulas() {
+    // given
+    let app = TestApp::spawn().await;
+    app.log_in(&app.test_user.username, &app.test_user.password)
+        .await;
+    sqlx::query!(
+        r#"
+        INSERT INTO audit_events (
+            audit_event_id,
+            occurred_at,
+            event_type,
+            request_id,
+            ip_address,
+            payload
+        )
+        VALUES ($1, now(), 'login_failed', '=1+2', '@SUM(A1:A2)', '{}')
+        "#,
+        Uuid::new_v4(),
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to store audit event");
+
+    // when
+    let response = app.get_audit_export("event_type=login_failed").await;
+
+    // then
+    let csv = response.text().await.unwrap();
+    let lines: Vec<_> = csv.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[1].ends_with(",'=1+2,'@SUM(A1:A2),{}"));
+}
+
+#[tokio::test]
+async fn audit_events_cannot_be_modified() {
+    // given
+    let app = TestApp::spawn().await;
+    app.log_in(&app.test_user.username, &app.test_user.password)
+        .await;
+
+    // when
+    let result = sqlx::query!("DELETE FROM audit_events")
+        .execute(&app.db_pool)
+        .await;
+
+    // then
+    assert!(result.is_err());
+}
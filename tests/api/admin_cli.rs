@@ -0,0 +1,142 @@
+use crate::helpers::{
+    assert_redirect_to, create_confirmed_subscriber, when_sending_an_email, TestApp,
+};
+use argon2::Params;
+use claims::{assert_err, assert_ok, assert_some};
+use secrecy::Secret;
+use serde_json::json;
+use uuid::Uuid;
+use wiremock::ResponseTemplate;
+use zero2prod::{
+    authentication::password::{create_user, PasswordHashing},
+    delivery_queue::{
+        delivery_stats, list_queued_tasks, purge_queued_tasks, requeue_failed_deliveries,
+    },
+    domain::LocalPartRules,
+    subscriber_import::{import_subscribers, parse_csv},
+};
+
+fn password_hashing() -> PasswordHashing {
+    PasswordHashing::new(Params::new(15000, 2, 1, None).unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn a_created_user_can_log_in() {
+    // given
+    let app = TestApp::spawn().await;
+    let username = Uuid::new_v4().to_string();
+    let password = "correct-horse-battery-staple-7".to_string();
+
+    // when
+    assert_ok!(
+        create_user(
+            &app.db_pool,
+            &password_hashing(),
+            &username,
+            Secret::new(password.clone()),
+        )
+        .await
+    );
+
+    // then
+    let response = app
+        .post_login(&json!({ "username": username, "password": password }))
+        .await;
+    assert_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn creating_a_user_with_a_taken_username_fails() {
+    // given
+    let app = TestApp::spawn().await;
+
+    // when
+    let result = create_user(
+        &app.db_pool,
+        &password_hashing(),
+        &app.test_user.username,
+        Secret::new(Uuid::new_v4().to_string()),
+    )
+    .await;
+
+    // then
+    assert_err!(result);
+}
+
+#[tokio::test]
+async fn requeued_failed_deliveries_are_sent_again() {
+    // given
+    let app = TestApp::spawn().await;
+    create_confirmed_subscriber(&app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let issue_id = app.publish_issue("Newsletter Title").await;
+    app.dispatch_all_pending_emails().await;
+
+    // when
+    let requeued = requeue_failed_deliveries(&app.db_pool, issue_id)
+        .await
+        .unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    // then
+    assert_eq!(requeued, 1);
+    let stats = assert_some!(delivery_stats(&app.db_pool, issue_id).await.unwrap());
+    assert_eq!(stats.sent, 1);
+    assert_eq!(stats.failed, 0);
+}
+
+#[tokio::test]
+async fn purging_an_issue_leaves_the_tasks_of_other_issues_queued() {
+    // given
+    let app = TestApp::spawn().await;
+    create_confirmed_subscriber(&app).await;
+    let purged_issue_id = app.publish_issue("Newsletter Title").await;
+    let kept_issue_id = app.publish_issue("Newsletter Title").await;
+
+    // when
+    let purged = purge_queued_tasks(&app.db_pool, Some(purged_issue_id))
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(purged, 1);
+    let tasks = list_queued_tasks(&app.db_pool, None, 100).await.unwrap();
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(tasks[0].issue_id, kept_issue_id);
+}
+
+#[tokio::test]
+async fn imported_subscribers_are_confirmed_and_existing_ones_skipped() {
+    // given
+    let app = TestApp::spawn().await;
+    let csv = "email,name\nimie@example.com,Imie\nnazwisko@example.com,Nazwisko\n";
+    let subscribers = parse_csv(csv, &LocalPartRules::default()).unwrap();
+    import_subscribers(&app.db_pool, &subscribers[..1])
+        .await
+        .unwrap();
+
+    // when
+    let summary = import_subscribers(&app.db_pool, &subscribers)
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(summary.imported, 1);
+    assert_eq!(summary.skipped, 1);
+    let statuses = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(statuses.len(), 2);
+    assert!(statuses.iter().all(|row| row.status == "confirmed"));
+}
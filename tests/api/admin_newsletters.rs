@@ -1,14 +1,13 @@
-use crate::helpers::{assert_redirect_to, ConfirmationLinks, TestApp};
-use fake::{
-    faker::{internet::en::SafeEmail, name::en::Name},
-    Fake,
+use crate::helpers::{
+    assert_redirect_to, create_confirmed_subscriber, create_unconfirmed_subscriber,
+    when_sending_an_email, TestApp,
 };
 use serde_json::json;
 use std::time::Duration;
 use uuid::Uuid;
 use wiremock::{
-    matchers::{any, method, path},
-    Mock, MockBuilder, ResponseTemplate,
+    matchers::{any, method},
+    Mock, ResponseTemplate,
 };
 
 #[tokio::test]
@@ -236,46 +235,3 @@ async fn concurrent_form_submission_is_handled_gracefully() {
 
     app.dispatch_all_pending_emails().await;
 }
-
-async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
-    let name: String = Name().fake();
-    let email: String = SafeEmail().fake();
-    let body = serde_urlencoded::to_string(json!({
-        "name": name,
-        "email": email,
-    }))
-    .unwrap();
-
-    let _mock_guard_ = when_sending_an_email()
-        .respond_with(ResponseTemplate::new(200))
-        .expect(1)
-        .mount_as_scoped(&app.email_server)
-        .await;
-
-    app.post_subscriptions(body)
-        .await
-        .error_for_status()
-        .unwrap();
-
-    app.get_confirmation_links(
-        &app.email_server
-            .received_requests()
-            .await
-            .unwrap()
-            .pop()
-            .unwrap(),
-    )
-}
-
-async fn create_confirmed_subscriber(app: &TestApp) {
-    let links = create_unconfirmed_subscriber(app).await;
-    reqwest::get(links.html)
-        .await
-        .unwrap()
-        .error_for_status()
-        .unwrap();
-}
-
-fn when_sending_an_email() -> MockBuilder {
-    Mock::given(path("/email")).and(method("POST"))
-}
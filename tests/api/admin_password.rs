@@ -183,7 +183,63 @@ async fn too_long_password_is_rejected() {
 
     // then
     let html_page = app.get_change_password_html().await;
-    assert!(html_page.contains("<p><i>Passwords must be at most 128 characters long.</i></p>"));
+    assert!(html_page.contains("<p><i>Password must be at most 128 characters long.</i></p>"));
+}
+
+#[tokio::test]
+async fn password_containing_username_is_rejected() {
+    // given
+    let app = TestApp::spawn().await;
+    let new_password = format!("Pre-{}-fix!", app.test_user.username.to_uppercase());
+    let body = json!({
+        "current_password": &app.test_user.password,
+        "new_password": new_password,
+        "new_password_check": new_password,
+    });
+
+    let response = app
+        .post_login(&json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_redirect_to(&response, "/admin/dashboard");
+
+    // when
+    let response = app.post_change_password(&body).await;
+    assert_redirect_to(&response, "/admin/password");
+
+    // then
+    let html_page = app.get_change_password_html().await;
+    assert!(html_page.contains("<p><i>Password must not contain your username.</i></p>"));
+}
+
+#[tokio::test]
+async fn weak_password_is_rejected() {
+    // given
+    let app = TestApp::spawn().await;
+    let new_password = "abcdefgh12345678";
+    let body = json!({
+        "current_password": &app.test_user.password,
+        "new_password": new_password,
+        "new_password_check": new_password,
+    });
+
+    let response = app
+        .post_login(&json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_redirect_to(&response, "/admin/dashboard");
+
+    // when
+    let response = app.post_change_password(&body).await;
+    assert_redirect_to(&response, "/admin/password");
+
+    // then
+    let html_page = app.get_change_password_html().await;
+    assert!(html_page.contains("<p><i>Password is too weak"));
 }
 
 #[tokio::test]
@@ -0,0 +1,265 @@
+use crate::helpers::{
+    assert_redirect_to, create_confirmed_subscriber, when_sending_an_email, TestApp,
+};
+use serde_json::{json, Value};
+use uuid::Uuid;
+use wiremock::ResponseTemplate;
+
+/// Creates an active sequence with steps sent on the given days.
+async fn create_active_sequence(app: &TestApp, days: &[i32]) -> Uuid {
+    let response = app
+        .post_create_sequence(&json!({ "name": "Welcome series" }))
+        .await;
+    assert_redirect_to(&response, "/admin/sequences");
+    let drip_sequence_id = sqlx::query!("SELECT drip_sequence_id FROM drip_sequences")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .drip_sequence_id;
+
+    for day in days {
+        let response = app
+            .post_add_sequence_step(&step_form(drip_sequence_id, *day))
+            .await;
+        assert_redirect_to(&response, "/admin/sequences");
+    }
+
+    let response = app
+        .post_set_sequence_active(&json!({
+            "drip_sequence_id": drip_sequence_id,
+            "active": true,
+        }))
+        .await;
+    assert_redirect_to(&response, "/admin/sequences");
+
+    drip_sequence_id
+}
+
+fn step_form(drip_sequence_id: Uuid, day: i32) -> Value {
+    json!({
+        "drip_sequence_id": drip_sequence_id,
+        "delay_days": day,
+        "title": format!("Day {day}"),
+        "html_content": format!("<p>Onboarding on day {day}.</p>"),
+        "text_content": format!("Onboarding on day {day}."),
+    })
+}
+
+/// Subjects of emails sent to subscribers, other than confirmation emails.
+async fn sent_subjects(app: &TestApp) -> Vec<String> {
+    app.email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .map(|request| serde_json::from_slice::<Value>(&request.body).unwrap())
+        .map(|email| email["Subject"].as_str().unwrap().to_string())
+        .filter(|subject| subject != "Welcome!")
+        .collect()
+}
+
+#[tokio::test]
+async fn login_is_required_to_access_sequences_page() {
+    // given
+    let app = TestApp::spawn().await;
+
+    // when
+    let response = app.get_sequences().await;
+
+    // then
+    assert_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn confirmed_subscribers_receive_steps_as_they_become_due() {
+    // given
+    let app = TestApp::spawn().await;
+    app.log_in(&app.test_user.username, &app.test_user.password)
+        .await;
+    create_active_sequence(&app, &[0, 3]).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    create_confirmed_subscriber(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    // then
+    assert_eq!(sent_subjects(&app).await, ["Day 0"]);
+
+    // when
+    sqlx::query!(
+        r#"
+        UPDATE drip_enrollments
+        SET enrolled_at = enrolled_at - interval '4 days', next_send_at = now()
+        "#
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    // then
+    assert_eq!(sent_subjects(&app).await, ["Day 0", "Day 3"]);
+    let enrollment = sqlx::query!("SELECT completed_at, next_send_at FROM drip_enrollments")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(enrollment.completed_at.is_some());
+    assert!(enrollment.next_send_at.is_none());
+
+    let html_page = app.get_sequences_html().await;
+    assert!(html_page.contains("Completed: 1"));
+}
+
+#[tokio::test]
+async fn unsubscribing_stops_the_sequence() {
+    // given
+    let app = TestApp::spawn().await;
+    app.log_in(&app.test_user.username, &app.test_user.password)
+        .await;
+    create_active_sequence(&app, &[0, 3]).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    create_confirmed_subscriber(&app).await;
+    app.dispatch_all_pending_emails().await;
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+
+    // when
+    app.post_unsubscribe(&app.preferences_token(subscriber_id))
+        .await;
+    sqlx::query!("UPDATE drip_enrollments SET enrolled_at = enrolled_at - interval '4 days'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    // then
+    assert_eq!(sent_subjects(&app).await, ["Day 0"]);
+    let stopped_at = sqlx::query!("SELECT stopped_at FROM drip_enrollments")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .stopped_at;
+    assert!(stopped_at.is_some());
+}
+
+#[tokio::test]
+async fn inactive_sequences_are_not_entered() {
+    // given
+    let app = TestApp::spawn().await;
+    app.log_in(&app.test_user.username, &app.test_user.password)
+        .await;
+    let drip_sequence_id = create_active_sequence(&app, &[0]).await;
+    app.post_set_sequence_active(&json!({
+        "drip_sequence_id": drip_sequence_id,
+        "active": false,
+    }))
+    .await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    create_confirmed_subscriber(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    // then
+    assert!(sent_subjects(&app).await.is_empty());
+}
+
+#[tokio::test]
+async fn steps_must_not_come_before_the_previous_step() {
+    // given
+    let app = TestApp::spawn().await;
+    app.log_in(&app.test_user.username, &app.test_user.password)
+        .await;
+    let drip_sequence_id = create_active_sequence(&app, &[3]).await;
+
+    // when
+    let response = app
+        .post_add_sequence_step(&step_form(drip_sequence_id, 1))
+        .await;
+
+    // then
+    assert_redirect_to(&response, "/admin/sequences");
+    let html_page = app.get_sequences_html().await;
+    assert!(html_page.contains("The step must come on day 3 or later"));
+    let steps = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM drip_steps")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(steps, 1);
+}
+
+#[tokio::test]
+async fn steps_failing_to_send_are_retried_later() {
+    // given
+    let app = TestApp::spawn().await;
+    app.log_in(&app.test_user.username, &app.test_user.password)
+        .await;
+    create_active_sequence(&app, &[0]).await;
+    create_confirmed_subscriber(&app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    app.dispatch_all_pending_emails().await;
+
+    // then
+    let enrollment = sqlx::query!(
+        r#"
+        SELECT next_position, failed_attempts, next_send_at > now() AS "retried_later!"
+        FROM drip_enrollments
+        "#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(enrollment.next_position, 0);
+    assert_eq!(enrollment.failed_attempts, 1);
+    assert!(enrollment.retried_later);
+}
+
+#[tokio::test]
+async fn steps_failing_to_send_repeatedly_are_skipped() {
+    // given
+    let app = TestApp::spawn().await;
+    app.log_in(&app.test_user.username, &app.test_user.password)
+        .await;
+    create_active_sequence(&app, &[0]).await;
+    create_confirmed_subscriber(&app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    sqlx::query!("UPDATE drip_enrollments SET failed_attempts = 4")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // when
+    app.dispatch_all_pending_emails().await;
+
+    // then
+    let enrollment = sqlx::query!("SELECT completed_at, failed_attempts FROM drip_enrollments")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(enrollment.completed_at.is_some());
+    assert_eq!(enrollment.failed_attempts, 0);
+}
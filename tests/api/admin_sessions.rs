@@ -0,0 +1,95 @@
+use crate::helpers::{assert_redirect_to, TestApp};
+use serde_json::json;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn login_is_required_to_access_sessions_page() {
+    // given
+    let app = TestApp::spawn().await;
+
+    // when
+    let response = app.get_sessions().await;
+
+    // then
+    assert_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn sessions_page_lists_the_current_session() {
+    // given
+    let app = TestApp::spawn().await;
+    let response = app
+        .log_in(&app.test_user.username, &app.test_user.password)
+        .await;
+    assert_redirect_to(&response, "/admin/dashboard");
+
+    // when
+    let html_page = app.get_sessions_html().await;
+
+    // then
+    assert!(html_page.contains("<i>This session</i>"));
+}
+
+#[tokio::test]
+async fn logging_out_other_sessions_revokes_them() {
+    // given
+    let app = TestApp::spawn().await;
+    let other_client = app
+        .log_in_with_new_client(&app.test_user.username, &app.test_user.password)
+        .await;
+    let response = app.get_admin_dashboard_with(&other_client).await;
+    assert_eq!(response.status(), 200);
+
+    let response = app
+        .log_in(&app.test_user.username, &app.test_user.password)
+        .await;
+    assert_redirect_to(&response, "/admin/dashboard");
+
+    // when
+    let response = app.post_log_out_other_sessions().await;
+
+    // then
+    assert_redirect_to(&response, "/admin/sessions");
+
+    let html_page = app.get_sessions_html().await;
+    assert!(html_page.contains("<p><i>All other sessions have been logged out.</i></p>"));
+
+    let response = app.get_admin_dashboard_with(&other_client).await;
+    assert_redirect_to(&response, "/login");
+
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status(), 200);
+}
+
+#[tokio::test]
+async fn changing_password_logs_out_other_sessions() {
+    // given
+    let app = TestApp::spawn().await;
+    let new_password = Uuid::new_v4();
+    let other_client = app
+        .log_in_with_new_client(&app.test_user.username, &app.test_user.password)
+        .await;
+
+    let response = app
+        .log_in(&app.test_user.username, &app.test_user.password)
+        .await;
+    assert_redirect_to(&response, "/admin/dashboard");
+
+    // when
+    let response = app
+        .post_change_password(&json!({
+            "current_password": &app.test_user.password,
+            "new_password": new_password,
+            "new_password_check": new_password,
+        }))
+        .await;
+
+    // then
+    assert_redirect_to(&response, "/admin/password");
+
+    let response = app.get_admin_dashboard_with(&other_client).await;
+    assert_redirect_to(&response, "/login");
+
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status(), 200);
+}
@@ -0,0 +1,84 @@
+use crate::helpers::{assert_redirect_to, create_unmigrated_database, TestApp};
+use claims::{assert_err, assert_ok};
+use zero2prod::{
+    configuration::get_configuration,
+    migrations::{check_schema_version, run_migrations, schema_version, supported_schema_version},
+};
+
+async fn store_newer_migration(app: &TestApp) {
+    sqlx::query!(
+        r#"
+        INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+        VALUES ($1, 'from a newer release', true, '', 0)
+        "#,
+        supported_schema_version().unwrap() + 1,
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to store migration");
+}
+
+#[tokio::test]
+async fn login_is_required_to_access_status_page() {
+    // given
+    let app = TestApp::spawn().await;
+
+    // when
+    let response = app.get_status().await;
+
+    // then
+    assert_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn status_page_shows_the_schema_version() {
+    // given
+    let app = TestApp::spawn().await;
+    app.log_in(&app.test_user.username, &app.test_user.password)
+        .await;
+
+    // when
+    let html_page = app.get_status_html().await;
+
+    // then
+    let schema_version = supported_schema_version().unwrap();
+    assert!(html_page.contains(&format!("<td>{schema_version}</td>")));
+    assert!(!html_page.contains("pending migrations"));
+}
+
+#[tokio::test]
+async fn replicas_migrating_at_the_same_time_both_succeed() {
+    // given
+    let config = get_configuration().unwrap();
+    let db_pool = create_unmigrated_database().await;
+
+    // when
+    let (first, second) = tokio::join!(
+        run_migrations(&db_pool, &config),
+        run_migrations(&db_pool, &config)
+    );
+
+    // then
+    assert_ok!(first);
+    assert_ok!(second);
+    let mut connection = db_pool.acquire().await.unwrap();
+    assert_eq!(
+        schema_version(&mut connection).await.unwrap(),
+        supported_schema_version()
+    );
+}
+
+#[tokio::test]
+async fn a_schema_newer_than_the_binary_is_refused() {
+    // given
+    let app = TestApp::spawn().await;
+    store_newer_migration(&app).await;
+
+    // when
+    let checked = check_schema_version(&app.db_pool).await;
+    let migrated = run_migrations(&app.db_pool, &app.config()).await;
+
+    // then
+    assert_err!(checked);
+    assert_err!(migrated);
+}
@@ -0,0 +1,305 @@
+use crate::helpers::{
+    assert_redirect_to, create_confirmed_subscriber, when_sending_an_email, TestApp,
+};
+use serde_json::json;
+use uuid::Uuid;
+use wiremock::{matchers::any, Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn login_is_required_to_access_suppressions_page() {
+    // given
+    let app = TestApp::spawn().await;
+
+    // when
+    let response = app.get_suppressions().await;
+
+    // then
+    assert_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn suppressed_addresses_receive_no_confirmation_email() {
+    // given
+    let app = TestApp::spawn().await;
+    app.log_in(&app.test_user.username, &app.test_user.password)
+        .await;
+    let response = app
+        .post_add_suppression(&json!({
+            "entry": "Imie.Nazwisko@example.com",
+            "reason": "Asked us by phone",
+        }))
+        .await;
+    assert_redirect_to(&response, "/admin/suppressions");
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let response = app
+        .post_subscriptions("name=Imi%C4%99%20Nazwisko&email=imie.nazwisko%40example.com".into())
+        .await;
+
+    // then
+    assert_eq!(response.status(), 200);
+    let saved = sqlx::query!("SELECT source, skipped_sends FROM suppressions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.source, "admin");
+    assert_eq!(saved.skipped_sends, 1);
+}
+
+#[tokio::test]
+async fn issues_to_suppressed_domains_are_recorded_as_skipped() {
+    // given
+    let app = TestApp::spawn().await;
+    create_confirmed_subscriber(&app).await;
+    let email = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email;
+    let domain = email.rsplit_once('@').unwrap().1;
+    app.log_in(&app.test_user.username, &app.test_user.password)
+        .await;
+    app.post_add_suppression(&json!({
+        "entry": format!("@{domain}"),
+        "reason": "Domain no longer exists",
+    }))
+    .await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let response = app
+        .post_publish_newsletter(&json!({
+            "title": "Newsletter Title",
+            "html_content": "<p>Newsletter body as html.</p>",
+            "text_content": "Newsletter body as text.",
+            "idempotency_key": Uuid::new_v4(),
+        }))
+        .await;
+    assert_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    // then
+    let outcome = sqlx::query!("SELECT outcome FROM issue_deliveries")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .outcome;
+    assert_eq!(outcome, "skipped");
+    let html_page = app.get_suppressions_html().await;
+    assert!(html_page.contains("Domain no longer exists"));
+    assert!(html_page.contains("<td>1</td>"));
+}
+
+#[tokio::test]
+async fn suppressions_can_be_imported_from_csv() {
+    // given
+    let app = TestApp::spawn().await;
+    app.log_in(&app.test_user.username, &app.test_user.password)
+        .await;
+    app.post_add_suppression(&json!({
+        "entry": "example.org",
+        "reason": "Added by hand",
+    }))
+    .await;
+
+    // when
+    let response = app
+        .post_import_suppressions(&json!({
+            "csv": "entry,reason\nimie@example.com,Complained by email\nexample.org,\n@Example.net,\n",
+            "default_reason": "Migrated from the old list",
+        }))
+        .await;
+
+    // then
+    assert_redirect_to(&response, "/admin/suppressions");
+    let html_page = app.get_suppressions_html().await;
+    assert!(html_page.contains("2 entries have been imported, 1 were suppressed already."));
+
+    let saved = sqlx::query!("SELECT kind, value, reason, source FROM suppressions ORDER BY value")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    let saved: Vec<_> = saved
+        .iter()
+        .map(|row| (&*row.kind, &*row.value, &*row.reason, &*row.source))
+        .collect();
+    assert_eq!(
+        saved,
+        [
+            (
+                "domain",
+                "example.net",
+                "Migrated from the old list",
+                "csv_import"
+            ),
+            ("domain", "example.org", "Added by hand", "admin"),
+            (
+                "email",
+                "imie@example.com",
+                "Complained by email",
+                "csv_import"
+            ),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn csv_with_an_invalid_row_is_not_imported() {
+    // given
+    let app = TestApp::spawn().await;
+    app.log_in(&app.test_user.username, &app.test_user.password)
+        .await;
+
+    // when
+    let response = app
+        .post_import_suppressions(&json!({
+            "csv": "entry,reason\nimie@example.com,\nnot an entry,\n",
+            "default_reason": "Migrated from the old list",
+        }))
+        .await;
+
+    // then
+    assert_redirect_to(&response, "/admin/suppressions");
+    let html_page = app.get_suppressions_html().await;
+    assert!(html_page.contains("Nothing has been imported. Line 3:"));
+    let count = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM suppressions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(count, 0);
+}
+
+#[tokio::test]
+async fn removed_suppressions_no_longer_apply() {
+    // given
+    let app = TestApp::spawn().await;
+    app.log_in(&app.test_user.username, &app.test_user.password)
+        .await;
+    app.post_add_suppression(&json!({
+        "entry": "imie.nazwisko@example.com",
+        "reason": "Asked us by phone",
+    }))
+    .await;
+    let suppression_id = sqlx::query!("SELECT suppression_id FROM suppressions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .suppression_id;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let response = app
+        .post_remove_suppression(&json!({ "suppression_id": suppression_id }))
+        .await;
+    assert_redirect_to(&response, "/admin/suppressions");
+    app.post_subscriptions("name=Imi%C4%99%20Nazwisko&email=imie.nazwisko%40example.com".into())
+        .await;
+
+    // then
+    assert!(app
+        .get_suppressions_html()
+        .await
+        .contains("The suppression has been removed."));
+}
+
+#[tokio::test]
+async fn suppressed_new_address_receives_no_email_change_confirmation() {
+    // given
+    let app = TestApp::spawn().await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+    app.log_in(&app.test_user.username, &app.test_user.password)
+        .await;
+    app.post_add_suppression(&json!({
+        "entry": "nowy.adres@example.com",
+        "reason": "Asked us by phone",
+    }))
+    .await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    app.post_change_email(
+        &app.preferences_token(subscriber_id),
+        "nowy.adres@example.com",
+    )
+    .await;
+
+    // then
+    let skipped_sends = sqlx::query!("SELECT skipped_sends FROM suppressions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .skipped_sends;
+    assert_eq!(skipped_sends, 1);
+}
+
+#[tokio::test]
+async fn suppressed_old_address_is_not_notified_of_an_email_change() {
+    // given
+    let app = TestApp::spawn().await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber = sqlx::query!("SELECT id, email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    app.log_in(&app.test_user.username, &app.test_user.password)
+        .await;
+    app.post_add_suppression(&json!({
+        "entry": subscriber.email,
+        "reason": "Asked us by phone",
+    }))
+    .await;
+    // Only the confirmation sent to the new address.
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_change_email(
+        &app.preferences_token(subscriber.id),
+        "nowy.adres@example.com",
+    )
+    .await;
+    let confirmation = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let links = app.get_confirmation_links(&confirmation);
+
+    // when
+    let response = reqwest::get(links.html).await.unwrap();
+
+    // then
+    assert_eq!(response.status(), 200);
+    let skipped_sends = sqlx::query!("SELECT skipped_sends FROM suppressions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .skipped_sends;
+    assert_eq!(skipped_sends, 1);
+}
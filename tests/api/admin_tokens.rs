@@ -0,0 +1,110 @@
+use crate::helpers::{assert_redirect_to, TestApp};
+use serde_json::{json, Value};
+
+#[tokio::test]
+async fn login_is_required_to_access_tokens_page() {
+    // given
+    let app = TestApp::spawn().await;
+
+    // when
+    let response = app.get_tokens().await;
+
+    // then
+    assert_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn created_token_authenticates_api_requests() {
+    // given
+    let app = TestApp::spawn().await;
+    let token = app.create_api_token(&["issues:write"]).await;
+
+    // when
+    let response = app.get_api_me(&token).await;
+
+    // then
+    assert_eq!(response.status(), 200);
+
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["username"], json!(app.test_user.username));
+    assert_eq!(body["scopes"], json!(["issues:write"]));
+}
+
+#[tokio::test]
+async fn tokens_are_stored_hashed_and_listed_without_plaintext() {
+    // given
+    let app = TestApp::spawn().await;
+    let token = app.create_api_token(&["issues:read"]).await;
+
+    // when
+    let html_page = app.get_tokens_html().await;
+
+    // then
+    assert!(html_page.contains("test token"));
+    assert!(!html_page.contains(&token));
+
+    let saved = sqlx::query!("SELECT token_hash FROM api_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved token");
+    assert_ne!(saved.token_hash, token);
+}
+
+#[tokio::test]
+async fn token_without_scopes_is_rejected() {
+    // given
+    let app = TestApp::spawn().await;
+    let response = app
+        .log_in(&app.test_user.username, &app.test_user.password)
+        .await;
+    assert_redirect_to(&response, "/admin/dashboard");
+
+    // when
+    let response = app.post_create_token(&json!({ "name": "ci" })).await;
+
+    // then
+    assert_redirect_to(&response, "/admin/tokens");
+
+    let html_page = app.get_tokens_html().await;
+    assert!(html_page.contains("<p><i>Select at least one scope for the token.</i></p>"));
+}
+
+#[tokio::test]
+async fn revoked_token_is_rejected() {
+    // given
+    let app = TestApp::spawn().await;
+    let token = app.create_api_token(&["issues:read"]).await;
+    let saved = sqlx::query!("SELECT api_token_id FROM api_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved token");
+
+    // when
+    let response = app
+        .post_revoke_token(&json!({ "api_token_id": saved.api_token_id }))
+        .await;
+
+    // then
+    assert_redirect_to(&response, "/admin/tokens");
+
+    let html_page = app.get_tokens_html().await;
+    assert!(html_page.contains("<p><i>The token has been revoked.</i></p>"));
+
+    let response = app.get_api_me(&token).await;
+    assert_eq!(response.status(), 401);
+}
+
+#[tokio::test]
+async fn api_requests_without_a_valid_token_are_rejected() {
+    // given
+    let app = TestApp::spawn().await;
+
+    for token in ["", "z2p_invalid"] {
+        // when
+        let response = app.get_api_me(token).await;
+
+        // then
+        assert_eq!(response.status(), 401);
+        assert_eq!(response.headers()["www-authenticate"], "Bearer");
+    }
+}
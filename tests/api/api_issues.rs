@@ -0,0 +1,156 @@
+use crate::helpers::{assert_problem, create_confirmed_subscriber, when_sending_an_email, TestApp};
+use serde_json::{json, Value};
+use uuid::Uuid;
+use wiremock::ResponseTemplate;
+
+fn issue_body() -> Value {
+    json!({
+        "title": "Newsletter Title",
+        "html_content": "<p>Newsletter body as html.</p>",
+        "text_content": "Newsletter body as text.",
+    })
+}
+
+#[tokio::test]
+async fn created_issue_is_delivered_and_counted_in_stats() {
+    // given
+    let app = TestApp::spawn().await;
+    create_confirmed_subscriber(&app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let token = app.create_api_token(&["issues:read", "issues:write"]).await;
+
+    // when
+    let response = app
+        .post_api_issue(&token, &Uuid::new_v4().to_string(), &issue_body())
+        .await;
+
+    // then
+    assert_eq!(response.status(), 201);
+    let issue: Value = response.json().await.unwrap();
+    let issue_id = issue["issue_id"].as_str().unwrap();
+    assert_eq!(issue["title"], "Newsletter Title");
+
+    let response = app
+        .get_api(&format!("/api/v1/issues/{issue_id}/stats"), &token)
+        .await;
+    let stats: Value = response.json().await.unwrap();
+    assert_eq!(stats["pending"], 1);
+    assert_eq!(stats["sent"], 0);
+
+    app.dispatch_all_pending_emails().await;
+
+    let response = app
+        .get_api(&format!("/api/v1/issues/{issue_id}/stats"), &token)
+        .await;
+    let stats: Value = response.json().await.unwrap();
+    assert_eq!(stats["pending"], 0);
+    assert_eq!(stats["sent"], 1);
+
+    let response = app.get_api("/api/v1/issues", &token).await;
+    let list: Value = response.json().await.unwrap();
+    assert_eq!(list["issues"][0]["issue_id"], issue_id);
+}
+
+#[tokio::test]
+async fn creating_an_issue_is_idempotent() {
+    // given
+    let app = TestApp::spawn().await;
+    let token = app.create_api_token(&["issues:write"]).await;
+    let idempotency_key = Uuid::new_v4().to_string();
+
+    // when
+    let response1 = app
+        .post_api_issue(&token, &idempotency_key, &issue_body())
+        .await;
+    let response2 = app
+        .post_api_issue(&token, &idempotency_key, &issue_body())
+        .await;
+
+    // then
+    assert_eq!(response1.status(), 201);
+    assert_eq!(response2.status(), 201);
+    assert_eq!(
+        response1.json::<Value>().await.unwrap(),
+        response2.json::<Value>().await.unwrap()
+    );
+
+    let saved = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to count saved issues");
+    assert_eq!(saved.count, 1);
+}
+
+#[tokio::test]
+async fn creating_an_issue_requires_an_idempotency_key() {
+    // given
+    let app = TestApp::spawn().await;
+    let token = app.create_api_token(&["issues:write"]).await;
+
+    // when
+    let response = app.post_api("/api/v1/issues", &token, &issue_body()).await;
+
+    // then
+    assert_problem(&response, 400);
+    let problem: Value = response.json().await.unwrap();
+    assert_eq!(problem["status"], 400);
+    assert_eq!(problem["detail"], "The `Idempotency-Key` header is missing");
+}
+
+#[tokio::test]
+async fn invalid_issues_are_rejected_with_problem_details() {
+    // given
+    let app = TestApp::spawn().await;
+    let token = app.create_api_token(&["issues:write"]).await;
+    let test_cases = [
+        json!({ "title": "Newsletter Title" }),
+        json!({ "title": "", "html_content": "<p>body</p>", "text_content": "body" }),
+    ];
+
+    for body in test_cases {
+        // when
+        let response = app
+            .post_api_issue(&token, &Uuid::new_v4().to_string(), &body)
+            .await;
+
+        // then
+        assert_problem(&response, 400);
+    }
+}
+
+#[tokio::test]
+async fn missing_scope_is_rejected_with_problem_details() {
+    // given
+    let app = TestApp::spawn().await;
+    let token = app.create_api_token(&["subscribers:read"]).await;
+
+    // when
+    let response = app.get_api("/api/v1/issues", &token).await;
+
+    // then
+    assert_problem(&response, 403);
+    let problem: Value = response.json().await.unwrap();
+    assert_eq!(
+        problem["detail"],
+        "This operation requires the `issues:read` scope"
+    );
+}
+
+#[tokio::test]
+async fn unknown_issue_is_not_found() {
+    // given
+    let app = TestApp::spawn().await;
+    let token = app.create_api_token(&["issues:read"]).await;
+
+    // when
+    let response = app
+        .get_api(&format!("/api/v1/issues/{}", Uuid::new_v4()), &token)
+        .await;
+
+    // then
+    assert_problem(&response, 404);
+}
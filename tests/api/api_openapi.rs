@@ -0,0 +1,181 @@
+use crate::helpers::TestApp;
+use reqwest::{header::CONTENT_TYPE, Method};
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn openapi_document_is_served_without_authentication() {
+    // given
+    let app = TestApp::spawn().await;
+
+    // when
+    let response = app.get_openapi_document().await;
+
+    // then
+    assert_eq!(response.status(), 200);
+
+    let document: Value = response.json().await.unwrap();
+    assert!(document["openapi"].as_str().unwrap().starts_with("3.1"));
+    assert!(document["components"]["securitySchemes"]["bearer_token"].is_object());
+}
+
+#[tokio::test]
+async fn document_lists_every_operation_of_the_api() {
+    // given
+    let app = TestApp::spawn().await;
+
+    // when
+    let document: Value = app.get_openapi_document().await.json().await.unwrap();
+
+    // then
+    let operations: BTreeSet<(String, String)> = document["paths"]
+        .as_object()
+        .unwrap()
+        .iter()
+        .flat_map(|(path, operations)| {
+            operations
+                .as_object()
+                .unwrap()
+                .keys()
+                .map(move |method| (method.clone(), path.clone()))
+        })
+        .collect();
+    let expected: BTreeSet<(String, String)> = [
+        ("get", "/api/v1/me"),
+        ("get", "/api/v1/issues"),
+        ("post", "/api/v1/issues"),
+        ("get", "/api/v1/issues/{issue_id}"),
+        ("get", "/api/v1/issues/{issue_id}/stats"),
+        ("get", "/api/v1/subscribers"),
+        ("post", "/api/v1/subscribers"),
+        ("delete", "/api/v1/subscribers/{subscriber_id}"),
+        ("post", "/api/v1/subscribers/{subscriber_id}/email"),
+    ]
+    .map(|(method, path)| (method.to_string(), path.to_string()))
+    .into();
+    assert_eq!(operations, expected);
+}
+
+#[tokio::test]
+async fn document_describes_bodies_and_scopes_of_operations() {
+    // given
+    let app = TestApp::spawn().await;
+
+    // when
+    let document: Value = app.get_openapi_document().await.json().await.unwrap();
+
+    // then
+    let create_issue = &document["paths"]["/api/v1/issues"]["post"];
+    assert_eq!(
+        create_issue["requestBody"]["content"]["application/json"]["schema"]["$ref"],
+        "#/components/schemas/NewIssue"
+    );
+    assert_eq!(
+        create_issue["responses"]["201"]["content"]["application/json"]["schema"]["$ref"],
+        "#/components/schemas/Issue"
+    );
+    assert_eq!(
+        create_issue["responses"]["400"]["content"]["application/problem+json"]["schema"]["$ref"],
+        "#/components/schemas/Problem"
+    );
+    assert_eq!(
+        create_issue["security"],
+        json!([{ "bearer_token": ["issues:write"] }])
+    );
+
+    let schemas = &document["components"]["schemas"];
+    assert_eq!(
+        required_fields(&schemas["NewIssue"]),
+        ["html_content", "text_content", "title"]
+    );
+    assert_eq!(
+        required_fields(&schemas["NewSubscriberBody"]),
+        ["email", "name"]
+    );
+    assert_eq!(
+        required_fields(&schemas["Subscriber"]),
+        ["email", "name", "status", "subscribed_at", "subscriber_id"]
+    );
+    let problem_fields: BTreeSet<&str> = schemas["Problem"]["properties"]
+        .as_object()
+        .unwrap()
+        .keys()
+        .map(String::as_str)
+        .collect();
+    assert_eq!(
+        problem_fields,
+        BTreeSet::from(["type", "title", "status", "detail"])
+    );
+}
+
+/// Required properties of a schema, sorted.
+fn required_fields(schema: &Value) -> Vec<&str> {
+    let mut fields: Vec<&str> = schema["required"]
+        .as_array()
+        .unwrap_or_else(|| panic!("{schema} has no required properties"))
+        .iter()
+        .map(|field| field.as_str().unwrap())
+        .collect();
+    fields.sort_unstable();
+    fields
+}
+
+/// Requests every documented operation with a token holding all scopes.
+/// A route missing from the router falls through to axum's fallback,
+/// which answers with a bare `404` or a `405` instead of a handler response.
+#[tokio::test]
+async fn every_documented_operation_is_routed() {
+    // given
+    let app = TestApp::spawn().await;
+    let token = app
+        .create_api_token(&[
+            "issues:read",
+            "issues:write",
+            "subscribers:read",
+            "subscribers:write",
+        ])
+        .await;
+    let document: Value = app.get_openapi_document().await.json().await.unwrap();
+    let paths = document["paths"].as_object().unwrap();
+    assert!(!paths.is_empty());
+
+    for (path, operations) in paths {
+        let endpoint = substitute_path_parameters(path);
+
+        for method in operations.as_object().unwrap().keys() {
+            let method: Method = method.to_uppercase().parse().unwrap();
+
+            // when
+            let response = app
+                .request_api(method.clone(), &endpoint, &token)
+                .header("Idempotency-Key", Uuid::new_v4().to_string())
+                .json(&json!({}))
+                .send()
+                .await
+                .unwrap();
+
+            // then
+            let status = response.status().as_u16();
+            let is_handler_response =
+                status != 404 || response.headers().contains_key(CONTENT_TYPE);
+            assert!(
+                status != 405 && is_handler_response,
+                "{method} {path} is documented, but not routed"
+            );
+        }
+    }
+}
+
+fn substitute_path_parameters(path: &str) -> String {
+    path.split('/')
+        .map(|segment| {
+            if segment.starts_with('{') && segment.ends_with('}') {
+                Uuid::new_v4().to_string()
+            } else {
+                segment.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
@@ -0,0 +1,127 @@
+use crate::helpers::{assert_problem, when_sending_an_email, TestApp};
+use serde_json::{json, Value};
+use uuid::Uuid;
+use wiremock::ResponseTemplate;
+
+#[tokio::test]
+async fn added_subscriber_is_listed_and_receives_a_confirmation_email() {
+    // given
+    let app = TestApp::spawn().await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let token = app
+        .create_api_token(&["subscribers:read", "subscribers:write"])
+        .await;
+
+    // when
+    let response = app
+        .post_api(
+            "/api/v1/subscribers",
+            &token,
+            &json!({ "name": "le guin", "email": "ursula_le_guin@gmail.com" }),
+        )
+        .await;
+
+    // then
+    assert_eq!(response.status(), 201);
+
+    let response = app.get_api("/api/v1/subscribers", &token).await;
+    let list: Value = response.json().await.unwrap();
+    assert_eq!(list["subscribers"][0]["email"], "ursula_le_guin@gmail.com");
+    assert_eq!(list["subscribers"][0]["status"], "pending_confirmation");
+}
+
+#[tokio::test]
+async fn invalid_subscriber_is_rejected_with_problem_details() {
+    // given
+    let app = TestApp::spawn().await;
+    let token = app.create_api_token(&["subscribers:write"]).await;
+
+    // when
+    let response = app
+        .post_api(
+            "/api/v1/subscribers",
+            &token,
+            &json!({ "name": "le guin", "email": "definitely-not-an-email" }),
+        )
+        .await;
+
+    // then
+    assert_problem(&response, 400);
+}
+
+#[tokio::test]
+async fn adding_an_existing_subscriber_is_a_conflict() {
+    // given
+    let app = TestApp::spawn().await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let token = app.create_api_token(&["subscribers:write"]).await;
+    let body = json!({ "name": "le guin", "email": "ursula_le_guin@gmail.com" });
+    let response = app.post_api("/api/v1/subscribers", &token, &body).await;
+    assert_eq!(response.status(), 201);
+
+    // when
+    let response = app.post_api("/api/v1/subscribers", &token, &body).await;
+
+    // then
+    assert_problem(&response, 409);
+}
+
+#[tokio::test]
+async fn removed_subscriber_is_gone() {
+    // given
+    let app = TestApp::spawn().await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let token = app
+        .create_api_token(&["subscribers:read", "subscribers:write"])
+        .await;
+    let response = app
+        .post_api(
+            "/api/v1/subscribers",
+            &token,
+            &json!({ "name": "le guin", "email": "ursula_le_guin@gmail.com" }),
+        )
+        .await;
+    let subscriber: Value = response.json().await.unwrap();
+    let endpoint = format!(
+        "/api/v1/subscribers/{}",
+        subscriber["subscriber_id"].as_str().unwrap()
+    );
+
+    // when
+    let response = app.delete_api(&endpoint, &token).await;
+
+    // then
+    assert_eq!(response.status(), 204);
+
+    let response = app.get_api("/api/v1/subscribers", &token).await;
+    let list: Value = response.json().await.unwrap();
+    assert_eq!(list["subscribers"], json!([]));
+
+    let response = app.delete_api(&endpoint, &token).await;
+    assert_problem(&response, 404);
+}
+
+#[tokio::test]
+async fn removing_an_unknown_subscriber_is_not_found() {
+    // given
+    let app = TestApp::spawn().await;
+    let token = app.create_api_token(&["subscribers:write"]).await;
+
+    // when
+    let response = app
+        .delete_api(&format!("/api/v1/subscribers/{}", Uuid::new_v4()), &token)
+        .await;
+
+    // then
+    assert_problem(&response, 404);
+}
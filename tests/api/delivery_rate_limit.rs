@@ -0,0 +1,80 @@
+use crate::helpers::{create_confirmed_subscriber, when_sending_an_email, TestApp};
+use std::time::Duration;
+use wiremock::ResponseTemplate;
+use zero2prod::issue_delivery_worker::{try_execute_task, ExecutionOutcome};
+
+async fn execute_task(app: &TestApp) -> ExecutionOutcome {
+    try_execute_task(
+        &app.db_pool,
+        &app.email_client,
+        &app.rate_limit,
+        &app.preferences_links,
+        &app.tracking_links,
+    )
+    .await
+    .unwrap()
+}
+
+async fn queued_tasks(app: &TestApp) -> i64 {
+    sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM issue_delivery_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count
+}
+
+#[tokio::test]
+async fn the_worker_waits_once_the_budget_is_exhausted() {
+    // given
+    let app = TestApp::spawn_with(|config| {
+        config.delivery_rate_limit.messages_per_second = 1;
+        config.delivery_rate_limit.messages_per_hour = 1;
+    })
+    .await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.publish_issue("Newsletter Title").await;
+
+    // when
+    let first = execute_task(&app).await;
+    let second = execute_task(&app).await;
+
+    // then
+    assert!(matches!(first, ExecutionOutcome::TaskCompleted));
+    assert!(
+        matches!(second, ExecutionOutcome::RateLimited(wait) if wait > Duration::from_secs(3000))
+    );
+    assert_eq!(queued_tasks(&app).await, 1);
+}
+
+#[tokio::test]
+async fn the_worker_pauses_for_retry_after_when_rate_limited_by_the_provider() {
+    // given
+    let app = TestApp::spawn().await;
+    create_confirmed_subscriber(&app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "120"))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.publish_issue("Newsletter Title").await;
+
+    // when
+    let first = execute_task(&app).await;
+    let second = execute_task(&app).await;
+
+    // then
+    assert!(
+        matches!(first, ExecutionOutcome::RateLimited(wait) if wait == Duration::from_secs(120))
+    );
+    assert!(
+        matches!(second, ExecutionOutcome::RateLimited(wait) if wait > Duration::from_secs(100))
+    );
+    // The issue is sent once the pause is over.
+    assert_eq!(queued_tasks(&app).await, 1);
+}
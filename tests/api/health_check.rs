@@ -1,4 +1,10 @@
 use crate::helpers::TestApp;
+use reqwest::Method;
+use serde_json::json;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
 
 #[tokio::test]
 async fn health_check_works() {
@@ -12,3 +18,84 @@ async fn health_check_works() {
     assert!(response.status().is_success());
     assert_eq!(response.content_length(), Some(0));
 }
+
+#[tokio::test]
+async fn liveness_does_not_check_dependencies() {
+    // given
+    let app = TestApp::spawn().await;
+
+    // when
+    let response = app.request(Method::GET, "/health/live").await;
+
+    // then
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body, json!({ "status": "up" }));
+}
+
+#[tokio::test]
+async fn readiness_reports_status_and_latency_of_each_dependency() {
+    // given
+    let app = TestApp::spawn().await;
+
+    // when
+    let response = app.request(Method::GET, "/health/ready").await;
+
+    // then
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "up");
+    for dependency in ["database", "redis"] {
+        assert_eq!(body["checks"][dependency]["status"], "up", "{dependency}");
+        assert!(
+            body["checks"][dependency]["latency_ms"].is_f64(),
+            "{dependency}"
+        );
+    }
+    assert!(body["checks"].get("email_provider").is_none());
+}
+
+#[tokio::test]
+async fn readiness_fails_when_the_email_provider_rejects_the_token() {
+    // given
+    let app = TestApp::spawn_with(|config| config.health_check.check_email_provider = true).await;
+    Mock::given(path("/server"))
+        .and(method("GET"))
+        .respond_with(ResponseTemplate::new(401))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let response = app.request(Method::GET, "/health/ready").await;
+
+    // then
+    assert_eq!(response.status(), 503);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "down");
+    assert_eq!(body["checks"]["email_provider"]["status"], "down");
+    assert_eq!(body["checks"]["database"]["status"], "up");
+}
+
+#[tokio::test]
+async fn readiness_reuses_the_email_provider_check_for_a_while() {
+    // given
+    let app = TestApp::spawn_with(|config| config.health_check.check_email_provider = true).await;
+    Mock::given(path("/server"))
+        .and(method("GET"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let first = app.request(Method::GET, "/health/ready").await;
+    let second = app.request(Method::GET, "/health/ready").await;
+
+    // then
+    for response in [first, second] {
+        assert_eq!(response.status(), 200);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["checks"]["email_provider"]["status"], "up");
+    }
+}
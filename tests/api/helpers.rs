@@ -1,18 +1,33 @@
 use argon2::{password_hash::SaltString, Algorithm, Argon2, Params, PasswordHasher, Version};
+use axum::http::Uri;
 use claims::assert_some_eq;
+use fake::{
+    faker::{internet::en::SafeEmail, name::en::Name},
+    Fake,
+};
 use linkify::{LinkFinder, LinkKind};
 use once_cell::sync::Lazy;
-use reqwest::{header::CONTENT_TYPE, redirect, Response};
+use reqwest::{header::CONTENT_TYPE, redirect, Method, RequestBuilder, Response};
+use secrecy::ExposeSecret;
 use serde::Serialize;
 use serde_json::json;
 use sqlx::{Connection, Executor, PgConnection, PgPool};
 use std::{net::SocketAddr, str::FromStr};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
-use wiremock::MockServer;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockBuilder, MockServer, ResponseTemplate,
+};
 use zero2prod::{
-    configuration::{get_configuration, DatabaseSettings},
+    configuration::{get_configuration, DatabaseSettings, PostmarkWebhookSettings, Settings},
+    delivery_rate_limit::DeliveryRateLimit,
+    domain::{PreferencesLinks, TrackingLinks},
+    drip_sequences::try_execute_drip_step,
     email_client::EmailClient,
     issue_delivery_worker::{try_execute_task, ExecutionOutcome},
+    migrations::run_migrations,
     startup::{get_pg_connection_pool, Application},
     telemetry::{get_subscriber, init_subscriber},
 };
@@ -34,61 +49,148 @@ pub struct TestApp {
     pub db_pool: PgPool,
     pub email_server: MockServer,
     pub email_client: EmailClient,
+    pub rate_limit: DeliveryRateLimit,
+    pub preferences_links: PreferencesLinks,
+    pub tracking_links: TrackingLinks,
+    pub postmark_webhook: PostmarkWebhookSettings,
     pub test_user: TestUser,
+    config: Settings,
     client: reqwest::Client,
+    shutdown: CancellationToken,
+    server: JoinHandle<Result<(), anyhow::Error>>,
 }
 
 impl TestApp {
     const FAILED_TO_EXECUTE_REQUEST: &'static str = "Failed to execute request";
 
     pub async fn spawn() -> Self {
+        Self::spawn_with(|_| {}).await
+    }
+
+    /// Spawns the application with the test configuration adjusted by `configure`.
+    pub async fn spawn_with(configure: impl FnOnce(&mut Settings)) -> Self {
         Lazy::force(&TRACING);
 
         let mut config = get_configuration().expect("Failed to read configuration");
         config.database.database_name = Uuid::new_v4().to_string();
         config.application.port = 0;
+        // Tests submit the subscribe form right after rendering it.
+        config.subscribe_protection.minimum_fill_time_milliseconds = 0;
+        configure(&mut config);
 
-        let db_pool = configure_database(&config.database).await;
+        let db_pool = configure_database(&config).await;
         let email_server = MockServer::start().await;
         config.email_client.base_url = email_server.uri();
         let email_client = config.email_client.client();
-
-        let app = Application::build(config).await;
+        let rate_limit = config.delivery_rate_limit.rate_limit();
+        let base_url = Uri::from_str(&config.application.base_url).unwrap();
+        let preferences_links =
+            PreferencesLinks::new(base_url.clone(), config.application.hmac_secret.clone());
+        let tracking_links = TrackingLinks::new(
+            base_url,
+            config.application.hmac_secret.clone(),
+            config.tracking.enabled,
+        );
+        let postmark_webhook = config.postmark_webhook.clone();
+
+        let app = Application::build(config.clone()).await;
         let address = app.local_addr();
 
         let test_user = TestUser::generate();
         test_user.store(&db_pool).await;
 
-        let client = reqwest::Client::builder()
-            .redirect(redirect::Policy::none())
-            .cookie_store(true)
-            .build()
-            .unwrap();
+        let client = Self::new_client();
 
-        tokio::spawn(app.run_until_stopped());
+        let shutdown = CancellationToken::new();
+        let server = tokio::spawn(app.run_until_stopped(shutdown.clone()));
 
         Self {
             address,
             db_pool,
             email_server,
             email_client,
+            rate_limit,
+            preferences_links,
+            tracking_links,
+            postmark_webhook,
             test_user,
+            config,
             client,
+            shutdown,
+            server,
         }
     }
 
+    /// Configuration of the application, for running a worker against its
+    /// database and email server.
+    pub fn config(&self) -> Settings {
+        self.config.clone()
+    }
+
+    /// Shuts the application down as on SIGTERM and waits for it to stop.
+    pub async fn shut_down(&mut self) {
+        self.shutdown.cancel();
+        (&mut self.server)
+            .await
+            .expect("Server task panicked")
+            .expect("Server failed to shut down");
+    }
+
+    fn new_client() -> reqwest::Client {
+        reqwest::Client::builder()
+            .redirect(redirect::Policy::none())
+            .cookie_store(true)
+            .build()
+            .unwrap()
+    }
+
     pub async fn dispatch_all_pending_emails(&self) {
         loop {
-            if let ExecutionOutcome::EmptyQueue =
-                try_execute_task(&self.db_pool, &self.email_client)
-                    .await
-                    .unwrap()
-            {
-                break;
+            let issue_outcome = try_execute_task(
+                &self.db_pool,
+                &self.email_client,
+                &self.rate_limit,
+                &self.preferences_links,
+                &self.tracking_links,
+            )
+            .await
+            .unwrap();
+            let drip_outcome = try_execute_drip_step(
+                &self.db_pool,
+                &self.email_client,
+                &self.rate_limit,
+                &self.preferences_links,
+            )
+            .await
+            .unwrap();
+            match (issue_outcome, drip_outcome) {
+                (ExecutionOutcome::EmptyQueue, ExecutionOutcome::EmptyQueue) => break,
+                (ExecutionOutcome::RateLimited(wait), _)
+                | (_, ExecutionOutcome::RateLimited(wait)) => tokio::time::sleep(wait).await,
+                _ => {}
             }
         }
     }
 
+    pub async fn request(&self, method: Method, endpoint: &str) -> Response {
+        self.client
+            .request(method, self.url(endpoint))
+            .send()
+            .await
+            .expect(Self::FAILED_TO_EXECUTE_REQUEST)
+    }
+
+    pub async fn get_home_html(&self) -> String {
+        self.client
+            .get(self.url("/"))
+            .send()
+            .await
+            .expect(Self::FAILED_TO_EXECUTE_REQUEST)
+            .text()
+            .await
+            .unwrap()
+    }
+
     pub async fn get_health_check(&self) -> reqwest::Response {
         self.client
             .get(self.url("/health_check"))
@@ -127,6 +229,22 @@ impl TestApp {
             .expect(Self::FAILED_TO_EXECUTE_REQUEST)
     }
 
+    /// Posts the subscribe form as relayed by a proxy on behalf of `client_ip`.
+    pub async fn post_subscriptions_forwarded_for(
+        &self,
+        body: String,
+        client_ip: &str,
+    ) -> reqwest::Response {
+        self.client
+            .post(self.url("/subscriptions"))
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .header("X-Forwarded-For", client_ip)
+            .body(body)
+            .send()
+            .await
+            .expect(Self::FAILED_TO_EXECUTE_REQUEST)
+    }
+
     pub fn get_confirmation_links(&self, request: &wiremock::Request) -> ConfirmationLinks {
         let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
 
@@ -151,6 +269,74 @@ impl TestApp {
         ConfirmationLinks { html, plain_text }
     }
 
+    /// Preferences token of a subscriber, as put in the links of delivered issues.
+    pub fn preferences_token(&self, subscriber_id: Uuid) -> String {
+        let link = self.preferences_links.link(subscriber_id);
+        let (_, token) = link.split_once("token=").unwrap();
+        token.to_string()
+    }
+
+    pub async fn get_preferences(&self, token: &str) -> Response {
+        self.client
+            .get(self.url("/subscriptions/preferences"))
+            .query(&[("token", token)])
+            .send()
+            .await
+            .expect(Self::FAILED_TO_EXECUTE_REQUEST)
+    }
+
+    pub async fn get_preferences_html(&self, token: &str) -> String {
+        self.get_preferences(token).await.text().await.unwrap()
+    }
+
+    pub async fn post_preferences<Body>(&self, body: &Body) -> Response
+    where
+        Body: Serialize,
+    {
+        self.client
+            .post(self.url("/subscriptions/preferences"))
+            .form(body)
+            .send()
+            .await
+            .expect(Self::FAILED_TO_EXECUTE_REQUEST)
+    }
+
+    pub async fn post_change_email(&self, token: &str, email: &str) -> Response {
+        self.client
+            .post(self.url("/subscriptions/preferences/email"))
+            .form(&[("token", token), ("email", email)])
+            .send()
+            .await
+            .expect(Self::FAILED_TO_EXECUTE_REQUEST)
+    }
+
+    pub async fn post_unsubscribe(&self, token: &str) -> Response {
+        self.client
+            .post(self.url("/subscriptions/unsubscribe"))
+            .form(&[("token", token)])
+            .send()
+            .await
+            .expect(Self::FAILED_TO_EXECUTE_REQUEST)
+    }
+
+    pub fn postmark_webhook_secret(&self) -> &str {
+        self.postmark_webhook.secret.expose_secret()
+    }
+
+    /// Posts a webhook the way Postmark does, with basic auth.
+    pub async fn post_postmark_webhook(&self, body: &serde_json::Value) -> Response {
+        self.client
+            .post(self.url("/webhooks/postmark"))
+            .basic_auth(
+                &self.postmark_webhook.username,
+                Some(self.postmark_webhook_secret()),
+            )
+            .json(body)
+            .send()
+            .await
+            .expect(Self::FAILED_TO_EXECUTE_REQUEST)
+    }
+
     pub async fn post_login<Body>(&self, body: &Body) -> Response
     where
         Body: serde::Serialize,
@@ -186,6 +372,14 @@ impl TestApp {
         self.get_admin_dashboard().await.text().await.unwrap()
     }
 
+    pub async fn get_admin_dashboard_with(&self, client: &reqwest::Client) -> Response {
+        client
+            .get(self.url("/admin/dashboard"))
+            .send()
+            .await
+            .expect(Self::FAILED_TO_EXECUTE_REQUEST)
+    }
+
     pub async fn post_publish_newsletter<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
@@ -243,6 +437,318 @@ impl TestApp {
             .expect(Self::FAILED_TO_EXECUTE_REQUEST)
     }
 
+    pub async fn get_sessions(&self) -> Response {
+        self.client
+            .get(self.url("/admin/sessions"))
+            .send()
+            .await
+            .expect(Self::FAILED_TO_EXECUTE_REQUEST)
+    }
+
+    pub async fn get_sessions_html(&self) -> String {
+        self.get_sessions().await.text().await.unwrap()
+    }
+
+    pub async fn post_log_out_other_sessions(&self) -> Response {
+        self.client
+            .post(self.url("/admin/sessions/logout_others"))
+            .send()
+            .await
+            .expect(Self::FAILED_TO_EXECUTE_REQUEST)
+    }
+
+    pub async fn get_audit(&self, query: &str) -> Response {
+        self.client
+            .get(self.url(&format!("/admin/audit?{query}")))
+            .send()
+            .await
+            .expect(Self::FAILED_TO_EXECUTE_REQUEST)
+    }
+
+    pub async fn get_audit_html(&self, query: &str) -> String {
+        self.get_audit(query).await.text().await.unwrap()
+    }
+
+    pub async fn get_audit_export(&self, query: &str) -> Response {
+        self.client
+            .get(self.url(&format!("/admin/audit/export?{query}")))
+            .send()
+            .await
+            .expect(Self::FAILED_TO_EXECUTE_REQUEST)
+    }
+
+    pub async fn get_tokens(&self) -> Response {
+        self.client
+            .get(self.url("/admin/tokens"))
+            .send()
+            .await
+            .expect(Self::FAILED_TO_EXECUTE_REQUEST)
+    }
+
+    pub async fn get_tokens_html(&self) -> String {
+        self.get_tokens().await.text().await.unwrap()
+    }
+
+    pub async fn post_create_token<Body>(&self, body: &Body) -> Response
+    where
+        Body: Serialize,
+    {
+        self.client
+            .post(self.url("/admin/tokens"))
+            .form(body)
+            .send()
+            .await
+            .expect(Self::FAILED_TO_EXECUTE_REQUEST)
+    }
+
+    pub async fn post_revoke_token<Body>(&self, body: &Body) -> Response
+    where
+        Body: Serialize,
+    {
+        self.client
+            .post(self.url("/admin/tokens/revoke"))
+            .form(body)
+            .send()
+            .await
+            .expect(Self::FAILED_TO_EXECUTE_REQUEST)
+    }
+
+    pub async fn get_sequences(&self) -> Response {
+        self.client
+            .get(self.url("/admin/sequences"))
+            .send()
+            .await
+            .expect(Self::FAILED_TO_EXECUTE_REQUEST)
+    }
+
+    pub async fn get_sequences_html(&self) -> String {
+        self.get_sequences().await.text().await.unwrap()
+    }
+
+    pub async fn post_create_sequence<Body>(&self, body: &Body) -> Response
+    where
+        Body: Serialize,
+    {
+        self.client
+            .post(self.url("/admin/sequences"))
+            .form(body)
+            .send()
+            .await
+            .expect(Self::FAILED_TO_EXECUTE_REQUEST)
+    }
+
+    pub async fn post_add_sequence_step<Body>(&self, body: &Body) -> Response
+    where
+        Body: Serialize,
+    {
+        self.client
+            .post(self.url("/admin/sequences/steps"))
+            .form(body)
+            .send()
+            .await
+            .expect(Self::FAILED_TO_EXECUTE_REQUEST)
+    }
+
+    pub async fn post_set_sequence_active<Body>(&self, body: &Body) -> Response
+    where
+        Body: Serialize,
+    {
+        self.client
+            .post(self.url("/admin/sequences/active"))
+            .form(body)
+            .send()
+            .await
+            .expect(Self::FAILED_TO_EXECUTE_REQUEST)
+    }
+
+    pub async fn get_status(&self) -> Response {
+        self.client
+            .get(self.url("/admin/status"))
+            .send()
+            .await
+            .expect(Self::FAILED_TO_EXECUTE_REQUEST)
+    }
+
+    pub async fn get_status_html(&self) -> String {
+        self.get_status().await.text().await.unwrap()
+    }
+
+    pub async fn get_suppressions(&self) -> Response {
+        self.client
+            .get(self.url("/admin/suppressions"))
+            .send()
+            .await
+            .expect(Self::FAILED_TO_EXECUTE_REQUEST)
+    }
+
+    pub async fn get_suppressions_html(&self) -> String {
+        self.get_suppressions().await.text().await.unwrap()
+    }
+
+    pub async fn post_add_suppression<Body>(&self, body: &Body) -> Response
+    where
+        Body: Serialize,
+    {
+        self.client
+            .post(self.url("/admin/suppressions"))
+            .form(body)
+            .send()
+            .await
+            .expect(Self::FAILED_TO_EXECUTE_REQUEST)
+    }
+
+    pub async fn post_import_suppressions<Body>(&self, body: &Body) -> Response
+    where
+        Body: Serialize,
+    {
+        self.client
+            .post(self.url("/admin/suppressions/import"))
+            .form(body)
+            .send()
+            .await
+            .expect(Self::FAILED_TO_EXECUTE_REQUEST)
+    }
+
+    pub async fn post_remove_suppression<Body>(&self, body: &Body) -> Response
+    where
+        Body: Serialize,
+    {
+        self.client
+            .post(self.url("/admin/suppressions/remove"))
+            .form(body)
+            .send()
+            .await
+            .expect(Self::FAILED_TO_EXECUTE_REQUEST)
+    }
+
+    /// Logs the test user in and creates an API token with given scopes,
+    /// returning the plaintext token shown once on the tokens page.
+    pub async fn create_api_token(&self, scopes: &[&str]) -> String {
+        let response = self
+            .log_in(&self.test_user.username, &self.test_user.password)
+            .await;
+        assert_redirect_to(&response, "/admin/dashboard");
+
+        let mut body = serde_json::Map::new();
+        body.insert("name".into(), json!("test token"));
+        for scope in scopes {
+            body.insert(scope.to_string(), json!(true));
+        }
+        let response = self.post_create_token(&body).await;
+        assert_redirect_to(&response, "/admin/tokens");
+
+        let html_page = self.get_tokens_html().await;
+        let start = html_page
+            .find("z2p_")
+            .expect("Token not found on the tokens page");
+
+        html_page[start..]
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+            .collect()
+    }
+
+    pub async fn get_api_me(&self, token: &str) -> Response {
+        self.get_api("/api/v1/me", token).await
+    }
+
+    pub async fn get_api(&self, endpoint: &str, token: &str) -> Response {
+        self.client
+            .get(self.url(endpoint))
+            .bearer_auth(token)
+            .send()
+            .await
+            .expect(Self::FAILED_TO_EXECUTE_REQUEST)
+    }
+
+    pub async fn post_api<Body>(&self, endpoint: &str, token: &str, body: &Body) -> Response
+    where
+        Body: Serialize,
+    {
+        self.client
+            .post(self.url(endpoint))
+            .bearer_auth(token)
+            .json(body)
+            .send()
+            .await
+            .expect(Self::FAILED_TO_EXECUTE_REQUEST)
+    }
+
+    pub async fn post_api_issue<Body>(
+        &self,
+        token: &str,
+        idempotency_key: &str,
+        body: &Body,
+    ) -> Response
+    where
+        Body: Serialize,
+    {
+        self.client
+            .post(self.url("/api/v1/issues"))
+            .bearer_auth(token)
+            .header("Idempotency-Key", idempotency_key)
+            .json(body)
+            .send()
+            .await
+            .expect(Self::FAILED_TO_EXECUTE_REQUEST)
+    }
+
+    /// Publishes an issue through the API, returning its id.
+    pub async fn publish_issue(&self, title: &str) -> Uuid {
+        let token = self.create_api_token(&["issues:write"]).await;
+        let body = json!({
+            "title": title,
+            "html_content": "<p>Newsletter body as html.</p>",
+            "text_content": "Newsletter body as text.",
+        });
+        let response = self
+            .post_api_issue(&token, &Uuid::new_v4().to_string(), &body)
+            .await;
+        assert_eq!(response.status(), 201);
+
+        let body: serde_json::Value = response.json().await.unwrap();
+        body["issue_id"].as_str().unwrap().parse().unwrap()
+    }
+
+    pub fn request_api(&self, method: Method, endpoint: &str, token: &str) -> RequestBuilder {
+        self.client
+            .request(method, self.url(endpoint))
+            .bearer_auth(token)
+    }
+
+    pub async fn get_openapi_document(&self) -> Response {
+        self.client
+            .get(self.url("/api/openapi.json"))
+            .send()
+            .await
+            .expect(Self::FAILED_TO_EXECUTE_REQUEST)
+    }
+
+    pub async fn delete_api(&self, endpoint: &str, token: &str) -> Response {
+        self.client
+            .delete(self.url(endpoint))
+            .bearer_auth(token)
+            .send()
+            .await
+            .expect(Self::FAILED_TO_EXECUTE_REQUEST)
+    }
+
+    pub async fn log_in_with_new_client(&self, username: &str, password: &str) -> reqwest::Client {
+        let client = Self::new_client();
+
+        client
+            .post(self.url("/login"))
+            .form(&json!({
+                "username": username,
+                "password": password,
+            }))
+            .send()
+            .await
+            .expect(Self::FAILED_TO_EXECUTE_REQUEST);
+
+        client
+    }
+
     pub async fn log_in(&self, username: &str, password: &str) -> Response {
         self.client
             .post(self.url("/login"))
@@ -260,12 +766,27 @@ impl TestApp {
     }
 }
 
+pub fn assert_problem(response: &Response, status: u16) {
+    assert_eq!(response.status(), status);
+    assert_eq!(response.headers()[CONTENT_TYPE], "application/problem+json");
+}
+
 pub fn assert_redirect_to(response: &Response, url: &str) {
     assert_eq!(response.status(), 303);
     assert_eq!(response.headers().get("Location").unwrap(), url);
 }
 
-async fn configure_database(configuration: &DatabaseSettings) -> PgPool {
+/// Creates a database of its own, left unmigrated, as on a first deployment.
+pub async fn create_unmigrated_database() -> PgPool {
+    let mut configuration = get_configuration()
+        .expect("Failed to read configuration")
+        .database;
+    configuration.database_name = Uuid::new_v4().to_string();
+
+    create_database(&configuration).await
+}
+
+async fn create_database(configuration: &DatabaseSettings) -> PgPool {
     let mut conn = PgConnection::connect_with(&configuration.without_db())
         .await
         .expect("Failed to connect to Postgres");
@@ -274,10 +795,13 @@ async fn configure_database(configuration: &DatabaseSettings) -> PgPool {
         .await
         .expect("Failed to create database");
 
-    let pool = get_pg_connection_pool(configuration);
+    get_pg_connection_pool(configuration)
+}
+
+async fn configure_database(config: &Settings) -> PgPool {
+    let pool = create_database(&config.database).await;
 
-    sqlx::migrate!("./migrations")
-        .run(&pool)
+    run_migrations(&pool, config)
         .await
         .expect("Failed to migrate database");
 
@@ -329,3 +853,46 @@ pub struct ConfirmationLinks {
     pub html: reqwest::Url,
     pub plain_text: reqwest::Url,
 }
+
+pub async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
+    let name: String = Name().fake();
+    let email: String = SafeEmail().fake();
+    let body = serde_urlencoded::to_string(json!({
+        "name": name,
+        "email": email,
+    }))
+    .unwrap();
+
+    let _mock_guard_ = when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    app.get_confirmation_links(
+        &app.email_server
+            .received_requests()
+            .await
+            .unwrap()
+            .pop()
+            .unwrap(),
+    )
+}
+
+pub async fn create_confirmed_subscriber(app: &TestApp) {
+    let links = create_unconfirmed_subscriber(app).await;
+    reqwest::get(links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+pub fn when_sending_an_email() -> MockBuilder {
+    Mock::given(path("/email")).and(method("POST"))
+}
@@ -1,5 +1,7 @@
 use crate::helpers::{assert_redirect_to, TestApp};
+use argon2::{password_hash::SaltString, Algorithm, Argon2, Params, PasswordHasher, Version};
 use serde_json::json;
+use std::time::Duration;
 use uuid::Uuid;
 
 #[tokio::test]
@@ -56,3 +58,61 @@ async fn an_error_flash_message_is_set_on_failure() {
     let html_page = app.get_login_html().await;
     assert!(!html_page.contains("<p><i>Authentication failed</i></p>"));
 }
+
+#[tokio::test]
+async fn password_hash_with_weak_parameters_is_upgraded_on_login() {
+    // given
+    let app = TestApp::spawn().await;
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    let weak_password_hash = Argon2::new(
+        Algorithm::Argon2id,
+        Version::V0x13,
+        Params::new(4096, 1, 1, None).unwrap(),
+    )
+    .hash_password(app.test_user.password.as_bytes(), &salt)
+    .unwrap()
+    .to_string();
+
+    sqlx::query!(
+        "UPDATE users SET password_hash = $1 WHERE user_id = $2",
+        weak_password_hash,
+        app.test_user.user_id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to update password hash");
+
+    // when
+    let response = app
+        .log_in(&app.test_user.username, &app.test_user.password)
+        .await;
+    assert_redirect_to(&response, "/admin/dashboard");
+
+    // then
+    let mut upgraded_password_hash = None;
+    for _ in 0..50 {
+        let saved = sqlx::query!(
+            "SELECT password_hash FROM users WHERE user_id = $1",
+            app.test_user.user_id,
+        )
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch password hash");
+
+        if saved.password_hash != weak_password_hash {
+            upgraded_password_hash = Some(saved.password_hash);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    let upgraded_password_hash =
+        upgraded_password_hash.expect("Password hash has not been upgraded");
+    assert!(upgraded_password_hash.starts_with("$argon2id$v=19$m=15000,t=2,p=1$"));
+
+    app.post_logout().await;
+    let response = app
+        .log_in(&app.test_user.username, &app.test_user.password)
+        .await;
+    assert_redirect_to(&response, "/admin/dashboard");
+}
@@ -1,8 +1,27 @@
+mod admin_audit;
+mod admin_cli;
 mod admin_dashboard;
 mod admin_newsletters;
 mod admin_password;
+mod admin_sequences;
+mod admin_sessions;
+mod admin_status;
+mod admin_suppressions;
+mod admin_tokens;
+mod api_issues;
+mod api_openapi;
+mod api_subscribers;
+mod delivery_rate_limit;
 mod health_check;
 mod helpers;
 mod login;
+mod queue_listener;
+mod security_headers;
+mod shutdown;
+mod subscribe_protection;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_email;
+mod subscriptions_preferences;
+mod tracking;
+mod webhooks_postmark;
@@ -0,0 +1,75 @@
+use crate::helpers::{create_confirmed_subscriber, TestApp};
+use serde_json::json;
+use std::time::Duration;
+use tokio::time::timeout;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+use zero2prod::{
+    configuration::WorkerSettings,
+    queue_listener::{listen_for_tasks, QueueWakeup},
+};
+
+fn worker_settings() -> WorkerSettings {
+    WorkerSettings {
+        concurrency: 1,
+        poll_interval_seconds: 3600,
+        fallback_poll_interval_seconds: 3600,
+    }
+}
+
+/// Waits for the listener to connect, which wakes workers up once.
+async fn wait_for_listener(wakeup: &mut QueueWakeup) {
+    timeout(Duration::from_secs(5), wakeup.wait_for_tasks())
+        .await
+        .expect("The listener did not connect");
+}
+
+#[tokio::test]
+async fn publishing_an_issue_wakes_idle_workers_up() {
+    // given
+    let app = TestApp::spawn().await;
+    create_confirmed_subscriber(&app).await;
+    let stop = CancellationToken::new();
+    let (mut wakeup, listener) =
+        listen_for_tasks(app.db_pool.clone(), &worker_settings(), stop.clone());
+    wait_for_listener(&mut wakeup).await;
+
+    // when
+    let token = app.create_api_token(&["issues:write"]).await;
+    let body = json!({
+        "title": "Newsletter Title",
+        "html_content": "<p>Newsletter body as html.</p>",
+        "text_content": "Newsletter body as text.",
+    });
+    let response = app
+        .post_api_issue(&token, &Uuid::new_v4().to_string(), &body)
+        .await;
+    assert_eq!(response.status(), 201);
+
+    // then
+    timeout(Duration::from_secs(5), wakeup.wait_for_tasks())
+        .await
+        .expect("Idle workers were not woken up");
+
+    stop.cancel();
+    listener.await.unwrap();
+}
+
+#[tokio::test]
+async fn idle_workers_sleep_until_tasks_are_enqueued() {
+    // given
+    let app = TestApp::spawn().await;
+    let stop = CancellationToken::new();
+    let (mut wakeup, listener) =
+        listen_for_tasks(app.db_pool.clone(), &worker_settings(), stop.clone());
+    wait_for_listener(&mut wakeup).await;
+
+    // when
+    let woken_up = timeout(Duration::from_millis(500), wakeup.wait_for_tasks()).await;
+
+    // then
+    assert!(woken_up.is_err());
+
+    stop.cancel();
+    listener.await.unwrap();
+}
@@ -0,0 +1,143 @@
+use crate::helpers::TestApp;
+use reqwest::{Method, Response};
+use zero2prod::configuration::Environment;
+
+const ROUTES: [(Method, &str); 22] = [
+    (Method::GET, "/"),
+    (Method::GET, "/health_check"),
+    (Method::POST, "/subscriptions"),
+    (Method::GET, "/subscriptions/confirm"),
+    (Method::GET, "/login"),
+    (Method::POST, "/login"),
+    (Method::GET, "/admin/dashboard"),
+    (Method::GET, "/admin/newsletters"),
+    (Method::POST, "/admin/newsletters"),
+    (Method::GET, "/admin/password"),
+    (Method::POST, "/admin/password"),
+    (Method::GET, "/admin/sessions"),
+    (Method::POST, "/admin/sessions/logout_others"),
+    (Method::GET, "/admin/tokens"),
+    (Method::POST, "/admin/tokens/revoke"),
+    (Method::GET, "/admin/audit"),
+    (Method::GET, "/admin/audit/export"),
+    (Method::GET, "/api/openapi.json"),
+    (Method::GET, "/api/v1/me"),
+    (Method::GET, "/api/v1/issues"),
+    (Method::GET, "/not-a-route"),
+    // Last, as it ends the session of the logged in tests.
+    (Method::POST, "/admin/logout"),
+];
+
+fn assert_security_headers(response: &Response, route: &str) {
+    let headers = response.headers();
+
+    let csp = headers
+        .get("content-security-policy")
+        .unwrap_or_else(|| panic!("Missing Content-Security-Policy on `{route}`"))
+        .to_str()
+        .unwrap();
+    assert!(csp.contains("default-src 'self'"), "{route}: {csp}");
+    assert!(csp.contains("frame-ancestors 'none'"), "{route}: {csp}");
+    assert!(!csp.contains("{nonce}"), "{route}: {csp}");
+
+    assert_eq!(headers["x-frame-options"], "DENY", "{route}");
+    assert_eq!(
+        headers["referrer-policy"], "strict-origin-when-cross-origin",
+        "{route}"
+    );
+    assert_eq!(headers["x-content-type-options"], "nosniff", "{route}");
+}
+
+fn script_nonce(response: &Response) -> String {
+    let csp = response.headers()["content-security-policy"]
+        .to_str()
+        .unwrap();
+    let start = csp.find("'nonce-").expect("No nonce in the policy") + "'nonce-".len();
+
+    csp[start..].chars().take_while(|c| *c != '\'').collect()
+}
+
+#[tokio::test]
+async fn every_route_sends_security_headers_when_logged_out() {
+    // given
+    let app = TestApp::spawn().await;
+
+    for (method, route) in ROUTES {
+        // when
+        let response = app.request(method, route).await;
+
+        // then
+        assert_security_headers(&response, route);
+    }
+}
+
+#[tokio::test]
+async fn every_route_sends_security_headers_when_logged_in() {
+    // given
+    let app = TestApp::spawn().await;
+    app.log_in(&app.test_user.username, &app.test_user.password)
+        .await;
+
+    for (method, route) in ROUTES {
+        // when
+        let response = app.request(method, route).await;
+
+        // then
+        assert_security_headers(&response, route);
+    }
+}
+
+#[tokio::test]
+async fn every_request_gets_a_fresh_script_nonce() {
+    // given
+    let app = TestApp::spawn().await;
+
+    // when
+    let first = app.request(Method::GET, "/").await;
+    let second = app.request(Method::GET, "/").await;
+
+    // then
+    let first = script_nonce(&first);
+    let second = script_nonce(&second);
+    assert!(first.len() >= 22);
+    assert_ne!(first, second);
+}
+
+#[tokio::test]
+async fn hsts_is_not_sent_outside_of_production() {
+    // given
+    let app = TestApp::spawn().await;
+
+    for (method, route) in ROUTES {
+        // when
+        let response = app.request(method, route).await;
+
+        // then
+        assert!(
+            response
+                .headers()
+                .get("strict-transport-security")
+                .is_none(),
+            "Unexpected HSTS header on `{route}`"
+        );
+    }
+}
+
+#[tokio::test]
+async fn hsts_is_sent_in_production() {
+    // given
+    let app = TestApp::spawn_with(|config| config.environment = Environment::Production).await;
+
+    for (method, route) in ROUTES {
+        // when
+        let response = app.request(method, route).await;
+
+        // then
+        assert_security_headers(&response, route);
+        assert_eq!(
+            response.headers()["strict-transport-security"],
+            "max-age=31536000; includeSubDomains",
+            "{route}"
+        );
+    }
+}
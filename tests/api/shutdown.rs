@@ -0,0 +1,116 @@
+use crate::helpers::{create_confirmed_subscriber, when_sending_an_email, TestApp};
+use claims::{assert_err, assert_ok};
+use std::time::Duration;
+use tokio::{task::JoinHandle, time::timeout};
+use tokio_util::sync::CancellationToken;
+use wiremock::ResponseTemplate;
+use zero2prod::issue_delivery_worker::run_worker_until_stopped;
+
+#[tokio::test]
+async fn the_server_stops_accepting_connections_on_shutdown() {
+    // given
+    let mut app = TestApp::spawn().await;
+    assert!(app.get_health_check().await.status().is_success());
+
+    // when
+    tokio::time::timeout(Duration::from_secs(5), app.shut_down())
+        .await
+        .expect("Server did not shut down in time");
+
+    // then
+    assert_err!(reqwest::get(format!("http://{}/health_check", app.address)).await);
+}
+
+#[tokio::test]
+async fn workers_finish_in_flight_deliveries_and_stop_dequeuing_on_shutdown() {
+    // given
+    let app = TestApp::spawn().await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(1)))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let (shutdown, worker) = spawn_worker(&app, 30);
+    app.publish_issue("Newsletter Title").await;
+    wait_for_delivery_to_start(&app).await;
+
+    // when
+    shutdown.cancel();
+
+    // then
+    let outcome = timeout(Duration::from_secs(5), worker)
+        .await
+        .expect("Worker did not shut down in time");
+    assert_ok!(outcome.unwrap());
+    assert_eq!(delivered_count(&app).await, 1);
+    assert_eq!(queued_count(&app).await, 1);
+}
+
+#[tokio::test]
+async fn deliveries_overrunning_the_shutdown_timeout_are_rolled_back() {
+    // given
+    let app = TestApp::spawn().await;
+    create_confirmed_subscriber(&app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(30)))
+        .mount(&app.email_server)
+        .await;
+    let (shutdown, worker) = spawn_worker(&app, 1);
+    app.publish_issue("Newsletter Title").await;
+    wait_for_delivery_to_start(&app).await;
+
+    // when
+    shutdown.cancel();
+
+    // then
+    let outcome = timeout(Duration::from_secs(5), worker)
+        .await
+        .expect("Worker did not shut down in time");
+    assert_ok!(outcome.unwrap());
+    assert_eq!(delivered_count(&app).await, 0);
+    assert_eq!(queued_count(&app).await, 1);
+}
+
+/// Runs a single delivery worker against the application.
+fn spawn_worker(
+    app: &TestApp,
+    shutdown_timeout_seconds: u64,
+) -> (CancellationToken, JoinHandle<Result<(), anyhow::Error>>) {
+    let mut config = app.config();
+    config.worker.concurrency = 1;
+    config.application.shutdown_timeout_seconds = shutdown_timeout_seconds;
+
+    let shutdown = CancellationToken::new();
+    let worker = tokio::spawn(run_worker_until_stopped(config, shutdown.clone()));
+    (shutdown, worker)
+}
+
+/// Waits for the worker to send the issue to the email provider, which then
+/// holds the response back.
+async fn wait_for_delivery_to_start(app: &TestApp) {
+    let confirmations = app.email_server.received_requests().await.unwrap().len();
+
+    timeout(Duration::from_secs(5), async {
+        while app.email_server.received_requests().await.unwrap().len() == confirmations {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("The worker did not start delivering");
+}
+
+async fn delivered_count(app: &TestApp) -> i64 {
+    sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM issue_deliveries"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+async fn queued_count(app: &TestApp) -> i64 {
+    sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM issue_delivery_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+}
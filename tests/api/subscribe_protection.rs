@@ -0,0 +1,366 @@
+use crate::helpers::{assert_redirect_to, when_sending_an_email, TestApp};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use wiremock::ResponseTemplate;
+use zero2prod::subscribe_protection::delete_expired_attempts;
+
+fn form(email: &str, extra: &[(&str, &str)]) -> String {
+    let mut fields = vec![("name", "Imię Nazwisko"), ("email", email)];
+    fields.extend_from_slice(extra);
+    serde_urlencoded::to_string(fields).unwrap()
+}
+
+/// Value of a hidden input of the home page subscribe form.
+fn hidden_input(html_page: &str, name: &str) -> String {
+    let marker = format!(r#"name="{name}" value=""#);
+    let start = html_page
+        .find(&marker)
+        .unwrap_or_else(|| panic!("Input `{name}` not found"))
+        + marker.len();
+
+    html_page[start..]
+        .chars()
+        .take_while(|c| *c != '"')
+        .collect()
+}
+
+fn solve_proof_of_work(challenge: &str, difficulty_bits: u32) -> String {
+    (0u64..)
+        .map(|nonce| nonce.to_string())
+        .find(|nonce| {
+            let digest = Sha256::new()
+                .chain_update(challenge)
+                .chain_update(nonce)
+                .finalize();
+            let leading_zeros = digest
+                .iter()
+                .position(|byte| *byte != 0)
+                .map_or(256, |i| i as u32 * 8 + digest[i].leading_zeros());
+            leading_zeros >= difficulty_bits
+        })
+        .unwrap()
+}
+
+async fn count_subscribers(app: &TestApp) -> i64 {
+    sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count
+}
+
+#[tokio::test]
+async fn filled_in_honeypot_is_silently_ignored() {
+    // given
+    let app = TestApp::spawn().await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let response = app
+        .post_subscriptions(form(
+            "bot@example.com",
+            &[("website", "https://spam.example.com")],
+        ))
+        .await;
+
+    // then
+    assert_eq!(response.status(), 200);
+    assert_eq!(count_subscribers(&app).await, 0);
+}
+
+#[tokio::test]
+async fn form_submitted_before_minimum_fill_time_is_rejected() {
+    // given
+    let app = TestApp::spawn_with(|config| {
+        config.subscribe_protection.minimum_fill_time_milliseconds = 60_000;
+    })
+    .await;
+    let form_token = hidden_input(&app.get_home_html().await, "form_token");
+
+    // when
+    let without_token = app.post_subscriptions(form("a@example.com", &[])).await;
+    let too_fast = app
+        .post_subscriptions(form("a@example.com", &[("form_token", &form_token)]))
+        .await;
+
+    // then
+    assert_eq!(without_token.status(), 400);
+    assert_eq!(too_fast.status(), 400);
+    assert_eq!(count_subscribers(&app).await, 0);
+}
+
+#[tokio::test]
+async fn form_submitted_after_minimum_fill_time_is_accepted() {
+    // given
+    let app = TestApp::spawn_with(|config| {
+        config.subscribe_protection.minimum_fill_time_milliseconds = 50;
+    })
+    .await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let form_token = hidden_input(&app.get_home_html().await, "form_token");
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // when
+    let response = app
+        .post_subscriptions(form("a@example.com", &[("form_token", &form_token)]))
+        .await;
+
+    // then
+    assert_eq!(response.status(), 200);
+}
+
+#[tokio::test]
+async fn attempts_for_a_single_email_are_rate_limited() {
+    // given
+    let app = TestApp::spawn_with(|config| {
+        config.subscribe_protection.attempts_per_email_per_hour = 2;
+    })
+    .await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    for _ in 0..2 {
+        let response = app.post_subscriptions(form("a@example.com", &[])).await;
+        assert_eq!(response.status(), 200);
+    }
+
+    // when
+    let response = app.post_subscriptions(form("a@example.com", &[])).await;
+
+    // then
+    assert_eq!(response.status(), 429);
+}
+
+#[tokio::test]
+async fn attempts_from_a_single_ip_address_are_rate_limited() {
+    // given
+    let app = TestApp::spawn_with(|config| {
+        config.subscribe_protection.attempts_per_ip_per_hour = 2;
+    })
+    .await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    for i in 0..2 {
+        let response = app
+            .post_subscriptions(form(&format!("{i}@example.com"), &[]))
+            .await;
+        assert_eq!(response.status(), 200);
+    }
+
+    // when
+    let response = app.post_subscriptions(form("other@example.com", &[])).await;
+
+    // then
+    assert_eq!(response.status(), 429);
+}
+
+#[tokio::test]
+async fn attempts_are_rate_limited_by_the_forwarded_address_behind_a_proxy() {
+    // given
+    let app = TestApp::spawn_with(|config| {
+        config.application.trusted_proxies = 1;
+        config.subscribe_protection.attempts_per_ip_per_hour = 1;
+    })
+    .await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_subscriptions_forwarded_for(form("a@example.com", &[]), "203.0.113.7")
+        .await;
+    assert_eq!(response.status(), 200);
+
+    // when
+    let other_client = app
+        .post_subscriptions_forwarded_for(form("b@example.com", &[]), "198.51.100.1")
+        .await;
+    let same_client = app
+        .post_subscriptions_forwarded_for(form("c@example.com", &[]), "203.0.113.7")
+        .await;
+
+    // then
+    assert_eq!(other_client.status(), 200);
+    assert_eq!(same_client.status(), 429);
+}
+
+#[tokio::test]
+async fn attempts_older_than_a_day_are_deleted() {
+    // given
+    let app = TestApp::spawn().await;
+    sqlx::query!(
+        r#"
+        INSERT INTO subscribe_attempts (attempted_at, ip_address, email)
+        VALUES
+            (now() - interval '25 hours', '203.0.113.7', 'old@example.com'),
+            (now() - interval '1 hour', '203.0.113.7', 'recent@example.com')
+        "#
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // when
+    let deleted = delete_expired_attempts(&app.db_pool).await.unwrap();
+
+    // then
+    assert_eq!(deleted, 1);
+    let emails = sqlx::query_scalar!("SELECT email FROM subscribe_attempts")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(emails, vec!["recent@example.com".to_string()]);
+}
+
+#[tokio::test]
+async fn confirmation_emails_to_a_pending_address_are_capped_per_day() {
+    // given
+    let app = TestApp::spawn_with(|config| {
+        config.subscribe_protection.confirmation_emails_per_day = 2;
+    })
+    .await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    for _ in 0..2 {
+        let response = app.post_subscriptions(form("a@example.com", &[])).await;
+        assert_eq!(response.status(), 200);
+    }
+
+    // when
+    let response = app.post_subscriptions(form("a@example.com", &[])).await;
+
+    // then
+    assert_eq!(response.status(), 429);
+}
+
+#[tokio::test]
+async fn proof_of_work_is_required_when_enabled() {
+    // given
+    let app = TestApp::spawn_with(|config| {
+        config.subscribe_protection.proof_of_work_difficulty_bits = Some(8);
+    })
+    .await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let challenge = hidden_input(&app.get_home_html().await, "pow_challenge");
+    let solution = solve_proof_of_work(&challenge, 8);
+
+    // when
+    let unsolved = app.post_subscriptions(form("a@example.com", &[])).await;
+    let solved = app
+        .post_subscriptions(form(
+            "a@example.com",
+            &[("pow_challenge", &challenge), ("pow_solution", &solution)],
+        ))
+        .await;
+
+    // then
+    assert_eq!(unsolved.status(), 400);
+    assert_eq!(solved.status(), 200);
+}
+
+#[tokio::test]
+async fn solved_proof_of_work_cannot_be_replayed() {
+    // given
+    let app = TestApp::spawn_with(|config| {
+        config.subscribe_protection.proof_of_work_difficulty_bits = Some(8);
+    })
+    .await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let challenge = hidden_input(&app.get_home_html().await, "pow_challenge");
+    let solution = solve_proof_of_work(&challenge, 8);
+    let fields = [
+        ("pow_challenge", challenge.as_str()),
+        ("pow_solution", &solution),
+    ];
+    let response = app.post_subscriptions(form("a@example.com", &fields)).await;
+    assert_eq!(response.status(), 200);
+
+    // when
+    let response = app.post_subscriptions(form("b@example.com", &fields)).await;
+
+    // then
+    assert_eq!(response.status(), 400);
+    assert_eq!(count_subscribers(&app).await, 1);
+}
+
+#[tokio::test]
+async fn form_cannot_be_submitted_twice() {
+    // given
+    let app = TestApp::spawn_with(|config| {
+        config.subscribe_protection.minimum_fill_time_milliseconds = 50;
+    })
+    .await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let form_token = hidden_input(&app.get_home_html().await, "form_token");
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let response = app
+        .post_subscriptions(form("a@example.com", &[("form_token", &form_token)]))
+        .await;
+    assert_eq!(response.status(), 200);
+
+    // when
+    let response = app
+        .post_subscriptions(form("b@example.com", &[("form_token", &form_token)]))
+        .await;
+
+    // then
+    assert_eq!(response.status(), 400);
+    assert_eq!(count_subscribers(&app).await, 1);
+}
+
+#[tokio::test]
+async fn rejected_attempts_are_shown_on_the_dashboard() {
+    // given
+    let app = TestApp::spawn().await;
+    app.post_subscriptions(form("bot@example.com", &[("website", "spam")]))
+        .await;
+
+    // when
+    let response = app
+        .post_login(&json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_redirect_to(&response, "/admin/dashboard");
+    let html_page = app.get_admin_dashboard_html().await;
+
+    // then
+    let honeypot_row = html_page
+        .split("<tr>")
+        .find(|row| row.contains("<td>honeypot</td>"))
+        .expect("Honeypot row not found on the dashboard");
+    assert!(honeypot_row.contains("<td>1</td>"));
+}
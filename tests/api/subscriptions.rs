@@ -5,7 +5,10 @@ use wiremock::{
     matchers::{method, path},
     Mock, ResponseTemplate,
 };
-use zero2prod::domain::{token_regex, SubscriptionStatus};
+use zero2prod::{
+    data_migrations::recanonicalise_emails,
+    domain::{token_regex, LocalPartRules, SubscriptionStatus},
+};
 
 #[tokio::test]
 async fn subscribe_returns_a_200_for_valid_form_data() {
@@ -293,7 +296,7 @@ async fn subscribe_fails_if_there_is_a_fatal_database_error() {
     sqlx::query!(
         r#"
         ALTER TABLE subscription_tokens
-        DROP COLUMN subscription_token
+        DROP COLUMN subscription_token_hash
         "#
     )
     .execute(&app.db_pool)
@@ -306,3 +309,207 @@ async fn subscribe_fails_if_there_is_a_fatal_database_error() {
     // then
     assert_eq!(response.status(), 500);
 }
+
+#[tokio::test]
+async fn subscribe_rejects_disposable_email_domains() {
+    // given
+    let app = TestApp::spawn().await;
+    let body = "name=Imi%C4%99%20Nazwisko&email=imie.nazwisko%40eu.Mailinator.com";
+
+    // when
+    let response = app.post_subscriptions(body.into()).await;
+
+    // then
+    assert_eq!(response.status(), 400);
+    assert!(response.text().await.unwrap().contains("disposable"));
+}
+
+#[tokio::test]
+async fn subscribe_detects_addresses_differing_only_in_domain_form() {
+    // given
+    let app = TestApp::spawn().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    for email in ["imie%40%C5%BB%C3%93%C5%81W.pl", "imie%40xn--w-uga1v8h.pl"] {
+        let body = format!("name=Imi%C4%99%20Nazwisko&email={email}");
+        let response = app.post_subscriptions(body).await;
+        assert_eq!(response.status(), 200);
+    }
+
+    // then
+    let subscribers = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch subscribers");
+
+    assert_eq!(subscribers.len(), 1);
+    assert_eq!(subscribers[0].email, "imie@xn--w-uga1v8h.pl");
+}
+
+#[tokio::test]
+async fn subscribe_identifies_subscribers_by_canonical_email() {
+    // given
+    let app = TestApp::spawn().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    for email in ["Imie.Nazwisko%40Example.com", "imie.nazwisko%40example.COM"] {
+        let body = format!("name=Imi%C4%99%20Nazwisko&email={email}");
+        let response = app.post_subscriptions(body).await;
+        assert_eq!(response.status(), 200);
+    }
+
+    // then
+    let subscribers = sqlx::query!("SELECT email, canonical_email FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch subscribers");
+
+    assert_eq!(subscribers.len(), 1);
+    assert_eq!(subscribers[0].email, "Imie.Nazwisko@example.com");
+    assert_eq!(subscribers[0].canonical_email, "imie.nazwisko@example.com");
+}
+
+#[tokio::test]
+async fn subscribe_applies_configured_local_part_rules() {
+    // given
+    let app = TestApp::spawn_with(|config| {
+        config.local_part_rules.strip_plus_tags = true;
+    })
+    .await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let body = "name=Imi%C4%99%20Nazwisko&email=imie%2Bnews%40example.com";
+    app.post_subscriptions(body.into()).await;
+    sqlx::query!("UPDATE subscriptions SET status = 'confirmed'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // when
+    let body = "name=Imi%C4%99%20Nazwisko&email=imie%2Bother%40example.com";
+    let response = app.post_subscriptions(body.into()).await;
+
+    // then
+    assert_eq!(response.status(), 422);
+}
+
+async fn insert_subscriber(app: &TestApp, email: &str, canonical_email: &str, status: &str) {
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, canonical_email, name, subscribed_at, status)
+        VALUES (gen_random_uuid(), $1, $2, 'Imię Nazwisko', now(), $3)
+        "#,
+        email,
+        canonical_email,
+        status,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn canonical_emails_backfilled_by_the_migration_are_recomputed() {
+    // given
+    let app = TestApp::spawn().await;
+    insert_subscriber(&app, "Imie@Żółw.pl", "imie@żółw.pl", "confirmed").await;
+    sqlx::query!("UPDATE canonical_email_rules SET rules = ''")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // when
+    let mut connection = app.db_pool.acquire().await.unwrap();
+    recanonicalise_emails(&mut connection, &LocalPartRules::default())
+        .await
+        .unwrap();
+
+    // then
+    let canonical_email = sqlx::query!("SELECT canonical_email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .canonical_email;
+    assert_eq!(canonical_email, "imie@xn--w-uga1v8h.pl");
+}
+
+#[tokio::test]
+async fn subscribers_sharing_a_mailbox_under_new_rules_are_merged() {
+    // given
+    let app = TestApp::spawn().await;
+    insert_subscriber(
+        &app,
+        "imie@example.com",
+        "imie@example.com",
+        "pending_confirmation",
+    )
+    .await;
+    insert_subscriber(
+        &app,
+        "imie+news@example.com",
+        "imie+news@example.com",
+        "confirmed",
+    )
+    .await;
+    let rules = LocalPartRules {
+        strip_plus_tags: true,
+        ..LocalPartRules::default()
+    };
+
+    // when
+    let mut connection = app.db_pool.acquire().await.unwrap();
+    recanonicalise_emails(&mut connection, &rules)
+        .await
+        .unwrap();
+
+    // then
+    let saved = sqlx::query!("SELECT email, canonical_email, status FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.len(), 1);
+    assert_eq!(saved[0].email, "imie+news@example.com");
+    assert_eq!(saved[0].canonical_email, "imie@example.com");
+    assert_eq!(saved[0].status, "confirmed");
+}
+
+#[tokio::test]
+async fn canonical_emails_are_left_alone_while_the_rules_are_unchanged() {
+    // given
+    let app = TestApp::spawn().await;
+    insert_subscriber(&app, "Imie@example.com", "Imie@example.com", "confirmed").await;
+
+    // when
+    let mut connection = app.db_pool.acquire().await.unwrap();
+    recanonicalise_emails(&mut connection, &LocalPartRules::default())
+        .await
+        .unwrap();
+
+    // then
+    let canonical_email = sqlx::query!("SELECT canonical_email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .canonical_email;
+    assert_eq!(canonical_email, "Imie@example.com");
+}
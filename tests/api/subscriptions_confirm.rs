@@ -1,9 +1,11 @@
-use crate::helpers::TestApp;
-use claims::assert_some_eq;
+use crate::helpers::{create_unconfirmed_subscriber, TestApp};
+use claims::{assert_none, assert_some_eq};
+use uuid::Uuid;
 use wiremock::{
     matchers::{method, path},
     Mock, ResponseTemplate,
 };
+use zero2prod::{configuration::get_configuration, data_migrations::hash_plaintext_tokens};
 
 #[tokio::test]
 async fn confirmation_without_token_is_rejected_with_a_400() {
@@ -168,3 +170,87 @@ async fn clicking_on_the_confirmation_link_deletes_subscription_tokens() {
 
     assert_eq!(result.len(), 0);
 }
+
+#[tokio::test]
+async fn subscription_tokens_are_not_stored_in_plaintext() {
+    // given
+    let app = TestApp::spawn().await;
+
+    // when
+    let links = create_unconfirmed_subscriber(&app).await;
+
+    // then
+    let (_, token) = links
+        .html
+        .query_pairs()
+        .find(|(key, _)| key == "subscription_token")
+        .unwrap();
+    let row =
+        sqlx::query!("SELECT subscription_token, subscription_token_hash FROM subscription_tokens")
+            .fetch_one(&app.db_pool)
+            .await
+            .expect("Failed to fetch subscription token");
+
+    assert_none!(row.subscription_token);
+    let hash = row.subscription_token_hash.unwrap();
+    assert!(!hash.contains(token.as_ref()));
+}
+
+#[tokio::test]
+async fn plaintext_tokens_issued_before_hashing_can_still_be_confirmed() {
+    // given
+    let app = TestApp::spawn().await;
+    let subscriber_id = Uuid::new_v4();
+    let token = "PlaintextToken1234567890a";
+
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, canonical_email, name, subscribed_at, status)
+        VALUES (
+            $1,
+            'legacy@example.com',
+            'legacy@example.com',
+            'Legacy',
+            now(),
+            'pending_confirmation'
+        )
+        "#,
+        subscriber_id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscription_tokens (subscription_token, subscriber_id)
+        VALUES ($1, $2)
+        "#,
+        token,
+        subscriber_id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let secret = get_configuration().unwrap().application.hmac_secret;
+    let mut connection = app.db_pool.acquire().await.unwrap();
+    hash_plaintext_tokens(&mut connection, &secret)
+        .await
+        .unwrap();
+
+    // when
+    let response = app.confirm_subscription(token).await;
+
+    // then
+    assert_eq!(response.status(), 200);
+
+    let status = sqlx::query!(
+        "SELECT status FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .status;
+    assert_eq!(status, "confirmed");
+}
@@ -0,0 +1,343 @@
+use crate::helpers::{
+    assert_problem, assert_redirect_to, create_confirmed_subscriber, when_sending_an_email, TestApp,
+};
+use serde_json::{json, Value};
+use uuid::Uuid;
+use wiremock::ResponseTemplate;
+
+struct StoredSubscriber {
+    id: Uuid,
+    email: String,
+}
+
+async fn stored_subscribers(app: &TestApp) -> Vec<StoredSubscriber> {
+    sqlx::query_as!(
+        StoredSubscriber,
+        "SELECT id, email FROM subscriptions ORDER BY subscribed_at"
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap()
+}
+
+async fn sent_emails(app: &TestApp) -> Vec<Value> {
+    app.email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .map(|request| serde_json::from_slice(&request.body).unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn email_is_changed_only_after_the_new_address_is_confirmed() {
+    // given
+    let app = TestApp::spawn().await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber = stored_subscribers(&app).await.pop().unwrap();
+    let token = app.preferences_token(subscriber.id);
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let response = app
+        .post_change_email(&token, "nowy.adres@example.com")
+        .await;
+
+    // then
+    assert_redirect_to(
+        &response,
+        &format!("/subscriptions/preferences?token={token}"),
+    );
+    let html_page = app.get_preferences_html(&token).await;
+    assert!(html_page.contains("We have sent a confirmation link to nowy.adres@example.com"));
+    assert_eq!(
+        stored_subscribers(&app).await[0].email,
+        subscriber.email,
+        "The address changed before confirmation"
+    );
+
+    let confirmation = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: Value = serde_json::from_slice(&confirmation.body).unwrap();
+    assert_eq!(body["To"], "nowy.adres@example.com");
+
+    // when
+    let links = app.get_confirmation_links(&confirmation);
+    let response = reqwest::get(links.html).await.unwrap();
+
+    // then
+    assert_eq!(response.status(), 200);
+    let saved = sqlx::query!("SELECT id, email, canonical_email, status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.id, subscriber.id);
+    assert_eq!(saved.email, "nowy.adres@example.com");
+    assert_eq!(saved.canonical_email, "nowy.adres@example.com");
+    assert_eq!(saved.status, "confirmed");
+
+    let notification = sent_emails(&app).await.pop().unwrap();
+    assert_eq!(notification["To"], subscriber.email.as_str());
+    assert!(notification["TextBody"]
+        .as_str()
+        .unwrap()
+        .contains("nowy.adres@example.com"));
+}
+
+#[tokio::test]
+async fn confirmation_link_of_an_email_change_works_once() {
+    // given
+    let app = TestApp::spawn().await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber = stored_subscribers(&app).await.pop().unwrap();
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_change_email(
+        &app.preferences_token(subscriber.id),
+        "nowy.adres@example.com",
+    )
+    .await;
+    let confirmation = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let links = app.get_confirmation_links(&confirmation);
+    reqwest::get(links.html.clone()).await.unwrap();
+
+    // when
+    let response = reqwest::get(links.html).await.unwrap();
+
+    // then
+    assert_eq!(response.status(), 401);
+}
+
+#[tokio::test]
+async fn email_cannot_be_changed_to_an_address_already_subscribed() {
+    // given
+    let app = TestApp::spawn().await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    let subscribers = stored_subscribers(&app).await;
+    let token = app.preferences_token(subscribers[0].id);
+    let emails_before = sent_emails(&app).await.len();
+
+    // when
+    let response = app
+        .post_change_email(&token, &subscribers[1].email.to_uppercase())
+        .await;
+
+    // then
+    assert_redirect_to(
+        &response,
+        &format!("/subscriptions/preferences?token={token}"),
+    );
+    let html_page = app.get_preferences_html(&token).await;
+    assert!(html_page.contains("is already subscribed"));
+    assert_eq!(sent_emails(&app).await.len(), emails_before);
+}
+
+#[tokio::test]
+async fn email_change_requires_a_valid_preferences_token() {
+    // given
+    let app = TestApp::spawn().await;
+
+    // when
+    let response = app
+        .post_change_email("not-a-token", "nowy.adres@example.com")
+        .await;
+
+    // then
+    assert_eq!(response.status(), 401);
+}
+
+#[tokio::test]
+async fn admins_can_request_an_email_change_through_the_api() {
+    // given
+    let app = TestApp::spawn().await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber = stored_subscribers(&app).await.pop().unwrap();
+    let token = app.create_api_token(&["subscribers:write"]).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let response = app
+        .post_api(
+            &format!("/api/v1/subscribers/{}/email", subscriber.id),
+            &token,
+            &json!({ "email": "nowy.adres@example.com" }),
+        )
+        .await;
+
+    // then
+    assert_eq!(response.status(), 202);
+    let event_type =
+        sqlx::query!("SELECT event_type FROM audit_events ORDER BY occurred_at DESC LIMIT 1")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap()
+            .event_type;
+    assert_eq!(event_type, "subscriber_email_change_requested");
+}
+
+#[tokio::test]
+async fn api_email_change_of_an_unknown_subscriber_returns_404() {
+    // given
+    let app = TestApp::spawn().await;
+    let token = app.create_api_token(&["subscribers:write"]).await;
+
+    // when
+    let response = app
+        .post_api(
+            &format!("/api/v1/subscribers/{}/email", Uuid::new_v4()),
+            &token,
+            &json!({ "email": "nowy.adres@example.com" }),
+        )
+        .await;
+
+    // then
+    assert_problem(&response, 404);
+}
+
+#[tokio::test]
+async fn email_changes_are_capped_per_day() {
+    // given
+    let app = TestApp::spawn_with(|config| {
+        config.subscribe_protection.confirmation_emails_per_day = 2;
+    })
+    .await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber = stored_subscribers(&app).await.pop().unwrap();
+    let token = app.preferences_token(subscriber.id);
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    for i in 0..2 {
+        app.post_change_email(&token, &format!("nowy.adres.{i}@example.com"))
+            .await;
+    }
+    let emails_before = sent_emails(&app).await.len();
+
+    // when
+    app.post_change_email(&token, "nowy.adres.2@example.com")
+        .await;
+
+    // then
+    let html_page = app.get_preferences_html(&token).await;
+    assert!(html_page.contains("Too many confirmation emails"));
+    assert_eq!(sent_emails(&app).await.len(), emails_before);
+}
+
+#[tokio::test]
+async fn email_changes_count_towards_the_ip_address_rate_limit() {
+    // given
+    let app = TestApp::spawn_with(|config| {
+        config.subscribe_protection.attempts_per_ip_per_hour = 2;
+    })
+    .await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber = stored_subscribers(&app).await.pop().unwrap();
+    let token = app.preferences_token(subscriber.id);
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_change_email(&token, "nowy.adres@example.com")
+        .await;
+    let emails_before = sent_emails(&app).await.len();
+
+    // when
+    app.post_change_email(&token, "inny.adres@example.com")
+        .await;
+
+    // then
+    let html_page = app.get_preferences_html(&token).await;
+    assert!(html_page.contains("Too many subscription attempts from this address"));
+    assert_eq!(sent_emails(&app).await.len(), emails_before);
+}
+
+#[tokio::test]
+async fn email_change_expires_after_a_day() {
+    // given
+    let app = TestApp::spawn().await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber = stored_subscribers(&app).await.pop().unwrap();
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_change_email(
+        &app.preferences_token(subscriber.id),
+        "nowy.adres@example.com",
+    )
+    .await;
+    let confirmation = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let links = app.get_confirmation_links(&confirmation);
+    sqlx::query!("UPDATE email_changes SET requested_at = now() - interval '25 hours'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // when
+    let response = reqwest::get(links.html).await.unwrap();
+
+    // then
+    assert_eq!(response.status(), 401);
+    assert_eq!(stored_subscribers(&app).await[0].email, subscriber.email);
+}
+
+#[tokio::test]
+async fn later_email_change_replaces_an_unconfirmed_one() {
+    // given
+    let app = TestApp::spawn().await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber = stored_subscribers(&app).await.pop().unwrap();
+    let token = app.preferences_token(subscriber.id);
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_change_email(&token, "nowy.adres@example.com")
+        .await;
+    app.post_change_email(&token, "inny.adres@example.com")
+        .await;
+    let requests = app.email_server.received_requests().await.unwrap();
+    let earlier = app.get_confirmation_links(&requests[requests.len() - 2]);
+    let later = app.get_confirmation_links(&requests[requests.len() - 1]);
+
+    // when
+    let earlier_response = reqwest::get(earlier.html).await.unwrap();
+    let later_response = reqwest::get(later.html).await.unwrap();
+
+    // then
+    assert_eq!(earlier_response.status(), 401);
+    assert_eq!(later_response.status(), 200);
+    assert_eq!(
+        stored_subscribers(&app).await[0].email,
+        "inny.adres@example.com"
+    );
+}
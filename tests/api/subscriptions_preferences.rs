@@ -0,0 +1,319 @@
+use crate::helpers::{
+    assert_redirect_to, create_confirmed_subscriber, when_sending_an_email, TestApp,
+};
+use linkify::{LinkFinder, LinkKind};
+use serde_json::{json, Value};
+use uuid::Uuid;
+use wiremock::{matchers::any, Mock, ResponseTemplate};
+
+async fn subscriber_id(app: &TestApp) -> Uuid {
+    sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id
+}
+
+/// Emails sent to subscribers, other than confirmation emails.
+async fn delivered_issues(app: &TestApp) -> Vec<Value> {
+    app.email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .map(|request| serde_json::from_slice::<Value>(&request.body).unwrap())
+        .filter(|email| email["Subject"] != "Welcome!")
+        .collect()
+}
+
+fn preferences_form(token: &str) -> Value {
+    json!({
+        "token": token,
+        "name": "Imię Nazwisko",
+        "content_format": "html",
+        "digest_frequency": "immediate",
+        "pause": "0",
+    })
+}
+
+#[tokio::test]
+async fn delivered_issues_link_to_the_preferences_page() {
+    // given
+    let app = TestApp::spawn().await;
+    create_confirmed_subscriber(&app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.publish_issue("Newsletter Title").await;
+    app.dispatch_all_pending_emails().await;
+
+    let email = delivered_issues(&app).await.pop().unwrap();
+    let text_body = email["TextBody"].as_str().unwrap();
+    let link = LinkFinder::new()
+        .links(text_body)
+        .filter(|l| *l.kind() == LinkKind::Url)
+        .find(|l| l.as_str().contains("/subscriptions/preferences"))
+        .expect("No preferences link in the delivered issue");
+    let mut link = reqwest::Url::parse(link.as_str()).unwrap();
+    link.set_port(Some(app.address.port())).unwrap();
+
+    // when
+    let response = reqwest::get(link).await.unwrap();
+
+    // then
+    assert_eq!(response.status(), 200);
+    assert!(response.text().await.unwrap().contains("Save preferences"));
+    assert!(email["HtmlBody"]
+        .as_str()
+        .unwrap()
+        .contains("/subscriptions/preferences?token="));
+}
+
+#[tokio::test]
+async fn preferences_are_rejected_with_a_401_for_invalid_tokens() {
+    // given
+    let app = TestApp::spawn().await;
+    create_confirmed_subscriber(&app).await;
+    let token = app.preferences_token(subscriber_id(&app).await);
+    let (_, signature) = token.split_once('.').unwrap();
+    let forged = format!("{}.{signature}", Uuid::new_v4().simple());
+
+    for token in ["", "not-a-token", &forged] {
+        // when
+        let get_response = app.get_preferences(token).await;
+        let post_response = app.post_preferences(&preferences_form(token)).await;
+        let unsubscribe_response = app.post_unsubscribe(token).await;
+
+        // then
+        assert_eq!(get_response.status(), 401, "{token}");
+        assert_eq!(post_response.status(), 401, "{token}");
+        assert_eq!(unsubscribe_response.status(), 401, "{token}");
+    }
+}
+
+#[tokio::test]
+async fn subscribers_can_update_their_preferences() {
+    // given
+    let app = TestApp::spawn().await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber_id = subscriber_id(&app).await;
+    let token = app.preferences_token(subscriber_id);
+
+    // when
+    let response = app
+        .post_preferences(&json!({
+            "token": token,
+            "name": "Nowe Imię",
+            "content_format": "plain_text",
+            "digest_frequency": "weekly",
+            "pause": "30",
+        }))
+        .await;
+
+    // then
+    assert_redirect_to(
+        &response,
+        &format!("/subscriptions/preferences?token={token}"),
+    );
+    let html_page = app.get_preferences_html(&token).await;
+    assert!(html_page.contains("Your preferences have been updated."));
+    assert!(html_page.contains("Paused until"));
+
+    let saved = sqlx::query!(
+        r#"
+        SELECT name, content_format, digest_frequency, paused_until
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(saved.name, "Nowe Imię");
+    assert_eq!(saved.content_format, "plain_text");
+    assert_eq!(saved.digest_frequency, "weekly");
+    assert!(saved.paused_until.is_some());
+}
+
+#[tokio::test]
+async fn invalid_preferences_are_not_saved() {
+    // given
+    let app = TestApp::spawn().await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber_id = subscriber_id(&app).await;
+    let token = app.preferences_token(subscriber_id);
+    let test_cases = [
+        ("name", "<script>", "forbidden characters"),
+        ("content_format", "pdf", "not a valid variant"),
+        ("digest_frequency", "hourly", "not a valid variant"),
+        ("pause", "1000", "not a valid pause period"),
+    ];
+
+    for (field, value, error) in test_cases {
+        let mut form = preferences_form(&token);
+        form[field] = json!(value);
+
+        // when
+        let response = app.post_preferences(&form).await;
+
+        // then
+        assert_redirect_to(
+            &response,
+            &format!("/subscriptions/preferences?token={token}"),
+        );
+        let html_page = app.get_preferences_html(&token).await;
+        assert!(html_page.contains(error), "{field}: {html_page}");
+    }
+
+    let saved = sqlx::query!(
+        "SELECT content_format, digest_frequency FROM subscriptions WHERE id = $1",
+        subscriber_id,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(saved.content_format, "html");
+    assert_eq!(saved.digest_frequency, "immediate");
+}
+
+#[tokio::test]
+async fn plain_text_subscribers_receive_issues_without_html() {
+    // given
+    let app = TestApp::spawn().await;
+    create_confirmed_subscriber(&app).await;
+    let token = app.preferences_token(subscriber_id(&app).await);
+    let mut form = preferences_form(&token);
+    form["content_format"] = json!("plain_text");
+    app.post_preferences(&form).await;
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    app.publish_issue("Newsletter Title").await;
+    app.dispatch_all_pending_emails().await;
+
+    // then
+    let email = delivered_issues(&app).await.pop().unwrap();
+    assert!(email.get("HtmlBody").is_none());
+    assert!(email["TextBody"]
+        .as_str()
+        .unwrap()
+        .contains("Newsletter body as text."));
+}
+
+#[tokio::test]
+async fn issues_are_skipped_while_delivery_is_paused() {
+    // given
+    let app = TestApp::spawn().await;
+    create_confirmed_subscriber(&app).await;
+    let token = app.preferences_token(subscriber_id(&app).await);
+    let mut form = preferences_form(&token);
+    form["pause"] = json!("7");
+    app.post_preferences(&form).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    app.publish_issue("Newsletter Title").await;
+    app.dispatch_all_pending_emails().await;
+
+    // then
+    let outcome = sqlx::query!("SELECT outcome FROM issue_deliveries")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .outcome;
+    assert_eq!(outcome, "skipped");
+}
+
+#[tokio::test]
+async fn unsubscribed_subscribers_receive_no_issues() {
+    // given
+    let app = TestApp::spawn().await;
+    create_confirmed_subscriber(&app).await;
+    let token = app.preferences_token(subscriber_id(&app).await);
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let response = app.post_unsubscribe(&token).await;
+
+    // then
+    assert_redirect_to(
+        &response,
+        &format!("/subscriptions/preferences?token={token}"),
+    );
+    let html_page = app.get_preferences_html(&token).await;
+    assert!(html_page.contains("You have been unsubscribed."));
+    assert!(html_page.contains("You are not subscribed"));
+
+    app.publish_issue("Newsletter Title").await;
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn digest_subscribers_receive_queued_issues_together_when_due() {
+    // given
+    let app = TestApp::spawn().await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber_id = subscriber_id(&app).await;
+    let token = app.preferences_token(subscriber_id);
+    let mut form = preferences_form(&token);
+    form["digest_frequency"] = json!("weekly");
+    app.post_preferences(&form).await;
+    sqlx::query!(
+        "UPDATE subscriptions SET last_digest_sent_at = now() WHERE id = $1",
+        subscriber_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.publish_issue("First Issue").await;
+    app.publish_issue("Second Issue").await;
+
+    // when
+    app.dispatch_all_pending_emails().await;
+
+    // then
+    assert!(delivered_issues(&app).await.is_empty());
+
+    // when
+    sqlx::query!(
+        "UPDATE subscriptions SET last_digest_sent_at = now() - interval '8 days' WHERE id = $1",
+        subscriber_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    // then
+    let emails = delivered_issues(&app).await;
+    assert_eq!(emails.len(), 1);
+    assert_eq!(emails[0]["Subject"], "Your weekly digest: 2 new issues");
+    let text_body = emails[0]["TextBody"].as_str().unwrap();
+    assert!(text_body.contains("First Issue"));
+    assert!(text_body.contains("Second Issue"));
+}
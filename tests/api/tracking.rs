@@ -0,0 +1,189 @@
+use crate::helpers::{create_confirmed_subscriber, when_sending_an_email, TestApp};
+use linkify::{LinkFinder, LinkKind};
+use serde_json::{json, Value};
+use uuid::Uuid;
+use wiremock::ResponseTemplate;
+
+fn tracked_issue_body() -> Value {
+    json!({
+        "title": "Newsletter Title",
+        "html_content": r#"<p>Read <a href="https://example.com/article?a=1&amp;b=2">the article</a>.</p>"#,
+        "text_content": "Read the article at https://example.com/article?a=1&b=2.",
+        "track_opens": true,
+        "track_clicks": true,
+    })
+}
+
+/// Publishes an issue through the API and delivers it, returning the issue id
+/// and the HTML body of the email.
+async fn publish_and_deliver(app: &TestApp, token: &str, body: &Value) -> (String, String) {
+    create_confirmed_subscriber(app).await;
+    when_sending_an_email()
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_api_issue(token, &Uuid::new_v4().to_string(), body)
+        .await;
+    assert_eq!(response.status(), 201);
+    let issue: Value = response.json().await.unwrap();
+    let issue_id = issue["issue_id"].as_str().unwrap().to_string();
+
+    app.dispatch_all_pending_emails().await;
+
+    let request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let email: Value = serde_json::from_slice(&request.body).unwrap();
+    let html_body = email["HtmlBody"].as_str().unwrap().to_string();
+
+    (issue_id, html_body)
+}
+
+/// The tracking link with the given path prefix, pointed at the test app.
+fn tracking_link(app: &TestApp, html_body: &str, prefix: &str) -> reqwest::Url {
+    let mut link = LinkFinder::new()
+        .links(html_body)
+        .filter(|l| *l.kind() == LinkKind::Url)
+        .map(|l| reqwest::Url::parse(l.as_str()).unwrap())
+        .find(|l| l.path().starts_with(prefix))
+        .unwrap_or_else(|| panic!("No `{prefix}` link in {html_body}"));
+    link.set_port(Some(app.address.port())).unwrap();
+    link
+}
+
+#[tokio::test]
+async fn opens_and_clicks_of_tracked_issues_are_counted_in_stats() {
+    // given
+    let app = TestApp::spawn().await;
+    let token = app.create_api_token(&["issues:read", "issues:write"]).await;
+    let (issue_id, html_body) = publish_and_deliver(&app, &token, &tracked_issue_body()).await;
+    assert!(!html_body.contains("https://example.com/article"));
+
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+
+    // when
+    let pixel = client
+        .get(tracking_link(&app, &html_body, "/o/"))
+        .send()
+        .await
+        .unwrap();
+    let redirect = client
+        .get(tracking_link(&app, &html_body, "/r/"))
+        .send()
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(pixel.status(), 200);
+    assert_eq!(pixel.headers()["Content-Type"], "image/gif");
+    assert_eq!(redirect.status(), 302);
+    assert_eq!(
+        redirect.headers()["Location"],
+        "https://example.com/article?a=1&b=2"
+    );
+
+    let response = app
+        .get_api(&format!("/api/v1/issues/{issue_id}/stats"), &token)
+        .await;
+    let stats: Value = response.json().await.unwrap();
+    assert_eq!(stats["opens"], 1);
+    assert_eq!(stats["unique_opens"], 1);
+    assert_eq!(stats["clicks"], 1);
+    assert_eq!(stats["unique_clicks"], 1);
+}
+
+#[tokio::test]
+async fn issues_are_sent_untouched_when_tracking_is_disabled_globally() {
+    // given
+    let app = TestApp::spawn_with(|config| config.tracking.enabled = false).await;
+    let token = app.create_api_token(&["issues:read", "issues:write"]).await;
+
+    // when
+    let (issue_id, html_body) = publish_and_deliver(&app, &token, &tracked_issue_body()).await;
+
+    // then
+    assert!(html_body.contains(r#"href="https://example.com/article?a=1&amp;b=2""#));
+    assert!(!html_body.contains("/o/"));
+
+    let response = app
+        .get_api(&format!("/api/v1/issues/{issue_id}"), &token)
+        .await;
+    let issue: Value = response.json().await.unwrap();
+    assert_eq!(issue["track_opens"], false);
+    assert_eq!(issue["track_clicks"], false);
+}
+
+#[tokio::test]
+async fn issues_are_not_tracked_unless_asked_to() {
+    // given
+    let app = TestApp::spawn().await;
+    let token = app.create_api_token(&["issues:read", "issues:write"]).await;
+    let mut body = tracked_issue_body();
+    body.as_object_mut().unwrap().remove("track_opens");
+    body.as_object_mut().unwrap().remove("track_clicks");
+
+    // when
+    let (_, html_body) = publish_and_deliver(&app, &token, &body).await;
+
+    // then
+    assert!(html_body.contains(r#"href="https://example.com/article?a=1&amp;b=2""#));
+    assert!(!html_body.contains("/o/"));
+}
+
+#[tokio::test]
+async fn invalid_click_tokens_are_rejected_with_404() {
+    // given
+    let app = TestApp::spawn().await;
+
+    // when
+    let response = app
+        .request(reqwest::Method::GET, "/r/not-a-valid-token")
+        .await;
+
+    // then
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn the_pixel_is_served_for_invalid_tokens() {
+    // given
+    let app = TestApp::spawn().await;
+
+    // when
+    let response = app
+        .request(reqwest::Method::GET, "/o/not-a-valid-token")
+        .await;
+
+    // then
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["Content-Type"], "image/gif");
+}
+
+#[tokio::test]
+async fn newsletter_form_offers_tracking_only_when_enabled() {
+    // given
+    let enabled = TestApp::spawn().await;
+    let disabled = TestApp::spawn_with(|config| config.tracking.enabled = false).await;
+
+    for (app, offered) in [(&enabled, true), (&disabled, false)] {
+        app.log_in(&app.test_user.username, &app.test_user.password)
+            .await;
+
+        // when
+        let html_page = app.get_newsletter_form_html().await;
+
+        // then
+        assert_eq!(html_page.contains(r#"name="track_opens""#), offered);
+        assert_eq!(html_page.contains(r#"name="track_clicks""#), offered);
+    }
+}
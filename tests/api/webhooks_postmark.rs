@@ -0,0 +1,247 @@
+use crate::helpers::{create_confirmed_subscriber, TestApp};
+use secrecy::Secret;
+use serde_json::{json, Value};
+use zero2prod::configuration::{get_configuration, Environment};
+
+async fn subscriber_email(app: &TestApp) -> String {
+    sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email
+}
+
+async fn subscriber_status(app: &TestApp) -> String {
+    sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .status
+}
+
+fn bounce(id: i64, bounce_type: &str, email: &str) -> Value {
+    json!({
+        "RecordType": "Bounce",
+        "ID": id,
+        "Type": bounce_type,
+        "TypeCode": 1,
+        "MessageID": "883953f4-6105-42a2-a16a-77a8eac79483",
+        "Email": email,
+        "BouncedAt": "2026-10-18T16:33:54.9070259Z",
+        "Description": "The server was unable to deliver your message.",
+    })
+}
+
+#[tokio::test]
+async fn webhooks_without_valid_credentials_are_rejected() {
+    // given
+    let app = TestApp::spawn().await;
+    let body = bounce(1, "HardBounce", "imie.nazwisko@example.com");
+    let url = format!("http://{}/webhooks/postmark", app.address);
+    let client = reqwest::Client::new();
+    let requests = [
+        client.post(&url),
+        client
+            .post(&url)
+            .basic_auth(&app.postmark_webhook.username, Some("wrong-secret")),
+        client
+            .post(&url)
+            .basic_auth("someone-else", Some("wrong-secret")),
+        client.post(&url).header("X-Webhook-Secret", "wrong-secret"),
+    ];
+
+    for request in requests {
+        // when
+        let response = request.json(&body).send().await.unwrap();
+
+        // then
+        assert_eq!(response.status(), 401);
+    }
+    let events = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM email_events")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(events, 0);
+}
+
+#[tokio::test]
+async fn webhooks_authenticated_with_a_shared_secret_are_accepted() {
+    // given
+    let app = TestApp::spawn().await;
+    let body = bounce(1, "HardBounce", "imie.nazwisko@example.com");
+
+    // when
+    let response = reqwest::Client::new()
+        .post(format!("http://{}/webhooks/postmark", app.address))
+        .header("X-Webhook-Secret", app.postmark_webhook_secret())
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(response.status(), 200);
+}
+
+#[tokio::test]
+async fn hard_bounce_suppresses_the_subscriber() {
+    // given
+    let app = TestApp::spawn().await;
+    create_confirmed_subscriber(&app).await;
+    let email = subscriber_email(&app).await;
+
+    // when
+    let response = app
+        .post_postmark_webhook(&bounce(1, "HardBounce", &email))
+        .await;
+
+    // then
+    assert_eq!(response.status(), 200);
+    assert_eq!(subscriber_status(&app).await, "bounced");
+    let event = sqlx::query!("SELECT event_type, provider_message_id FROM email_events")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(event.event_type, "hard_bounce");
+    assert_eq!(
+        event.provider_message_id.as_deref(),
+        Some("883953f4-6105-42a2-a16a-77a8eac79483")
+    );
+}
+
+#[tokio::test]
+async fn soft_bounces_suppress_the_subscriber_once_they_reach_the_threshold() {
+    // given
+    let app = TestApp::spawn().await;
+    create_confirmed_subscriber(&app).await;
+    let email = subscriber_email(&app).await;
+    let threshold = app.postmark_webhook.soft_bounce_threshold;
+
+    for id in 1..threshold {
+        app.post_postmark_webhook(&bounce(id, "SoftBounce", &email))
+            .await;
+        // Postmark retries webhooks, which must not be counted twice.
+        app.post_postmark_webhook(&bounce(id, "SoftBounce", &email))
+            .await;
+    }
+
+    // then
+    assert_eq!(subscriber_status(&app).await, "confirmed");
+
+    // when
+    app.post_postmark_webhook(&bounce(threshold, "SoftBounce", &email))
+        .await;
+
+    // then
+    assert_eq!(subscriber_status(&app).await, "bounced");
+}
+
+#[tokio::test]
+async fn spam_complaint_suppresses_the_subscriber() {
+    // given
+    let app = TestApp::spawn().await;
+    create_confirmed_subscriber(&app).await;
+    let email = subscriber_email(&app).await;
+    let mut complaint = bounce(1, "SpamComplaint", &email);
+    complaint["RecordType"] = json!("SpamComplaint");
+
+    // when
+    let response = app.post_postmark_webhook(&complaint).await;
+
+    // then
+    assert_eq!(response.status(), 200);
+    assert_eq!(subscriber_status(&app).await, "complained");
+}
+
+#[tokio::test]
+async fn manual_suppression_unsubscribes_the_subscriber() {
+    // given
+    let app = TestApp::spawn().await;
+    create_confirmed_subscriber(&app).await;
+    let email = subscriber_email(&app).await;
+
+    // when
+    let response = app
+        .post_postmark_webhook(&json!({
+            "RecordType": "SubscriptionChange",
+            "MessageID": "883953f4-6105-42a2-a16a-77a8eac79483",
+            "ChangedAt": "2026-10-18T16:33:54.9070259Z",
+            "Recipient": email,
+            "SuppressSending": true,
+            "SuppressionReason": "ManualSuppression",
+        }))
+        .await;
+
+    // then
+    assert_eq!(response.status(), 200);
+    assert_eq!(subscriber_status(&app).await, "unsubscribed");
+}
+
+#[tokio::test]
+async fn unsupported_records_are_acknowledged_and_ignored() {
+    // given
+    let app = TestApp::spawn().await;
+    create_confirmed_subscriber(&app).await;
+    let email = subscriber_email(&app).await;
+    let test_cases = [
+        json!({ "RecordType": "Open", "Recipient": email }),
+        bounce(1, "AutoResponder", &email),
+    ];
+
+    for body in test_cases {
+        // when
+        let response = app.post_postmark_webhook(&body).await;
+
+        // then
+        assert_eq!(response.status(), 200);
+    }
+    assert_eq!(subscriber_status(&app).await, "confirmed");
+    let events = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM email_events")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(events, 0);
+}
+
+#[tokio::test]
+async fn malformed_payloads_are_rejected_with_a_400() {
+    // given
+    let app = TestApp::spawn().await;
+
+    // when
+    let response = app
+        .post_postmark_webhook(&json!({ "RecordType": "Bounce", "Email": 42 }))
+        .await;
+
+    // then
+    assert_eq!(response.status(), 400);
+}
+
+#[test]
+fn placeholder_secret_is_refused_in_production() {
+    // given
+    let settings = get_configuration().unwrap().postmark_webhook;
+
+    // when
+    let local = settings.credentials(Environment::Local);
+    let production = settings.credentials(Environment::Production);
+
+    // then
+    assert!(local.is_ok());
+    assert!(production.is_err());
+}
+
+#[test]
+fn configured_secret_is_accepted_in_production() {
+    // given
+    let mut settings = get_configuration().unwrap().postmark_webhook;
+    settings.secret = Secret::new("a-secret-only-postmark-knows".to_string());
+
+    // when
+    let result = settings.credentials(Environment::Production);
+
+    // then
+    assert!(result.is_ok());
+}